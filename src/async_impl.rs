@@ -1,2 +1,9 @@
+pub mod combiner;
+pub mod drbg;
 pub mod pqkd;
-pub mod request_builder;
\ No newline at end of file
+mod random;
+pub mod relay;
+mod sync;
+pub mod request_builder;
+pub mod transport;
+pub mod watcher;
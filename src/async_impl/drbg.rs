@@ -0,0 +1,89 @@
+use super::pqkd::PqkdClient;
+use crate::drbg::{
+    check_entropy_len, DrbgConfig, HmacDrbg, ENTROPY_INPUT_LEN, MAX_BYTES_PER_REQUEST, NONCE_LEN,
+};
+use crate::error::PqkdError;
+
+/// Fast local random number generator whose entropy comes from the QRNG
+/// of the pQKD device.
+///
+/// The HMAC_DRBG is instantiated with entropy input and nonce fetched with
+/// [PqkdClient::get_random_bytes] and reseeded from it every
+/// `reseed_interval` requests, or before every request when prediction
+/// resistance is enabled.
+///
+/// # Example
+///
+/// ```no_run
+/// use pqkd::BuilderPqkdClient;
+/// use pqkd::drbg::DrbgConfig;
+/// use std::error::Error;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn Error>> {
+///     let pqkd_client = BuilderPqkdClient::with_addr("http://172.16.0.154:8082")?
+///         .build();
+///
+///     let mut drbg = pqkd_client.drbg(DrbgConfig::default()).await?;
+///     let nonce = drbg.random_bytes(12).await?;
+///
+///     Ok(())
+/// }
+/// ```
+pub struct PqkdDrbg {
+    pqkd_client: PqkdClient,
+    config: DrbgConfig,
+    drbg: HmacDrbg,
+}
+
+impl PqkdDrbg {
+    /// Instantiates a new DRBG seeded from the QRNG of `pqkd_client`.
+    pub async fn new(pqkd_client: PqkdClient, config: DrbgConfig) -> Result<Self, PqkdError> {
+        config.check()?;
+        let seed = pqkd_client
+            .get_random_bytes((ENTROPY_INPUT_LEN + NONCE_LEN) as u32)
+            .await?;
+        check_entropy_len(&seed, ENTROPY_INPUT_LEN + NONCE_LEN)?;
+        let (entropy, nonce) = seed.split_at(ENTROPY_INPUT_LEN);
+        let drbg = HmacDrbg::new(entropy, nonce, &config.personalization, config.reseed_interval);
+        Ok(Self {
+            pqkd_client,
+            config,
+            drbg,
+        })
+    }
+
+    /// Reseeds the DRBG with fresh entropy from the QRNG.
+    pub async fn reseed(&mut self) -> Result<(), PqkdError> {
+        let entropy = self
+            .pqkd_client
+            .get_random_bytes(ENTROPY_INPUT_LEN as u32)
+            .await?;
+        check_entropy_len(&entropy, ENTROPY_INPUT_LEN)?;
+        self.drbg.reseed(&entropy, &[]);
+        Ok(())
+    }
+
+    /// Fills `dest` with random bytes, reseeding from the QRNG when needed.
+    pub async fn fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), PqkdError> {
+        for chunk in dest.chunks_mut(MAX_BYTES_PER_REQUEST) {
+            if self.config.prediction_resistance || self.drbg.reseed_required() {
+                self.reseed().await?;
+            }
+            self.drbg.generate(chunk, &[])?;
+        }
+        Ok(())
+    }
+
+    /// Returns `len` random bytes.
+    pub async fn random_bytes(&mut self, len: usize) -> Result<Vec<u8>, PqkdError> {
+        let mut bytes = vec![0u8; len];
+        self.fill_bytes(&mut bytes).await?;
+        Ok(bytes)
+    }
+
+    /// Returns the settings of this DRBG.
+    pub fn config(&self) -> &DrbgConfig {
+        &self.config
+    }
+}
@@ -0,0 +1,54 @@
+use super::pqkd::PqkdClient;
+use crate::error::PqkdError;
+use crate::sync::{KeyAccept, KeyOffer};
+use crate::Key;
+
+impl PqkdClient {
+    /// Requests `number` keys of `size` bits for the slave SAE `sae_id` and
+    /// returns them with an offer to send to it (see [crate::sync]).
+    /// The local SAE ID of the client must be set.
+    pub async fn offer_keys(
+        &self,
+        sae_id: &str,
+        context: &str,
+        number: u32,
+        size: u16,
+    ) -> Result<(Vec<Key>, KeyOffer), PqkdError> {
+        let local_sae_id = self.get_local_sae_id().await;
+        if local_sae_id.is_empty() {
+            return Err(PqkdError::KeySyncError(
+                "local SAE ID is not set".to_string(),
+            ));
+        }
+        let keys = self
+            .enc_keys(sae_id)
+            .number(number)
+            .size(size)
+            .send()
+            .await?
+            .keys();
+        let offer = KeyOffer::new(local_sae_id, sae_id, context, &keys)?;
+        Ok((keys, offer))
+    }
+
+    /// Fetches the keys of `offer` from the KME, checks that the master SAE
+    /// holds the same keys and returns them with the acknowledgement to
+    /// send back.
+    pub async fn accept_keys(&self, offer: &KeyOffer) -> Result<(Vec<Key>, KeyAccept), PqkdError> {
+        let local_sae_id = self.get_local_sae_id().await;
+        if !local_sae_id.is_empty() && offer.slave_sae_id != local_sae_id {
+            return Err(PqkdError::KeySyncError(format!(
+                "offer for another SAE ({})",
+                offer.slave_sae_id
+            )));
+        }
+        let keys = self
+            .dec_keys(&offer.master_sae_id)
+            .key_ids(offer.key_ids.iter().map(String::as_str).collect())
+            .send()
+            .await?
+            .keys();
+        let accept = offer.accept(&keys)?;
+        Ok((keys, accept))
+    }
+}
@@ -0,0 +1,110 @@
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use tokio::sync::broadcast;
+
+use super::pqkd::PqkdClient;
+use crate::error::PqkdError;
+use crate::request::{PqkdMethod, PqkdRequest};
+use crate::watcher::{LinkStats, WatchEvent, WatchState, WatcherConfig};
+
+/// Number of events kept for subscribers that fall behind.
+const EVENT_CAPACITY: usize = 64;
+
+/// Watches the key buffers of links by requesting their status
+/// periodically.
+///
+/// Events are sent to the receivers returned by
+/// [subscribe](Self::subscribe). The watcher can be cloned, e.g. to
+/// [run](Self::run) it in a task of its own while subscribing and
+/// reading [stats](Self::stats) elsewhere.
+///
+/// # Example
+///
+/// ```no_run
+/// use pqkd::watcher::{WatchEvent, WatcherConfig};
+/// use pqkd::{BuilderPqkdClient, StatusWatcher};
+/// use std::error::Error;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn Error>> {
+///     let pqkd_client = BuilderPqkdClient::with_addr("http://172.16.0.154:8082")?
+///         .build();
+///     let watcher = StatusWatcher::new(pqkd_client, WatcherConfig {
+///         sae_ids: vec!["Test_2SAE".to_string()],
+///         thresholds: vec![1000],
+///         ..WatcherConfig::default()
+///     });
+///     let mut events = watcher.subscribe();
+///     tokio::spawn(watcher.clone().run());
+///
+///     while let Ok(event) = events.recv().await {
+///         if let WatchEvent::BelowThreshold { sae_id, .. } = event {
+///             println!("link to {} runs low on keys", sae_id);
+///         }
+///     }
+///     Ok(())
+/// }
+/// ```
+#[derive(Clone)]
+pub struct StatusWatcher {
+    pqkd_client: PqkdClient,
+    config: WatcherConfig,
+    state: Arc<Mutex<WatchState>>,
+    events: broadcast::Sender<WatchEvent>,
+}
+
+impl StatusWatcher {
+    pub fn new(pqkd_client: PqkdClient, config: WatcherConfig) -> Self {
+        Self {
+            pqkd_client,
+            config,
+            state: Arc::default(),
+            events: broadcast::channel(EVENT_CAPACITY).0,
+        }
+    }
+
+    /// Returns a receiver of the events emitted from now on.
+    pub fn subscribe(&self) -> broadcast::Receiver<WatchEvent> {
+        self.events.subscribe()
+    }
+
+    /// Returns the key buffer of the link to `sae_id` as last seen.
+    pub fn stats(&self, sae_id: &str) -> Option<LinkStats> {
+        self.state.lock().unwrap().stats(sae_id)
+    }
+
+    /// Requests the status of every watched link once, sends the events
+    /// it emits to the subscribers and returns them.
+    pub async fn poll(&self) -> Vec<WatchEvent> {
+        let mut events = Vec::new();
+        for sae_id in &self.config.sae_ids {
+            let result = self
+                .pqkd_client
+                .kme_execute_request(PqkdRequest::new(PqkdMethod::Status, sae_id))
+                .await
+                .and_then(|response| response.as_status().ok_or(PqkdError::ErrorKmeRequest));
+            let mut state = self.state.lock().unwrap();
+            events.extend(state.update(
+                sae_id,
+                &self.config.thresholds,
+                result.as_ref(),
+                Instant::now(),
+            ));
+        }
+        for event in &events {
+            // Sending only fails when nobody is subscribed.
+            let _ = self.events.send(event.clone());
+        }
+        events
+    }
+
+    /// Polls the watched links every `interval` of the config. Runs until
+    /// the future is dropped.
+    pub async fn run(self) {
+        loop {
+            self.poll().await;
+            tokio::time::sleep(self.config.interval).await;
+        }
+    }
+}
@@ -0,0 +1,426 @@
+//! Tamper-evident audit log of key deliveries.
+//!
+//! With `with_audit_log` of the client builders, every `enc_keys` and
+//! `dec_keys` request is recorded as a line of JSON: when it was made, by
+//! which local SAE for which peer SAE, the key IDs and key size, and its
+//! outcome. Keys are never recorded.
+//!
+//! Each record contains the SHA-256 hash of the previous record, so
+//! changing, reordering or inserting records, or removing records before
+//! the last one, breaks the chain. [verify] checks the chain of a log.
+//!
+//! The hashes are not keyed: records removed from the end of the log, or a
+//! log rewritten with recomputed hashes, still verify. To detect that, keep
+//! the [AuditHead] returned by [AuditLog::head] outside of the log, e.g. in
+//! a remote log or the state of the application, and check the log against
+//! it with [verify_head].
+//!
+//! If a record cannot be written, the log stops accepting records, so a
+//! partially written line stays the last line of the log.
+//!
+//! ```text
+//! {"seq":0,"timestamp_ms":1760000000000,"operation":"enc_keys","local_sae_id":"Test_1SAE","peer_sae_id":"Test_2SAE","key_ids":["bc490419-7d60-487f-adc1-4ddcc177c139"],"key_size":256,"outcome":"ok","prev_hash":"0000…","hash":"6f1e…"}
+//! ```
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::error::PqkdError;
+use crate::metrics::decoded_len;
+use crate::request::{PqkdMethod, PqkdRequest};
+use crate::response::PqkdResponse;
+
+/// `prev_hash` of the first record of a log.
+const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// A line of the audit log.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditRecord {
+    /// Position of the record in the log, counted from 0.
+    pub seq: u64,
+    /// Time of the record in milliseconds since the Unix epoch.
+    pub timestamp_ms: u64,
+    /// `enc_keys` or `dec_keys`.
+    pub operation: String,
+    pub local_sae_id: String,
+    pub peer_sae_id: String,
+    /// IDs of the delivered keys, or of the requested keys if the request failed.
+    pub key_ids: Vec<String>,
+    /// Size of the keys in bits, if known.
+    pub key_size: Option<u32>,
+    /// `ok`, or the kind of the error (see [PqkdError::kind]).
+    pub outcome: String,
+    /// Hash of the previous record.
+    pub prev_hash: String,
+    /// SHA-256 (hex) of this record with an empty `hash`.
+    pub hash: String,
+}
+
+impl AuditRecord {
+    fn compute_hash(&self) -> String {
+        let unhashed = Self {
+            hash: String::new(),
+            ..self.clone()
+        };
+        let json = serde_json::to_vec(&unhashed).expect("audit record is serializable");
+        hex::encode(Sha256::digest(json))
+    }
+}
+
+/// Last record of a log, to be kept outside of it (see [verify_head]).
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditHead {
+    /// Number of records in the log.
+    pub records: u64,
+    /// Hash of the last record, or the `prev_hash` of the first record if
+    /// the log is empty.
+    pub hash: String,
+}
+
+/// Append-only sink of [AuditRecord]s, shared by the clones of a client.
+#[derive(Clone)]
+pub struct AuditLog {
+    chain: Arc<Mutex<Chain>>,
+}
+
+struct Chain {
+    writer: Box<dyn Write + Send>,
+    next_seq: u64,
+    last_hash: String,
+    /// Set when a record could not be written completely.
+    poisoned: bool,
+}
+
+impl AuditLog {
+    /// Starts a new log written to `writer`.
+    pub fn new(writer: impl Write + Send + 'static) -> Self {
+        Self {
+            chain: Arc::new(Mutex::new(Chain {
+                writer: Box::new(writer),
+                next_seq: 0,
+                last_hash: GENESIS_HASH.to_string(),
+                poisoned: false,
+            })),
+        }
+    }
+
+    /// Opens the log at `path` for appending, creating it if needed.
+    /// The chain of an existing log is verified and continued.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, PqkdError> {
+        let path = path.as_ref();
+        let last = match File::open(path) {
+            Ok(file) => verify(BufReader::new(file))?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => None,
+            Err(err) => return Err(err.into()),
+        };
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let log = Self::new(file);
+        if let Some(last) = last {
+            let mut chain = log.chain.lock().unwrap();
+            chain.next_seq = last.seq + 1;
+            chain.last_hash = last.hash;
+        }
+        Ok(log)
+    }
+
+    /// Returns the head of the log after the records written so far.
+    pub fn head(&self) -> AuditHead {
+        let chain = self.chain.lock().unwrap();
+        AuditHead {
+            records: chain.next_seq,
+            hash: chain.last_hash.clone(),
+        }
+    }
+
+    /// Records the outcome of a key request. Status requests are not
+    /// recorded. If the record cannot be written, the request fails with
+    /// [PqkdError::IoError], even if keys were delivered, and every later
+    /// request fails with [PqkdError::AuditLogError].
+    pub(crate) fn record(
+        &self,
+        local_sae_id: &str,
+        request: &PqkdRequest,
+        result: Result<PqkdResponse, PqkdError>,
+    ) -> Result<PqkdResponse, PqkdError> {
+        let operation = match request.pqkd_method() {
+            PqkdMethod::Status => return result,
+            PqkdMethod::EncKeys => "enc_keys",
+            PqkdMethod::DesKeys => "dec_keys",
+        };
+        let (key_ids, key_size, outcome) = match &result {
+            Ok(response) => {
+                let keys = match response {
+                    PqkdResponse::Keys(keys) => &keys[..],
+                    PqkdResponse::Status(_) => &[],
+                };
+                let key_size = keys
+                    .first()
+                    .map(|key| decoded_len(key.key()) as u32 * 8)
+                    .or(requested_size(request));
+                let key_ids = keys.iter().map(|key| key.key_id().to_string()).collect();
+                (key_ids, key_size, "ok")
+            }
+            Err(err) => (
+                request.key_ids().to_vec(),
+                requested_size(request),
+                err.kind(),
+            ),
+        };
+        let timestamp_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.as_millis() as u64);
+
+        let mut chain = self.chain.lock().unwrap();
+        if chain.poisoned {
+            return Err(PqkdError::AuditLogError(
+                "a previous record could not be written".to_string(),
+            ));
+        }
+        let mut record = AuditRecord {
+            seq: chain.next_seq,
+            timestamp_ms,
+            operation: operation.to_string(),
+            local_sae_id: local_sae_id.to_string(),
+            peer_sae_id: request.sae_id().to_string(),
+            key_ids,
+            key_size,
+            outcome: outcome.to_string(),
+            prev_hash: chain.last_hash.clone(),
+            hash: String::new(),
+        };
+        record.hash = record.compute_hash();
+        let mut line = serde_json::to_vec(&record)?;
+        line.push(b'\n');
+        // A failed write may leave part of the line in the log; no record
+        // may follow it.
+        if let Err(err) = chain
+            .writer
+            .write_all(&line)
+            .and_then(|_| chain.writer.flush())
+        {
+            chain.poisoned = true;
+            return Err(err.into());
+        }
+        chain.next_seq += 1;
+        chain.last_hash = record.hash;
+        result
+    }
+}
+
+/// Size of the keys requested by `enc_keys`, which `dec_keys` does not specify.
+fn requested_size(request: &PqkdRequest) -> Option<u32> {
+    match request.pqkd_method() {
+        PqkdMethod::EncKeys => Some(request.size() as u32),
+        _ => None,
+    }
+}
+
+/// Verifies the hash chain of an audit log and returns its last record,
+/// or `None` if it is empty. Fails with [PqkdError::AuditLogError] at the
+/// first record that was changed, removed or inserted.
+pub fn verify(log: impl BufRead) -> Result<Option<AuditRecord>, PqkdError> {
+    verify_records(log, |_| ())
+}
+
+/// Verifies the hash chain of an audit log like [verify] and that it still
+/// contains the last record of `head`, which detects records removed from
+/// the end of the log and logs rewritten with recomputed hashes. Records
+/// written after `head` was taken are accepted.
+pub fn verify_head(log: impl BufRead, head: &AuditHead) -> Result<Option<AuditRecord>, PqkdError> {
+    let mut found = head.records == 0 && head.hash == GENESIS_HASH;
+    let last = verify_records(log, |record| {
+        if record.seq + 1 == head.records {
+            found = record.hash == head.hash;
+        }
+    })?;
+    if !found {
+        return Err(PqkdError::AuditLogError(format!(
+            "record {} of the head is missing or changed",
+            head.records.saturating_sub(1)
+        )));
+    }
+    Ok(last)
+}
+
+fn verify_records(
+    log: impl BufRead,
+    mut visit: impl FnMut(&AuditRecord),
+) -> Result<Option<AuditRecord>, PqkdError> {
+    let mut last: Option<AuditRecord> = None;
+    for (index, line) in log.lines().enumerate() {
+        let line = line?;
+        let invalid =
+            |reason: &str| PqkdError::AuditLogError(format!("line {}: {}", index + 1, reason));
+        let record: AuditRecord =
+            serde_json::from_str(&line).map_err(|err| invalid(&err.to_string()))?;
+        let (seq, prev_hash) = match &last {
+            Some(last) => (last.seq + 1, last.hash.as_str()),
+            None => (0, GENESIS_HASH),
+        };
+        if record.seq != seq {
+            return Err(invalid(&format!(
+                "expected record {}, found {}",
+                seq, record.seq
+            )));
+        }
+        if record.prev_hash != prev_hash {
+            return Err(invalid("chain broken"));
+        }
+        if record.hash != record.compute_hash() {
+            return Err(invalid("hash mismatch"));
+        }
+        visit(&record);
+        last = Some(record);
+    }
+    Ok(last)
+}
+
+/// Verifies the audit log at `path` (see [verify]).
+pub fn verify_file(path: impl AsRef<Path>) -> Result<Option<AuditRecord>, PqkdError> {
+    verify(BufReader::new(File::open(path)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Key;
+
+    /// Writer whose output stays readable after it was moved into a log.
+    #[derive(Clone, Default)]
+    struct Shared(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn keys() -> PqkdResponse {
+        let key: Key = serde_json::from_value(serde_json::json!({
+            "key_ID": "bc490419-7d60-487f-adc1-4ddcc177c139",
+            "key": "wHHVxRwDJs3/bXd38GHP3oe4svTuRpZS0yCC7x4Ly+s="
+        }))
+        .unwrap();
+        PqkdResponse::Keys(vec![key])
+    }
+
+    fn log() -> Vec<String> {
+        let output = Shared::default();
+        let audit = AuditLog::new(output.clone());
+        let enc_keys = PqkdRequest::new(PqkdMethod::EncKeys, "Test_2SAE");
+        let mut dec_keys = PqkdRequest::new(PqkdMethod::DesKeys, "Test_2SAE");
+        dec_keys.key_ids_mut().push("unknown".to_string());
+        audit.record("Test_1SAE", &enc_keys, Ok(keys())).unwrap();
+        let _ = audit.record("Test_1SAE", &dec_keys, Err(PqkdError::ErrorKmeRequest));
+        let status = PqkdRequest::new(PqkdMethod::Status, "Test_2SAE");
+        let _ = audit.record("Test_1SAE", &status, Err(PqkdError::ErrorKmeRequest));
+        let output = String::from_utf8(output.0.lock().unwrap().clone()).unwrap();
+        output.lines().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn records() {
+        let lines = log();
+        assert_eq!(lines.len(), 2);
+        assert!(!lines[0].contains("wHHVxRwDJs3"));
+        let last = verify(lines.join("\n").as_bytes()).unwrap().unwrap();
+        assert_eq!(last.seq, 1);
+        assert_eq!(last.operation, "dec_keys");
+        assert_eq!(last.key_ids, ["unknown"]);
+        assert_eq!(last.key_size, None);
+        assert_eq!(last.outcome, "ErrorKmeRequest");
+        let first: AuditRecord = serde_json::from_str(&lines[0]).unwrap();
+        assert_eq!(first.key_size, Some(256));
+        assert_eq!(first.prev_hash, GENESIS_HASH);
+    }
+
+    #[test]
+    fn tampering() {
+        let lines = log();
+        let changed = lines[0].replace("Test_2SAE", "Test_3SAE");
+        let result = verify(format!("{}\n{}", changed, lines[1]).as_bytes());
+        assert!(
+            matches!(result, Err(PqkdError::AuditLogError(reason)) if reason == "line 1: hash mismatch")
+        );
+        let result = verify(lines[1].as_bytes());
+        assert!(matches!(result, Err(PqkdError::AuditLogError(_))));
+        let result = verify(format!("{}\n{}", lines[1], lines[0]).as_bytes());
+        assert!(matches!(result, Err(PqkdError::AuditLogError(_))));
+        assert_eq!(verify(&b""[..]).unwrap(), None);
+    }
+
+    #[test]
+    fn head() {
+        let output = Shared::default();
+        let audit = AuditLog::new(output.clone());
+        let empty = audit.head();
+        let enc_keys = PqkdRequest::new(PqkdMethod::EncKeys, "Test_2SAE");
+        audit.record("Test_1SAE", &enc_keys, Ok(keys())).unwrap();
+        let first = output.0.lock().unwrap().clone();
+        let head = audit.head();
+        assert_eq!(head.records, 1);
+        audit.record("Test_1SAE", &enc_keys, Ok(keys())).unwrap();
+        let both = output.0.lock().unwrap().clone();
+
+        assert!(verify_head(&both[..], &empty).is_ok());
+        assert!(verify_head(&first[..], &head).is_ok());
+        assert_eq!(verify_head(&both[..], &head).unwrap().unwrap().seq, 1);
+        // Truncating the log still verifies, but not against the head.
+        let head = audit.head();
+        assert!(verify(&first[..]).is_ok());
+        assert!(matches!(
+            verify_head(&first[..], &head),
+            Err(PqkdError::AuditLogError(_))
+        ));
+        assert!(verify_head(&b""[..], &head).is_err());
+    }
+
+    /// Writer accepting `limit` bytes, then failing.
+    struct Failing {
+        output: Shared,
+        limit: usize,
+    }
+
+    impl Write for Failing {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            if self.limit == 0 {
+                return Err(std::io::ErrorKind::Other.into());
+            }
+            let len = buf.len().min(self.limit);
+            self.limit -= len;
+            self.output.write(&buf[..len])
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn failed_write() {
+        let output = Shared::default();
+        let audit = AuditLog::new(Failing {
+            output: output.clone(),
+            limit: 20,
+        });
+        let enc_keys = PqkdRequest::new(PqkdMethod::EncKeys, "Test_2SAE");
+        let result = audit.record("Test_1SAE", &enc_keys, Ok(keys()));
+        assert!(matches!(result, Err(PqkdError::IoError(_))));
+        let result = audit.record("Test_1SAE", &enc_keys, Ok(keys()));
+        assert!(matches!(result, Err(PqkdError::AuditLogError(_))));
+        assert_eq!(audit.head().records, 0);
+        // Only the torn line was written.
+        let written = output.0.lock().unwrap().clone();
+        assert_eq!(written.len(), 20);
+        assert!(verify(&written[..]).is_err());
+    }
+}
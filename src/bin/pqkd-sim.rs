@@ -0,0 +1,73 @@
+//! Runs a simulated pair of pQKD devices until interrupted.
+use std::net::SocketAddr;
+use std::process::ExitCode;
+
+use clap::Parser;
+use pqkd::sim::{SimConfig, Simulator};
+
+#[derive(Parser)]
+#[command(name = "pqkd-sim", version, about = "Simulated pair of pQKD devices (ETSI GS QKD 014)")]
+struct Cli {
+    /// Address of the KME of side A.
+    #[arg(long, default_value = "127.0.0.1:8082")]
+    addr_a: SocketAddr,
+    /// Address of the KME of side B.
+    #[arg(long, default_value = "127.0.0.1:9082")]
+    addr_b: SocketAddr,
+    #[arg(long, default_value = "Sim_1KME")]
+    kme_id_a: String,
+    #[arg(long, default_value = "Sim_2KME")]
+    kme_id_b: String,
+    #[arg(long, default_value = "Sim_1SAE")]
+    sae_id_a: String,
+    #[arg(long, default_value = "Sim_2SAE")]
+    sae_id_b: String,
+    /// Default size of keys in bits.
+    #[arg(long, default_value_t = 256)]
+    key_size: u32,
+    /// Capacity of the shared key buffer.
+    #[arg(long, default_value_t = 4096)]
+    max_key_count: u32,
+    /// Number of keys in the buffer at start.
+    #[arg(long, default_value_t = 4096)]
+    initial_key_count: u32,
+    #[arg(long, default_value_t = 64)]
+    max_key_per_request: u32,
+    /// Number of keys added to the buffer per second.
+    #[arg(long, default_value_t = 0)]
+    key_rate: u32,
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let config = SimConfig {
+        addr_a: cli.addr_a,
+        addr_b: cli.addr_b,
+        kme_id_a: cli.kme_id_a,
+        kme_id_b: cli.kme_id_b,
+        sae_id_a: cli.sae_id_a,
+        sae_id_b: cli.sae_id_b,
+        key_size: cli.key_size,
+        max_key_count: cli.max_key_count,
+        initial_key_count: cli.initial_key_count,
+        max_key_per_request: cli.max_key_per_request,
+        key_rate: cli.key_rate,
+        ..Default::default()
+    };
+    let simulator = match Simulator::start(config) {
+        Ok(simulator) => simulator,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            return ExitCode::FAILURE;
+        }
+    };
+    println!("{} (side A) listening on {}", simulator.sae_id_a(), simulator.addr_a());
+    println!("{} (side B) listening on {}", simulator.sae_id_b(), simulator.addr_b());
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    let _ = runtime.block_on(tokio::signal::ctrl_c());
+    ExitCode::SUCCESS
+}
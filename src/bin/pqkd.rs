@@ -0,0 +1,311 @@
+//! Command-line client for the pQKD device.
+use std::error::Error;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use clap::{Parser, Subcommand, ValueEnum};
+use pqkd::blocking::{BuilderPqkdClient, PqkdClient};
+use pqkd::qrng::{QrngFormat, QrngReturnFormat};
+use pqkd::{Key, PqkdStatus};
+use serde_json::json;
+
+#[derive(Parser)]
+#[command(name = "pqkd", version, about = "Command-line client for the pQKD device")]
+struct Cli {
+    /// Address of the KME server.
+    #[arg(long, default_value = "http://127.0.0.1:8082")]
+    kme_addr: String,
+    /// Address of the QRNG server (by default the KME address with port 8085).
+    #[arg(long)]
+    qrng_addr: Option<String>,
+    /// CA certificate (PEM) used to verify the pQKD device.
+    #[arg(long, requires_all = ["client_cert", "client_key"])]
+    ca_cert: Option<PathBuf>,
+    /// Client certificate (PEM).
+    #[arg(long, requires_all = ["ca_cert", "client_key"])]
+    client_cert: Option<PathBuf>,
+    /// Client key (PKCS#8, RSA or EC PEM).
+    #[arg(long, requires_all = ["ca_cert", "client_cert"])]
+    client_key: Option<PathBuf>,
+    /// SAE ID of this application.
+    #[arg(long)]
+    local_sae_id: Option<String>,
+    /// Output format.
+    #[arg(long, value_enum, default_value_t = Output::Table)]
+    output: Output,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Show the status of the link to an SAE.
+    Status {
+        /// SAE ID of the peer.
+        sae_id: String,
+    },
+    /// Request new keys shared with an SAE.
+    EncKeys {
+        /// SAE ID of the peer.
+        sae_id: String,
+        /// Number of keys.
+        #[arg(long, default_value_t = 1)]
+        number: u32,
+        /// Size of each key in bits.
+        #[arg(long, default_value_t = 512)]
+        size: u16,
+        /// Requested key ID (can be repeated).
+        #[arg(long = "key-id")]
+        key_ids: Vec<String>,
+    },
+    /// Retrieve keys shared by an SAE.
+    DecKeys {
+        /// SAE ID of the peer.
+        sae_id: String,
+        /// Key ID (can be repeated).
+        #[arg(long = "key-id", required = true)]
+        key_ids: Vec<String>,
+    },
+    /// Fetch random data from the QRNG.
+    Random {
+        /// Format of the random data.
+        #[arg(long, value_enum, default_value_t = Format::Hex)]
+        format: Format,
+        /// Number of random bytes.
+        #[arg(long)]
+        size: u32,
+        /// Write the random data to this file instead of stdout.
+        #[arg(long)]
+        file: Option<PathBuf>,
+    },
+    /// Show a summary of the links to several SAEs.
+    Targets {
+        /// SAE IDs of the peers.
+        #[arg(required = true)]
+        sae_ids: Vec<String>,
+    },
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Output {
+    Table,
+    Json,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Format {
+    Hex,
+    Base64,
+    Bytes,
+}
+
+impl From<Format> for QrngFormat {
+    fn from(format: Format) -> Self {
+        match format {
+            Format::Hex => QrngFormat::Hex,
+            Format::Base64 => QrngFormat::Base64,
+            Format::Bytes => QrngFormat::Bytes,
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    match run(cli) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(cli: Cli) -> Result<(), Box<dyn Error>> {
+    let pqkd_client = build_client(&cli)?;
+    match cli.command {
+        Command::Status { sae_id } => {
+            let status = request_status(&pqkd_client, &sae_id)?;
+            match cli.output {
+                Output::Json => println!("{}", serde_json::to_string_pretty(&status)?),
+                Output::Table => print_table(&["FIELD", "VALUE"], status_fields(&status)),
+            }
+        }
+        Command::EncKeys {
+            sae_id,
+            number,
+            size,
+            key_ids,
+        } => {
+            let mut request = pqkd_client.enc_keys(&sae_id).size(size);
+            if key_ids.is_empty() {
+                request = request.number(number);
+            } else {
+                request = request.key_ids(key_ids.iter().map(|id| id.as_str()).collect());
+            }
+            print_keys(cli.output, &request.send()?.keys())?;
+        }
+        Command::DecKeys { sae_id, key_ids } => {
+            let keys = pqkd_client
+                .dec_keys(&sae_id)
+                .key_ids(key_ids.iter().map(|id| id.as_str()).collect())
+                .send()?
+                .keys();
+            print_keys(cli.output, &keys)?;
+        }
+        Command::Random { format, size, file } => {
+            let response = pqkd_client.get_random(format.into(), size)?;
+            if let Some(file) = file {
+                std::fs::write(file, raw_random(response.result()))?;
+            } else if cli.output == Output::Json {
+                let result = match response.result() {
+                    QrngReturnFormat::Bytes(_) => response.result().to_base64()?,
+                    other => String::from_utf8(raw_random(other))?,
+                };
+                let json = json!({
+                    "format": response.format().to_string(),
+                    "size": response.size(),
+                    "execute_time": response.execute_time(),
+                    "result": result,
+                });
+                println!("{}", serde_json::to_string_pretty(&json)?);
+            } else {
+                let mut stdout = std::io::stdout();
+                stdout.write_all(&raw_random(response.result()))?;
+                if !matches!(response.result(), QrngReturnFormat::Bytes(_)) {
+                    writeln!(stdout)?;
+                }
+            }
+        }
+        Command::Targets { sae_ids } => {
+            let mut statuses = Vec::new();
+            for sae_id in &sae_ids {
+                let status = request_status(&pqkd_client, sae_id)?;
+                statuses.push((sae_id, status));
+            }
+            match cli.output {
+                Output::Json => {
+                    let json: serde_json::Map<String, serde_json::Value> = statuses
+                        .iter()
+                        .map(|(sae_id, status)| Ok((sae_id.to_string(), serde_json::to_value(status)?)))
+                        .collect::<Result<_, serde_json::Error>>()?;
+                    println!("{}", serde_json::to_string_pretty(&json)?);
+                }
+                Output::Table => {
+                    let rows = statuses
+                        .iter()
+                        .map(|(sae_id, status)| {
+                            vec![
+                                sae_id.to_string(),
+                                status.source_kme_id.clone(),
+                                status.master_sae_id.clone(),
+                                status.stored_key_count.to_string(),
+                                status.max_key_count.to_string(),
+                                status.key_size.to_string(),
+                            ]
+                        })
+                        .collect();
+                    print_table(
+                        &["SAE ID", "SOURCE KME", "MASTER SAE", "STORED KEYS", "MAX KEYS", "KEY SIZE"],
+                        rows,
+                    );
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn build_client(cli: &Cli) -> Result<PqkdClient, Box<dyn Error>> {
+    let mut builder = BuilderPqkdClient::with_addr(&cli.kme_addr)?;
+    if let Some(qrng_addr) = &cli.qrng_addr {
+        builder = builder.with_qrng_addr(qrng_addr)?;
+    }
+    if let (Some(ca_cert), Some(client_cert), Some(client_key)) =
+        (&cli.ca_cert, &cli.client_cert, &cli.client_key)
+    {
+        builder = builder.with_tls(
+            &std::fs::read(ca_cert)?,
+            &std::fs::read(client_cert)?,
+            &std::fs::read(client_key)?,
+        )?;
+    }
+    if let Some(local_sae_id) = &cli.local_sae_id {
+        builder = builder.with_local_sae_id(local_sae_id);
+    }
+    Ok(builder.build())
+}
+
+fn request_status(pqkd_client: &PqkdClient, sae_id: &str) -> Result<PqkdStatus, Box<dyn Error>> {
+    match pqkd_client.status(sae_id).send()?.as_status() {
+        Some(status) => Ok(status),
+        None => Err(format!("KME did not answer the status request of {} with a status", sae_id).into()),
+    }
+}
+
+fn raw_random(result: &QrngReturnFormat) -> Vec<u8> {
+    match result {
+        QrngReturnFormat::Hex(val) | QrngReturnFormat::Base64(val) => val.as_bytes().to_vec(),
+        QrngReturnFormat::Bytes(val) => val.clone(),
+    }
+}
+
+fn status_fields(status: &PqkdStatus) -> Vec<Vec<String>> {
+    [
+        ("source_KME_ID", status.source_kme_id.clone()),
+        ("master_SAE_ID", status.master_sae_id.clone()),
+        ("key_size", status.key_size.to_string()),
+        ("stored_key_count", status.stored_key_count.to_string()),
+        ("max_key_count", status.max_key_count.to_string()),
+        ("max_key_per_request", status.max_key_per_request.to_string()),
+        ("max_key_size", status.max_key_size.to_string()),
+        ("min_key_size", status.min_key_size.to_string()),
+        ("max_SAE_ID_count", status.max_sae_id_count.to_string()),
+    ]
+    .into_iter()
+    .map(|(field, value)| vec![field.to_string(), value])
+    .collect()
+}
+
+fn print_keys(output: Output, keys: &[Key]) -> Result<(), Box<dyn Error>> {
+    match output {
+        Output::Json => {
+            let keys: Vec<serde_json::Value> = keys
+                .iter()
+                .map(|key| json!({"key_ID": key.key_id(), "key": key.key()}))
+                .collect();
+            println!("{}", serde_json::to_string_pretty(&json!({ "keys": keys }))?);
+        }
+        Output::Table => {
+            let rows = keys
+                .iter()
+                .map(|key| vec![key.key_id().to_string(), key.key().to_string()])
+                .collect();
+            print_table(&["KEY ID", "KEY"], rows);
+        }
+    }
+    Ok(())
+}
+
+fn print_table(headers: &[&str], rows: Vec<Vec<String>>) {
+    let mut widths: Vec<usize> = headers.iter().map(|header| header.len()).collect();
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.len());
+        }
+    }
+    let format_row = |cells: Vec<&str>| {
+        cells
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+            .collect::<Vec<String>>()
+            .join("  ")
+            .trim_end()
+            .to_string()
+    };
+    println!("{}", format_row(headers.to_vec()));
+    for row in &rows {
+        println!("{}", format_row(row.iter().map(|cell| cell.as_str()).collect()));
+    }
+}
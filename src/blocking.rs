@@ -1,6 +1,17 @@
+mod combiner;
+mod drbg;
 mod pqkd;
+mod random;
+mod relay;
 mod request_builder;
+mod sync;
+pub mod transport;
+mod watcher;
 
+pub use combiner::KeyCombiner;
+pub use drbg::PqkdDrbg;
 pub use pqkd::BuilderPqkdClient;
 pub use pqkd::PqkdClient;
-pub use request_builder::PqkdRequestBuilder;
\ No newline at end of file
+pub use relay::KeyRelay;
+pub use request_builder::PqkdRequestBuilder;
+pub use watcher::StatusWatcher;
\ No newline at end of file
@@ -0,0 +1,92 @@
+use std::thread;
+
+use super::pqkd::PqkdClient;
+use crate::combiner::{check_links, combine, in_order, link_key_ids, CompositeKey, CompositeKeyId};
+use crate::error::PqkdError;
+use crate::Key;
+
+/// Combines keys requested over several links (see [crate::combiner]).
+///
+/// # Example
+///
+/// ```no_run
+/// use pqkd::blocking::{BuilderPqkdClient, KeyCombiner};
+/// use std::error::Error;
+///
+/// fn main() -> Result<(), Box<dyn Error>> {
+///     let combiner = KeyCombiner::new()
+///         .with_link(BuilderPqkdClient::with_addr("http://172.16.0.154:8082")?.build(), "Test_2SAE")
+///         .with_link(BuilderPqkdClient::with_addr("http://172.16.0.155:8082")?.build(), "Test_2SAE");
+///     let keys = combiner.enc_keys(1, 256)?;
+///     // Send keys[0].key_id() to the peer, which calls dec_keys with it.
+///     println!("{}", keys[0].key_id());
+///     Ok(())
+/// }
+/// ```
+#[derive(Clone, Default)]
+pub struct KeyCombiner {
+    links: Vec<(PqkdClient, String)>,
+}
+
+impl KeyCombiner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the link of `pqkd_client` to the SAE `sae_id`, the peer SAE
+    /// on that link.
+    pub fn with_link(mut self, pqkd_client: PqkdClient, sae_id: &str) -> Self {
+        self.links.push((pqkd_client, sae_id.to_string()));
+        self
+    }
+
+    /// Requests `number` keys of `size` bits over every link, each on a
+    /// thread of its own, and combines them.
+    pub fn enc_keys(&self, number: u32, size: u16) -> Result<Vec<CompositeKey>, PqkdError> {
+        check_links(self.links.len())?;
+        combine(self.on_every_link(|_, pqkd_client, sae_id| {
+            Ok(pqkd_client
+                .enc_keys(sae_id)
+                .number(number)
+                .size(size)
+                .send()?
+                .keys())
+        })?)
+    }
+
+    /// Fetches the keys of `key_ids` over every link, each on a thread of
+    /// its own, and combines them.
+    pub fn dec_keys(&self, key_ids: &[CompositeKeyId]) -> Result<Vec<CompositeKey>, PqkdError> {
+        check_links(self.links.len())?;
+        combine(self.on_every_link(|link, pqkd_client, sae_id| {
+            let link_key_ids = link_key_ids(key_ids, link, self.links.len())?;
+            let keys = pqkd_client
+                .dec_keys(sae_id)
+                .key_ids(link_key_ids.clone())
+                .send()?
+                .keys();
+            in_order(keys, &link_key_ids)
+        })?)
+    }
+
+    fn on_every_link<F>(&self, request: F) -> Result<Vec<Vec<Key>>, PqkdError>
+    where
+        F: Fn(usize, &PqkdClient, &str) -> Result<Vec<Key>, PqkdError> + Sync,
+    {
+        thread::scope(|scope| {
+            let request = &request;
+            let handles: Vec<_> = self
+                .links
+                .iter()
+                .enumerate()
+                .map(|(link, (pqkd_client, sae_id))| {
+                    scope.spawn(move || request(link, pqkd_client, sae_id))
+                })
+                .collect();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().expect("key request panicked"))
+                .collect()
+        })
+    }
+}
@@ -1,11 +1,32 @@
+use super::drbg::PqkdDrbg;
 use super::request_builder::PqkdRequestBuilder;
+use super::transport::{ReqwestTransport, Transport};
+use crate::audit::AuditLog;
+use crate::breaker::{Attempt, BreakerPolicy, BreakerState, CircuitBreakers, Endpoint};
+use crate::budget::{KeyBudget, Quota};
+#[cfg(feature = "config")]
+use crate::config::ClientConfig;
+use crate::drbg::DrbgConfig;
+use crate::entropy::EntropyMode;
 use crate::error::PqkdError;
-use crate::qrng::{QrngFormat, QrngReturnFormat};
+use crate::metrics::Metrics;
+use crate::qrng::{QrngFormat, QrngResponse};
 use crate::request::{PqkdMethod, PqkdRequest};
 use crate::response::PqkdResponse;
+use crate::retry::RetryPolicy;
+use crate::tls::{self, CertReloader, KmePin, TlsFiles};
+use crate::trace;
+use crate::transport::{TransportRequest, TransportResponse};
 use crate::{Key, Keys, PqkdStatus};
 use reqwest::blocking::Client;
-use serde_json::{json, Value};
+use reqwest::header::{HeaderValue, CONTENT_TYPE};
+use reqwest::Method;
+use serde_json::json;
+use std::net::SocketAddr;
+#[cfg(feature = "config")]
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 use url::Url;
 
 /// Contains the necessary data for
@@ -13,7 +34,7 @@ use url::Url;
 ///
 /// # Example
 ///
-/// ```
+/// ```no_run
 /// use pqkd::blocking::BuilderPqkdClient;
 /// use std::error::Error;
 ///
@@ -76,9 +97,16 @@ use url::Url;
 pub struct PqkdClient {
     kme_addr: Url,
     qrng_addr: Url,
-    client: Client,
+    transport: Arc<dyn Transport>,
     local_target: Vec<u8>,
     local_sae_id: String,
+    entropy_mode: EntropyMode,
+    retry_policy: RetryPolicy,
+    kme_pin: KmePin,
+    metrics: Option<Metrics>,
+    budget: KeyBudget,
+    breakers: Option<CircuitBreakers>,
+    audit: Option<AuditLog>,
 }
 
 /// Build [PqkdClient](pqkd::PqkdClient) by combining an address KME server,
@@ -109,6 +137,26 @@ pub struct BuilderPqkdClient {
     client: Client,
     local_target: Vec<u8>,
     local_sae_id: String,
+    entropy_mode: EntropyMode,
+    retry_policy: RetryPolicy,
+    kme_pin: KmePin,
+    metrics: Option<Metrics>,
+    budget: KeyBudget,
+    breakers: Option<CircuitBreakers>,
+    audit: Option<AuditLog>,
+    tls: Option<(reqwest::Identity, reqwest::Certificate)>,
+    tls_reload: Option<(TlsFiles, Duration)>,
+    settings: ClientSettings,
+    transport: Option<Arc<dyn Transport>>,
+}
+
+/// Settings of the underlying HTTP client.
+#[derive(Clone, Default)]
+struct ClientSettings {
+    timeout: Option<Duration>,
+    connect_timeout: Option<Duration>,
+    resolve: Option<(String, SocketAddr)>,
+    tls_info: bool,
 }
 
 impl BuilderPqkdClient {
@@ -145,6 +193,17 @@ impl BuilderPqkdClient {
                 .unwrap(),
             local_target: Vec::new(),
             local_sae_id: String::new(),
+            entropy_mode: EntropyMode::Raw,
+            retry_policy: RetryPolicy::default(),
+            kme_pin: KmePin::default(),
+            metrics: None,
+            budget: KeyBudget::default(),
+            breakers: None,
+            audit: None,
+            tls: None,
+            tls_reload: None,
+            settings: ClientSettings::default(),
+            transport: None,
         })
     }
 
@@ -169,18 +228,12 @@ impl BuilderPqkdClient {
     pub fn with_qrng_addr(self, addr: &str) -> Result<Self, PqkdError> {
         let qrng_addr: Url = Url::parse(addr)
             .map_err(|_| PqkdError::BuildPqkdError("parsing failed.".to_string()))?;
-        Ok(Self {
-            kme_addr: self.kme_addr,
-            qrng_addr,
-            client: self.client,
-            local_target: self.local_target,
-            local_sae_id: self.local_sae_id,
-        })
+        Ok(Self { qrng_addr, ..self })
     }
 
     /// Add a CA certificate, client certificate and client key for TLS.
     /// This method accepts as parameters vectors of bytes certificates and key.
-    /// File must be in pem format.   
+    /// File must be in pem format. The key may be a PKCS#8, RSA or EC private key.
     /// # Examples
     ///
     /// ```
@@ -199,47 +252,301 @@ impl BuilderPqkdClient {
     ///     Ok(())
     /// }
     /// ```
+    #[allow(clippy::ptr_arg)]
     pub fn with_tls(
         self,
         ca_cert: &Vec<u8>,
         client_cert: &Vec<u8>,
         client_key: &Vec<u8>,
     ) -> Result<Self, PqkdError> {
-        let id = reqwest::Identity::from_pkcs8_pem(client_cert, client_key)?;
+        let id = tls::pem_identity(client_cert, client_key)?;
         let ca_cert = reqwest::Certificate::from_pem(ca_cert)?;
+        Self {
+            tls: Some((id, ca_cert)),
+            tls_reload: None,
+            ..self
+        }
+        .with_client()
+    }
+
+    /// Add a CA certificate (PEM) and a client identity from a PKCS#12
+    /// bundle (DER) for TLS.
+    /// PKCS#12 bundles are only supported by the `native-tls` backend.
+    pub fn with_pkcs12(self, ca_cert: &[u8], pkcs12: &[u8], password: &str) -> Result<Self, PqkdError> {
+        let id = tls::pkcs12_identity(pkcs12, password)?;
+        let ca_cert = reqwest::Certificate::from_pem(ca_cert)?;
+        Self {
+            tls: Some((id, ca_cert)),
+            tls_reload: None,
+            ..self
+        }
+        .with_client()
+    }
+
+    /// Add a CA certificate and client identity for TLS read from files.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use pqkd::blocking::BuilderPqkdClient;
+    /// use pqkd::tls::TlsFiles;
+    /// use std::error::Error;
+    ///
+    /// fn build_pqkd() -> Result<(), Box<dyn Error>> {
+    ///     let pqkd_builder = BuilderPqkdClient::with_addr("https://172.0.0.1:8082")?
+    ///         .with_tls_files(&TlsFiles::pkcs12("ca.pem", "client.p12", "secret"))?;
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn with_tls_files(self, files: &TlsFiles) -> Result<Self, PqkdError> {
+        let tls = files.load()?;
+        Self {
+            tls: Some(tls),
+            tls_reload: None,
+            ..self
+        }
+        .with_client()
+    }
+
+    /// Like [with_tls_files](Self::with_tls_files), but the files are
+    /// checked for changes at most once per `interval` while the client is
+    /// used. When the certificates are rotated, the underlying HTTP client
+    /// of the built [PqkdClient] (and all its clones) is rebuilt in place.
+    pub fn with_tls_reload(self, files: TlsFiles, interval: Duration) -> Result<Self, PqkdError> {
+        let builder = self.with_tls_files(&files)?;
         Ok(Self {
-            kme_addr: self.kme_addr,
-            qrng_addr: self.qrng_addr,
-            client: reqwest::blocking::Client::builder()
-                .use_native_tls()
-                .identity(id)
-                .add_root_certificate(ca_cert)
-                .build()?,
-            local_target: self.local_target,
-            local_sae_id: self.local_sae_id,
+            tls_reload: Some((files, interval)),
+            ..builder
         })
     }
 
+    /// Verify the certificate of the pQKD device against `server_name`
+    /// instead of the host of its address, e.g. when the device is
+    /// addressed by IP. The name is also sent in the TLS handshake (SNI).
+    ///
+    /// Applies to the KME address and to the QRNG address if it has the
+    /// same host, so call it after [with_qrng_addr](Self::with_qrng_addr).
+    pub fn with_server_name(self, server_name: &str) -> Result<Self, PqkdError> {
+        let (kme_addr, socket_addr) = tls::override_host(&self.kme_addr, server_name)?;
+        let qrng_addr = if self.qrng_addr.host() == self.kme_addr.host() {
+            tls::override_host(&self.qrng_addr, server_name)?.0
+        } else {
+            self.qrng_addr.clone()
+        };
+        Self {
+            kme_addr,
+            qrng_addr,
+            settings: ClientSettings {
+                resolve: Some((server_name.to_string(), socket_addr)),
+                ..self.settings
+            },
+            ..self
+        }
+        .with_client()
+    }
+
     pub fn with_local_target(self, local_target: Vec<u8>) -> Self {
         Self {
-            kme_addr: self.kme_addr,
-            qrng_addr: self.qrng_addr,
-            client: self.client,
             local_target,
-            local_sae_id: self.local_sae_id,
+            ..self
         }
     }
 
     pub fn with_local_sae_id(self, local_sae_id: &str) -> Self {
         Self {
-            kme_addr: self.kme_addr,
-            qrng_addr: self.qrng_addr,
-            client: self.client,
-            local_target: self.local_target,
             local_sae_id: String::from(local_sae_id),
+            ..self
+        }
+    }
+
+    /// Select whether random values from the QRNG are returned as received
+    /// or combined with OS entropy (see [EntropyMode]).
+    /// By default [EntropyMode::Raw] is used.
+    pub fn with_entropy_mode(self, entropy_mode: EntropyMode) -> Self {
+        Self {
+            entropy_mode,
+            ..self
         }
     }
 
+    /// Set a timeout for whole requests to the KME and QRNG servers.
+    pub fn with_timeout(self, timeout: Duration) -> Result<Self, PqkdError> {
+        Self {
+            settings: ClientSettings {
+                timeout: Some(timeout),
+                ..self.settings
+            },
+            ..self
+        }
+        .with_client()
+    }
+
+    /// Set a timeout for connecting to the KME and QRNG servers.
+    pub fn with_connect_timeout(self, connect_timeout: Duration) -> Result<Self, PqkdError> {
+        Self {
+            settings: ClientSettings {
+                connect_timeout: Some(connect_timeout),
+                ..self.settings
+            },
+            ..self
+        }
+        .with_client()
+    }
+
+    /// Select how failed requests are retried (see [RetryPolicy]).
+    /// By default requests are not retried.
+    pub fn with_retry_policy(self, retry_policy: RetryPolicy) -> Self {
+        Self {
+            retry_policy,
+            ..self
+        }
+    }
+
+    /// Record key consumption, latencies and errors of the built client
+    /// in `metrics` (see [Metrics]).
+    pub fn with_metrics(self, metrics: Metrics) -> Self {
+        Self {
+            metrics: Some(metrics),
+            ..self
+        }
+    }
+
+    /// Limit the key requests of the built client and its clones
+    /// (see [Quota]).
+    pub fn with_quota(self, quota: Quota) -> Self {
+        Self {
+            budget: self.budget.with_quota(None, quota),
+            ..self
+        }
+    }
+
+    /// Limit the key requests to the target SAE `sae_id`, in addition to
+    /// the quota of the client.
+    pub fn with_sae_quota(self, sae_id: &str, quota: Quota) -> Self {
+        Self {
+            budget: self.budget.with_quota(Some(sae_id), quota),
+            ..self
+        }
+    }
+
+    /// Fail requests to the KME or QRNG at once while it is unavailable
+    /// (see [BreakerPolicy]).
+    pub fn with_circuit_breaker(self, policy: BreakerPolicy) -> Self {
+        Self {
+            breakers: Some(CircuitBreakers::new(policy)),
+            ..self
+        }
+    }
+
+    /// Record every `enc_keys` and `dec_keys` request in `audit`
+    /// (see [AuditLog]).
+    pub fn with_audit_log(self, audit: AuditLog) -> Self {
+        Self {
+            audit: Some(audit),
+            ..self
+        }
+    }
+
+    /// Accept status and key responses only from the KME with the ID
+    /// `kme_id`. The status must report it as `source_KME_ID` and the
+    /// certificate of the KME must be issued to it (a DNS name of its
+    /// subject alternative names, or its subject common name), so
+    /// responses received without TLS are rejected.
+    pub fn with_kme_id(self, kme_id: &str) -> Result<Self, PqkdError> {
+        Self {
+            kme_pin: self.kme_pin.clone().with_kme_id(kme_id),
+            ..self
+        }
+        .with_client()
+    }
+
+    /// Accept status and key responses only from the KME whose certificate
+    /// has the SHA-256 `fingerprint`, given in hex and optionally separated
+    /// by colons (as printed by `openssl x509 -fingerprint -sha256`).
+    pub fn with_kme_fingerprint(self, fingerprint: &str) -> Result<Self, PqkdError> {
+        Self {
+            kme_pin: self.kme_pin.clone().with_fingerprint(fingerprint)?,
+            ..self
+        }
+        .with_client()
+    }
+
+    /// Send the requests to the KME and QRNG servers through `transport`
+    /// instead of the HTTP client configured by this builder, whose TLS,
+    /// server name and timeout settings are then ignored.
+    pub fn with_transport(self, transport: impl Transport + 'static) -> Self {
+        Self {
+            transport: Some(Arc::new(transport)),
+            ..self
+        }
+    }
+
+    /// Creates a builder from a profile of a configuration file, with
+    /// `PQKD_*` environment variables overriding its settings
+    /// (see [ClientConfig::load]).
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use pqkd::blocking::BuilderPqkdClient;
+    /// use std::error::Error;
+    ///
+    /// fn build_pqkd() -> Result<(), Box<dyn Error>> {
+    ///     let pqkd = BuilderPqkdClient::from_config("pqkd.toml", Some("alice"))?.build();
+    ///     Ok(())
+    /// }
+    /// ```
+    #[cfg(feature = "config")]
+    pub fn from_config(path: impl AsRef<Path>, profile: Option<&str>) -> Result<Self, PqkdError> {
+        Self::from_client_config(&ClientConfig::load(path, profile)?)
+    }
+
+    /// Creates a builder from the given settings.
+    #[cfg(feature = "config")]
+    pub fn from_client_config(config: &ClientConfig) -> Result<Self, PqkdError> {
+        let mut builder = Self::with_addr(config.kme_addr()?)?;
+        if let Some(qrng_addr) = &config.qrng_addr {
+            builder = builder.with_qrng_addr(qrng_addr)?;
+        }
+        if let Some(files) = config.tls_files()? {
+            builder = match config.tls_reload_interval() {
+                Some(interval) => builder.with_tls_reload(files, interval)?,
+                None => builder.with_tls_files(&files)?,
+            };
+        }
+        if let Some(server_name) = &config.server_name {
+            builder = builder.with_server_name(server_name)?;
+        }
+        if let Some(local_sae_id) = &config.local_sae_id {
+            builder = builder.with_local_sae_id(local_sae_id);
+        }
+        if let Some(kme_id) = &config.kme_id {
+            builder = builder.with_kme_id(kme_id)?;
+        }
+        if let Some(fingerprint) = &config.kme_fingerprint {
+            builder = builder.with_kme_fingerprint(fingerprint)?;
+        }
+        if let Some(timeout) = config.timeout() {
+            builder = builder.with_timeout(timeout)?;
+        }
+        if let Some(connect_timeout) = config.connect_timeout() {
+            builder = builder.with_connect_timeout(connect_timeout)?;
+        }
+        Ok(builder.with_retry_policy(config.retry_policy()))
+    }
+
+    fn with_client(self) -> Result<Self, PqkdError> {
+        let settings = ClientSettings {
+            tls_info: self.kme_pin.is_set(),
+            ..self.settings
+        };
+        Ok(Self {
+            client: build_client(&settings, self.tls.clone())?,
+            settings,
+            ..self
+        })
+    }
+
     /// Creates PqkdClient by passing it the data it contains and return it.
     ///
     /// # Examples
@@ -263,16 +570,73 @@ impl BuilderPqkdClient {
     /// }
     /// ```
     pub fn build(self) -> PqkdClient {
-        PqkdClient::new(
+        trace::client_built(trace::ClientSummary {
+            kme_addr: &self.kme_addr,
+            qrng_addr: &self.qrng_addr,
+            tls: self.tls.is_some(),
+            tls_reload: self.tls_reload.is_some(),
+            custom_transport: self.transport.is_some(),
+            kme_pinned: self.kme_pin.is_set(),
+            retry_policy: self.retry_policy,
+        });
+        let transport = match (self.transport, self.tls_reload) {
+            (Some(transport), _) => transport,
+            (None, Some((files, interval))) => {
+                let settings = self.settings;
+                let reloader = CertReloader::new(files, interval, self.client.clone(), move |id, ca_cert| {
+                    build_client(&settings, Some((id, ca_cert)))
+                });
+                Arc::new(ReqwestTransport::new(self.client).with_reloader(reloader))
+            }
+            (None, None) => Arc::new(ReqwestTransport::new(self.client)),
+        };
+        let mut pqkd_client = PqkdClient::with_transport(
             self.kme_addr,
             self.qrng_addr,
-            self.client,
+            transport,
             self.local_target,
             self.local_sae_id,
-        )
+        );
+        pqkd_client.entropy_mode = self.entropy_mode;
+        pqkd_client.retry_policy = self.retry_policy;
+        pqkd_client.kme_pin = self.kme_pin;
+        pqkd_client.metrics = self.metrics;
+        pqkd_client.budget = self.budget;
+        pqkd_client.breakers = self.breakers;
+        pqkd_client.audit = self.audit;
+        pqkd_client
     }
 }
 
+fn build_client(
+    settings: &ClientSettings,
+    tls: Option<(reqwest::Identity, reqwest::Certificate)>,
+) -> Result<Client, PqkdError> {
+    let mut client = match tls {
+        Some((id, ca_cert)) => {
+            #[cfg(feature = "rustls-tls")]
+            let client = reqwest::blocking::Client::builder().use_rustls_tls();
+            #[cfg(not(feature = "rustls-tls"))]
+            let client = reqwest::blocking::Client::builder().use_native_tls();
+            client.identity(id).add_root_certificate(ca_cert)
+        }
+        None => reqwest::blocking::Client::builder().http1_title_case_headers(),
+    };
+    if let Some(timeout) = settings.timeout {
+        client = client.timeout(timeout);
+    }
+    if let Some(connect_timeout) = settings.connect_timeout {
+        client = client.connect_timeout(connect_timeout);
+    }
+    if let Some((server_name, addr)) = &settings.resolve {
+        client = client.resolve(server_name, *addr);
+    }
+    if settings.tls_info {
+        client = client.tls_info(true);
+    }
+    Ok(client.build()?)
+}
+
 impl PqkdClient {
     /// Create a new ['PqkdClient'] from the given
     /// url of kme server, url of qrng server.
@@ -282,13 +646,37 @@ impl PqkdClient {
         client: Client,
         local_target: Vec<u8>,
         local_sae_id: String,
+    ) -> Self {
+        Self::with_transport(
+            kme_addr,
+            qrng_addr,
+            Arc::new(ReqwestTransport::new(client)),
+            local_target,
+            local_sae_id,
+        )
+    }
+
+    /// Create a new ['PqkdClient'] sending its requests through `transport`.
+    pub fn with_transport(
+        kme_addr: Url,
+        qrng_addr: Url,
+        transport: Arc<dyn Transport>,
+        local_target: Vec<u8>,
+        local_sae_id: String,
     ) -> Self {
         Self {
             kme_addr,
             qrng_addr,
-            client,
+            transport,
             local_target,
             local_sae_id,
+            entropy_mode: EntropyMode::Raw,
+            retry_policy: RetryPolicy::default(),
+            kme_pin: KmePin::default(),
+            metrics: None,
+            budget: KeyBudget::default(),
+            breakers: None,
+            audit: None,
         }
     }
 
@@ -338,13 +726,21 @@ impl PqkdClient {
     //     self._fetch_dec_keys(sae_id, key_ids).await
     // }
 
+    /// Fetches `size` random bytes from the QRNG in the given format,
+    /// together with the metadata reported by the device.
+    /// Fails if the decoded value does not have the requested size.
+    pub fn get_random(&self, format: QrngFormat, size: u32) -> Result<QrngResponse, PqkdError> {
+        self._fetch_random(format, size)
+    }
+
     pub fn get_random_hex(&self, size: u32) -> Result<String, PqkdError> {
-        Ok(self._fetch_random(QrngFormat::Hex, size)?.as_hex().unwrap())
+        Ok(self._fetch_random(QrngFormat::Hex, size)?.into_result().as_hex().unwrap())
     }
 
     pub fn get_random_bytes(&self, size: u32) -> Result<Vec<u8>, PqkdError> {
         Ok(self
             ._fetch_random(QrngFormat::Bytes, size)?
+            .into_result()
             .as_bytes()
             .unwrap())
     }
@@ -352,10 +748,30 @@ impl PqkdClient {
     pub fn get_random_base64(&self, size: u32) -> Result<String, PqkdError> {
         Ok(self
             ._fetch_random(QrngFormat::Base64, size)?
+            .into_result()
             .as_base64()
             .unwrap())
     }
 
+    /// Fetches random data in the hex format and returns it decoded to bytes.
+    pub fn get_random_hex_bytes(&self, size: u32) -> Result<Vec<u8>, PqkdError> {
+        self._fetch_random(QrngFormat::Hex, size)?
+            .result()
+            .to_bytes()
+    }
+
+    /// Fetches random data in the base64 format and returns it decoded to bytes.
+    pub fn get_random_base64_bytes(&self, size: u32) -> Result<Vec<u8>, PqkdError> {
+        self._fetch_random(QrngFormat::Base64, size)?
+            .result()
+            .to_bytes()
+    }
+
+    /// Creates a DRBG seeded from the QRNG of this pQKD (see [PqkdDrbg]).
+    pub fn drbg(&self, config: DrbgConfig) -> Result<PqkdDrbg, PqkdError> {
+        PqkdDrbg::new(self.clone(), config)
+    }
+
     pub fn get_sae_ids(&self) -> Result<Vec<String>, PqkdError> {
         todo!();
     }
@@ -375,33 +791,109 @@ impl PqkdClient {
     pub fn local_sae_id(&self) -> &str {
         &self.local_sae_id
     }
+
+    /// Returns the mode applied to random values from the QRNG.
+    pub fn entropy_mode(&self) -> EntropyMode {
+        self.entropy_mode
+    }
+
+    /// Returns the policy applied to failed requests.
+    pub fn retry_policy(&self) -> RetryPolicy {
+        self.retry_policy
+    }
+
+    /// Returns the metrics the client records to, if any.
+    pub fn metrics(&self) -> Option<&Metrics> {
+        self.metrics.as_ref()
+    }
+
+    /// Returns the state of the circuit breaker of `endpoint`, e.g. for
+    /// health checks. Without circuit breakers it is always closed.
+    pub fn circuit_state(&self, endpoint: Endpoint) -> BreakerState {
+        match &self.breakers {
+            Some(breakers) => breakers.get(endpoint).state(),
+            None => BreakerState::Closed,
+        }
+    }
+
+    /// Requests the status of the link to `sae_id` every `interval` to keep
+    /// the `stored_key_count` gauge of the metrics up to date. Never returns,
+    /// so run it on a thread of its own; failed requests are counted and do
+    /// not stop it.
+    pub fn poll_status(&self, sae_id: &str, interval: Duration) {
+        loop {
+            let _ = self.kme_execute_request(PqkdRequest::new(PqkdMethod::Status, sae_id));
+            std::thread::sleep(interval);
+        }
+    }
 }
 
 impl PqkdClient {
     pub fn kme_execute_request(
         &self,
         pqkd_request: PqkdRequest,
+    ) -> Result<PqkdResponse, PqkdError> {
+        let _span = trace::kme_span(&pqkd_request).entered();
+        let started = Instant::now();
+        let res = match self.budget.acquire(&pqkd_request) {
+            Ok(permit) => permit.release(self.kme_retry(&pqkd_request)),
+            Err(err) => Err(err),
+        };
+        let res = match &self.audit {
+            Some(audit) => audit.record(&self.local_sae_id, &pqkd_request, res),
+            None => res,
+        };
+        let elapsed = started.elapsed();
+        trace::kme_done(&res, elapsed);
+        if let Some(metrics) = &self.metrics {
+            metrics.record_kme(&pqkd_request, &res, elapsed);
+        }
+        res
+    }
+
+    /// Lets a request to `endpoint` through its circuit breaker, if any.
+    fn attempt(&self, endpoint: Endpoint) -> Result<Attempt, PqkdError> {
+        match &self.breakers {
+            Some(breakers) => breakers.get(endpoint).acquire(),
+            None => Ok(Attempt::unguarded()),
+        }
+    }
+
+    /// Sends a request to the KME, retrying it according to the retry policy.
+    fn kme_retry(&self, pqkd_request: &PqkdRequest) -> Result<PqkdResponse, PqkdError> {
+        let mut attempt = 0;
+        loop {
+            let res = match self.attempt(Endpoint::Kme) {
+                Ok(attempt) => attempt.finish(self._kme_execute_request(pqkd_request)),
+                Err(err) => Err(err),
+            };
+            match res {
+                Err(err) if self.retry_policy.retry(attempt, &err) => {
+                    let delay = self.retry_policy.delay(attempt);
+                    trace::retry(attempt, delay, &err);
+                    std::thread::sleep(delay);
+                    attempt += 1;
+                }
+                res => return res,
+            }
+        }
+    }
+
+    fn _kme_execute_request(
+        &self,
+        pqkd_request: &PqkdRequest,
     ) -> Result<PqkdResponse, PqkdError> {
         match pqkd_request.pqkd_method() {
             PqkdMethod::Status => {
-                let url = self
-                    .kme_addr
-                    .join(&format!("api/v1/keys/{}/status", pqkd_request.sae_id()))
-                    .map_err(|_| PqkdError::ErrorKmeRequest)
-                    .unwrap();
-                let res = self.client.get(url).send()?.error_for_status()?;
-
-                let body = res.text()?;
-                let status: PqkdStatus = serde_json::from_str(&body).unwrap();
+                let status = self._fetch_status(pqkd_request.sae_id())?;
                 Ok(PqkdResponse::Status(status))
             }
             PqkdMethod::EncKeys => {
                 let url = self
                     .kme_addr
                     .join(&format!("/api/v1/keys/{}/enc_keys", pqkd_request.sae_id()))
-                    .map_err(|_| PqkdError::ErrorKmeRequest)
-                    .unwrap();
-                let body = if pqkd_request.key_ids().len() > 0 {
+                    .map_err(|_| PqkdError::ErrorKmeRequest)?;
+                let body = if !pqkd_request.key_ids().is_empty() {
                     let ids: Vec<&str> = pqkd_request
                         .key_ids()
                         .iter()
@@ -412,82 +904,96 @@ impl PqkdClient {
                     json!({"size": pqkd_request.size(), "number": pqkd_request.number()})
                 };
 
-                let res = self.client.post(url).body(body.to_string());
-                let res = res.send()?.error_for_status().unwrap();
+                let request = TransportRequest::new(Method::POST, url).with_body(body.to_string().into_bytes());
+                let res = self.send_kme(request)?;
 
-                let body = res.text()?;
-                let keys: Keys = serde_json::from_str(&body)?;
+                let keys: Keys = serde_json::from_slice(&res.body)?;
                 Ok(PqkdResponse::Keys(keys.keys))
             }
             PqkdMethod::DesKeys => {
-                let url = self
-                    .kme_addr
-                    .join(&format!("/api/v1/keys/{}/dec_keys", pqkd_request.sae_id()))
-                    .map_err(|_| PqkdError::ErrorKmeRequest)
-                    .unwrap();
-                let key_ids: Vec<serde_json::Value> = pqkd_request
+                let key_ids: Vec<&str> = pqkd_request
                     .key_ids()
                     .iter()
-                    .map(|key_id| json!({"key_ID": key_id}))
+                    .map(|key_id| key_id.as_str())
                     .collect();
-                let body = json!({"key_IDs": key_ids});
-
-                let res = self
-                    .client
-                    .post(url)
-                    .header("Content-Type", "application/json")
-                    .body(body.to_string())
-                    .send()?
-                    .error_for_status()
-                    .unwrap();
-
-                let body = res.text().unwrap();
-                let keys: Keys = serde_json::from_str(&body).unwrap();
-                Ok(PqkdResponse::Keys(keys.keys))
+                let keys = self._fetch_dec_keys(pqkd_request.sae_id(), key_ids)?;
+                Ok(PqkdResponse::Keys(keys))
             }
         }
     }
 
-    fn _fetch_random(&self, format: QrngFormat, size: u32) -> Result<QrngReturnFormat, PqkdError> {
+    /// Sends a request and fails if the server did not answer with a success status.
+    fn send(&self, request: TransportRequest) -> Result<TransportResponse, PqkdError> {
+        let exchange = trace::Exchange::start(&request);
+        let res = self.transport.execute(request);
+        exchange.finish(&res);
+        res?.error_for_status()
+    }
+
+    /// Like [send](Self::send), but also verifies that the response comes from the pinned KME.
+    fn send_kme(&self, request: TransportRequest) -> Result<TransportResponse, PqkdError> {
+        let res = self.send(request)?;
+        self.kme_pin.verify_certificate(res.peer_certificate.as_deref())?;
+        Ok(res)
+    }
+
+    fn _fetch_random(&self, format: QrngFormat, size: u32) -> Result<QrngResponse, PqkdError> {
         format.check_size(size)?;
 
+        let _span = trace::qrng_span(format, size).entered();
+        let started = Instant::now();
+        let mut attempt = 0;
+        let res = loop {
+            let res = match self.attempt(Endpoint::Qrng) {
+                Ok(attempt) => attempt.finish(self._fetch_random_once(format, size)),
+                Err(err) => Err(err),
+            };
+            match res {
+                Err(err) if self.retry_policy.retry(attempt, &err) => {
+                    let delay = self.retry_policy.delay(attempt);
+                    trace::retry(attempt, delay, &err);
+                    std::thread::sleep(delay);
+                    attempt += 1;
+                }
+                res => break res,
+            }
+        };
+        let elapsed = started.elapsed();
+        trace::qrng_done(&res, elapsed);
+        if let Some(metrics) = &self.metrics {
+            metrics.record_qrng(size, &res, elapsed);
+        }
+        res
+    }
+
+    fn _fetch_random_once(&self, format: QrngFormat, size: u32) -> Result<QrngResponse, PqkdError> {
         let url = self
             .qrng_addr
             .join(&format!("qrng/{}?size={}", &format.to_string(), size))
-            .map_err(|_| PqkdError::ErrorQrngRequest)
-            .unwrap();
+            .map_err(|_| PqkdError::ErrorQrngRequest)?;
 
-        let res = self.client.get(url).send()?.error_for_status()?;
+        let res = self.send(TransportRequest::new(Method::GET, url))?;
+        let headers = QrngResponse::headers_from(&res.headers);
         let res = match format {
-            QrngFormat::Base64 => {
-                let body = res.text()?;
-                let v: Value = serde_json::from_str(&body)?;
-                QrngReturnFormat::Base64(v["result"].as_str().unwrap().to_string())
-            }
-            QrngFormat::Bytes => {
-                let body = res.bytes()?;
-                QrngReturnFormat::Bytes(body.to_vec())
-            }
-            QrngFormat::Hex => {
-                let body = res.text()?;
-                let v: Value = serde_json::from_str(&body)?;
-                QrngReturnFormat::Hex(v["result"].as_str().unwrap().to_string())
-            }
+            QrngFormat::Base64 | QrngFormat::Hex => QrngResponse::from_json(format, size, headers, res.text()?)?,
+            QrngFormat::Bytes => QrngResponse::from_bytes(size, headers, res.body)?,
         };
-        Ok(res)
+        match self.entropy_mode {
+            EntropyMode::Raw => Ok(res),
+            EntropyMode::Mixed => res.mix_with_os_entropy(),
+        }
     }
 
     fn _fetch_status(&self, sae_id: &str) -> Result<PqkdStatus, PqkdError> {
         let url = self
             .kme_addr
             .join(&format!("api/v1/keys/{}/status", sae_id))
-            .map_err(|_| PqkdError::ErrorKmeRequest)
-            .unwrap();
+            .map_err(|_| PqkdError::ErrorKmeRequest)?;
 
-        let res = self.client.get(url).send()?.error_for_status()?;
+        let res = self.send_kme(TransportRequest::new(Method::GET, url))?;
 
-        let body = res.text()?;
-        let status: PqkdStatus = serde_json::from_str(&body).unwrap();
+        let status: PqkdStatus = serde_json::from_slice(&res.body)?;
+        self.kme_pin.verify_status(&status)?;
         Ok(status)
     }
 
@@ -507,49 +1013,41 @@ impl PqkdClient {
         let url = self
             .kme_addr
             .join(&format!("/api/v1/keys/{}/enc_keys", sae_id))
-            .map_err(|_| PqkdError::ErrorKmeRequest)
-            .unwrap();
+            .map_err(|_| PqkdError::ErrorKmeRequest)?;
         let body = if let Some(ids) = key_ids {
-            let ids: Vec<&str> = ids.iter().map(|id| *id).collect();
             json!({"size": size, "key_IDs": ids})
         } else {
             json!({"size": size, "number": number})
         };
 
-        let res = self.client.post(url).body(body.to_string());
-        let res = res.send()?.error_for_status().unwrap();
+        let request = TransportRequest::new(Method::POST, url).with_body(body.to_string().into_bytes());
+        let res = self.send_kme(request)?;
 
-        let body = res.text()?;
-        let keys: Keys = serde_json::from_str(&body)?;
+        let keys: Keys = serde_json::from_slice(&res.body)?;
         Ok(keys.keys)
     }
 
-    fn _fetch_dec_keys(&self, sae_id: &str, key_ids: Vec<&str>) -> Result<Vec<Key>, PqkdError> {
+    fn _fetch_dec_keys(
+        &self,
+        sae_id: &str,
+        key_ids: Vec<&str>,
+    ) -> Result<Vec<Key>, PqkdError> {
         let url = self
             .kme_addr
             .join(&format!("/api/v1/keys/{}/dec_keys", sae_id))
-            .map_err(|_| PqkdError::ErrorKmeRequest)
-            .unwrap();
+            .map_err(|_| PqkdError::ErrorKmeRequest)?;
         let key_ids: Vec<serde_json::Value> = key_ids
             .iter()
             .map(|key_id| json!({"key_ID": *key_id}))
             .collect();
         let body = json!({"key_IDs": key_ids});
-        // let body = KeyIds{
-        //     key_IDs: key_ids.iter().map(|key_id| KeyId { key_ID: key_id.to_string()}).collect(),
-        // };
-
-        let res = self
-            .client
-            .post(url)
-            .header("Content-Type", "application/json")
-            .body(body.to_string())
-            .send()?
-            .error_for_status()
-            .unwrap();
-
-        let body = res.text().unwrap();
-        let keys: Keys = serde_json::from_str(&body).unwrap();
+
+        let request = TransportRequest::new(Method::POST, url)
+            .with_header(CONTENT_TYPE, HeaderValue::from_static("application/json"))
+            .with_body(body.to_string().into_bytes());
+        let res = self.send_kme(request)?;
+
+        let keys: Keys = serde_json::from_slice(&res.body)?;
         Ok(keys.keys)
     }
 }
@@ -0,0 +1,88 @@
+use std::ops::Range;
+
+use super::pqkd::PqkdClient;
+use crate::error::PqkdError;
+use crate::qrng::{QrngFormat, MAX_SIZE_FOR_BYTES_FORMAT};
+use crate::random::{self, BitReader};
+
+/// Typed random values sourced from the QRNG.
+impl PqkdClient {
+    /// Returns an integer uniformly distributed in `range`.
+    pub fn get_random_range(&self, range: Range<u64>) -> Result<u64, PqkdError> {
+        random::check_range(range.start, range.end)?;
+        let size = range.end - range.start;
+        let value = self
+            .sample(random::expected_bits(size), |reader| {
+                random::uniform_below(reader, size)
+            })?;
+        Ok(range.start + value)
+    }
+
+    /// Returns a float uniformly distributed in `[0, 1)`.
+    pub fn get_random_f64(&self) -> Result<f64, PqkdError> {
+        self.sample(53.0, random::unit_f64)
+    }
+
+    /// Returns a random (version 4) UUID.
+    pub fn get_random_uuid(&self) -> Result<String, PqkdError> {
+        let bytes = self.get_random_bytes(16)?;
+        random::uuid_v4(&bytes)
+            .ok_or_else(|| PqkdError::InvalidQrngData(QrngFormat::Bytes.to_string()))
+    }
+
+    /// Shuffles `items` in place, every permutation being equally likely.
+    pub fn shuffle<T>(&self, items: &mut [T]) -> Result<(), PqkdError> {
+        let len = items.len();
+        let swaps = self
+            .sample(random::shuffle_expected_bits(len), |reader| {
+                random::shuffle_swaps(reader, len)
+            })?;
+        random::apply_swaps(items, &swaps);
+        Ok(())
+    }
+
+    /// Returns a random permutation of `0..len`.
+    pub fn get_random_permutation(&self, len: usize) -> Result<Vec<usize>, PqkdError> {
+        let mut permutation: Vec<usize> = (0..len).collect();
+        self.shuffle(&mut permutation)?;
+        Ok(permutation)
+    }
+
+    /// Returns a password of `len` characters chosen uniformly from `alphabet`.
+    pub fn get_random_password(&self, len: usize, alphabet: &str) -> Result<String, PqkdError> {
+        let alphabet: Vec<char> = alphabet.chars().collect();
+        random::check_alphabet(&alphabet)?;
+        let size = alphabet.len() as u64;
+        let indices = self
+            .sample(random::expected_bits(size) * len as f64, |reader| {
+                (0..len)
+                    .map(|_| random::uniform_below(reader, size))
+                    .collect::<Option<Vec<u64>>>()
+            })?;
+        Ok(indices.iter().map(|index| alphabet[*index as usize]).collect())
+    }
+
+    fn sample<T>(
+        &self,
+        expected_bits: f64,
+        sampler: impl Fn(&mut BitReader) -> Option<T>,
+    ) -> Result<T, PqkdError> {
+        let mut bytes = self.fetch_random_bytes(random::bytes_for(expected_bits))?;
+        loop {
+            if let Some(value) = sampler(&mut BitReader::new(&bytes)) {
+                return Ok(value);
+            }
+            let more = self.fetch_random_bytes(bytes.len() / 8 + 8)?;
+            bytes.extend(more);
+        }
+    }
+
+    fn fetch_random_bytes(&self, len: usize) -> Result<Vec<u8>, PqkdError> {
+        let mut bytes = Vec::with_capacity(len);
+        while bytes.len() < len {
+            let size = (len - bytes.len()).min(MAX_SIZE_FOR_BYTES_FORMAT as usize);
+            bytes.extend(self.get_random_bytes(size as u32)?);
+        }
+        Ok(bytes)
+    }
+}
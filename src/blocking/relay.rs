@@ -0,0 +1,132 @@
+use super::pqkd::PqkdClient;
+use crate::error::PqkdError;
+use crate::relay::RelayMessage;
+use crate::Key;
+
+impl PqkdClient {
+    /// Starts relaying a new end-to-end key of `size` bits to the SAE
+    /// `destination_sae_id`, over the link to the SAE `sae_id`, the first
+    /// node of the path (see [crate::relay]). Returns the key and the
+    /// message to send to that node. The local SAE ID of the client must
+    /// be set.
+    pub fn relay_key(
+        &self,
+        sae_id: &str,
+        destination_sae_id: &str,
+        size: u16,
+    ) -> Result<(Key, RelayMessage), PqkdError> {
+        let local_sae_id = self.local_sae_id();
+        if local_sae_id.is_empty() {
+            return Err(PqkdError::RelayError("local SAE ID is not set".to_string()));
+        }
+        let hop_key = self.relay_hop_key(sae_id, size)?;
+        RelayMessage::start(local_sae_id, destination_sae_id, &hop_key)
+    }
+
+    /// Decrypts the end-to-end key of `message`, received over the link
+    /// to the SAE `sae_id`, the previous node of the path.
+    pub fn receive_relayed_key(
+        &self,
+        sae_id: &str,
+        message: &RelayMessage,
+    ) -> Result<Key, PqkdError> {
+        let mut keys = self
+            .dec_keys(sae_id)
+            .key_ids(vec![message.hop_key_id.as_str()])
+            .send()?
+            .keys();
+        let hop_key = keys.pop().ok_or_else(|| {
+            PqkdError::RelayError(format!("key {} is missing", message.hop_key_id))
+        })?;
+        message.open(&hop_key)
+    }
+
+    /// Returns the message relaying `key`, received with `message`, over
+    /// the link to the SAE `sae_id`, the next node of the path.
+    pub fn forward_relayed_key(
+        &self,
+        sae_id: &str,
+        key: &Key,
+        message: &RelayMessage,
+    ) -> Result<RelayMessage, PqkdError> {
+        let size = key
+            .material()?
+            .len()
+            .checked_mul(8)
+            .and_then(|size| u16::try_from(size).ok())
+            .ok_or_else(|| PqkdError::RelayError("key is too long".to_string()))?;
+        let hop_key = self.relay_hop_key(sae_id, size)?;
+        message.forward(key, &hop_key)
+    }
+
+    fn relay_hop_key(&self, sae_id: &str, size: u16) -> Result<Key, PqkdError> {
+        self.enc_keys(sae_id)
+            .size(size)
+            .send()?
+            .keys()
+            .pop()
+            .ok_or(PqkdError::ErrorKmeRequest)
+    }
+}
+
+/// Relays keys over a path of links whose clients are all in this
+/// process (see [crate::relay]).
+///
+/// Every hop is a link given by the clients of its two ends, whose local
+/// SAE IDs must be set. The receiving client of a hop and the sending
+/// client of the next hop belong to the same node.
+///
+/// # Example
+///
+/// ```no_run
+/// use pqkd::blocking::{BuilderPqkdClient, KeyRelay};
+/// use std::error::Error;
+///
+/// fn main() -> Result<(), Box<dyn Error>> {
+///     let client = |addr: &str, sae_id: &str| {
+///         BuilderPqkdClient::with_addr(addr).map(|builder| builder.with_local_sae_id(sae_id).build())
+///     };
+///     let relay = KeyRelay::new()
+///         .with_hop(client("http://172.16.0.154:8082", "A")?, client("http://172.16.0.155:8082", "B")?)
+///         .with_hop(client("http://172.16.0.155:8082", "B")?, client("http://172.16.0.156:8082", "C")?);
+///     let (key_a, key_c) = relay.relay_key(256)?;
+///     assert_eq!(key_a, key_c);
+///     Ok(())
+/// }
+/// ```
+#[derive(Clone, Default)]
+pub struct KeyRelay {
+    hops: Vec<(PqkdClient, PqkdClient)>,
+}
+
+impl KeyRelay {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends the link from the client `sender` to the client `receiver`
+    /// to the path.
+    pub fn with_hop(mut self, sender: PqkdClient, receiver: PqkdClient) -> Self {
+        self.hops.push((sender, receiver));
+        self
+    }
+
+    /// Relays a new end-to-end key of `size` bits from the first to the
+    /// last SAE of the path and returns the key as derived by each of them.
+    pub fn relay_key(&self, size: u16) -> Result<(Key, Key), PqkdError> {
+        let (source, first_node, destination) = match (self.hops.first(), self.hops.last()) {
+            (Some((source, first_node)), Some((_, destination))) => {
+                (source, first_node, destination)
+            }
+            _ => return Err(PqkdError::RelayError("empty path".to_string())),
+        };
+        let (source_key, mut message) =
+            source.relay_key(first_node.local_sae_id(), destination.local_sae_id(), size)?;
+        let mut key = first_node.receive_relayed_key(source.local_sae_id(), &message)?;
+        for (sender, receiver) in &self.hops[1..] {
+            message = sender.forward_relayed_key(receiver.local_sae_id(), &key, &message)?;
+            key = receiver.receive_relayed_key(sender.local_sae_id(), &message)?;
+        }
+        Ok((source_key, key))
+    }
+}
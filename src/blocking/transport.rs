@@ -0,0 +1,76 @@
+//! Transports of the blocking client (see [crate::transport]).
+use std::sync::Arc;
+
+use reqwest::blocking::Client;
+
+use crate::error::PqkdError;
+use crate::tls::CertReloader;
+use crate::transport::{MemoryTransport, TransportRequest, TransportResponse};
+
+/// Executes the HTTP exchanges of a [PqkdClient](super::PqkdClient).
+///
+/// The transport returns responses of any status; the client checks
+/// the status itself.
+pub trait Transport: Send + Sync {
+    fn execute(&self, request: TransportRequest) -> Result<TransportResponse, PqkdError>;
+}
+
+/// Transport sending requests with a [reqwest::blocking::Client].
+#[derive(Clone)]
+pub struct ReqwestTransport {
+    client: Client,
+    reloader: Option<Arc<CertReloader<Client>>>,
+}
+
+impl ReqwestTransport {
+    pub fn new(client: Client) -> Self {
+        Self {
+            client,
+            reloader: None,
+        }
+    }
+
+    /// Uses the client of `reloader`, rebuilt when the certificates are rotated.
+    pub(crate) fn with_reloader(self, reloader: CertReloader<Client>) -> Self {
+        Self {
+            reloader: Some(Arc::new(reloader)),
+            ..self
+        }
+    }
+
+    /// Returns the HTTP client, rebuilt if the certificates were rotated.
+    fn client(&self) -> Client {
+        match &self.reloader {
+            Some(reloader) => reloader.client(),
+            None => self.client.clone(),
+        }
+    }
+}
+
+impl Transport for ReqwestTransport {
+    fn execute(&self, request: TransportRequest) -> Result<TransportResponse, PqkdError> {
+        let res = self
+            .client()
+            .request(request.method, request.url)
+            .headers(request.headers)
+            .body(request.body)
+            .send()?;
+        let peer_certificate = res
+            .extensions()
+            .get::<reqwest::tls::TlsInfo>()
+            .and_then(|info| info.peer_certificate())
+            .map(<[u8]>::to_vec);
+        Ok(TransportResponse {
+            status: res.status(),
+            headers: res.headers().clone(),
+            peer_certificate,
+            body: res.bytes()?.to_vec(),
+        })
+    }
+}
+
+impl Transport for MemoryTransport {
+    fn execute(&self, request: TransportRequest) -> Result<TransportResponse, PqkdError> {
+        self.handle(request)
+    }
+}
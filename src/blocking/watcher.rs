@@ -0,0 +1,124 @@
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Instant;
+
+use super::pqkd::PqkdClient;
+use crate::error::PqkdError;
+use crate::request::{PqkdMethod, PqkdRequest};
+use crate::watcher::{LinkStats, WatchEvent, WatchState, WatcherConfig};
+
+type Callback = Arc<dyn Fn(&WatchEvent) + Send + Sync>;
+
+/// Watches the key buffers of links by requesting their status
+/// periodically.
+///
+/// Events are passed to the callbacks registered with
+/// [on_event](Self::on_event). The watcher can be cloned, e.g. to
+/// [run](Self::run) it on a thread of its own while reading
+/// [stats](Self::stats) elsewhere and [stopping](Self::stop) it.
+///
+/// # Example
+///
+/// ```no_run
+/// use pqkd::blocking::{BuilderPqkdClient, StatusWatcher};
+/// use pqkd::watcher::{WatchEvent, WatcherConfig};
+/// use std::error::Error;
+///
+/// fn main() -> Result<(), Box<dyn Error>> {
+///     let pqkd_client = BuilderPqkdClient::with_addr("http://172.16.0.154:8082")?
+///         .build();
+///     let watcher = StatusWatcher::new(pqkd_client, WatcherConfig {
+///         sae_ids: vec!["Test_2SAE".to_string()],
+///         thresholds: vec![1000],
+///         ..WatcherConfig::default()
+///     })
+///     .on_event(|event| {
+///         if let WatchEvent::BelowThreshold { sae_id, .. } = event {
+///             println!("link to {} runs low on keys", sae_id);
+///         }
+///     });
+///     let running = watcher.clone();
+///     let thread = std::thread::spawn(move || running.run());
+///     // ...
+///     watcher.stop();
+///     thread.join().unwrap();
+///     Ok(())
+/// }
+/// ```
+#[derive(Clone)]
+pub struct StatusWatcher {
+    pqkd_client: PqkdClient,
+    config: WatcherConfig,
+    state: Arc<Mutex<WatchState>>,
+    callbacks: Vec<Callback>,
+    /// Set by [stop](Self::stop), shared by the clones.
+    stopped: Arc<(Mutex<bool>, Condvar)>,
+}
+
+impl StatusWatcher {
+    pub fn new(pqkd_client: PqkdClient, config: WatcherConfig) -> Self {
+        Self {
+            pqkd_client,
+            config,
+            state: Arc::default(),
+            callbacks: Vec::new(),
+            stopped: Arc::default(),
+        }
+    }
+
+    /// Calls `callback` with every event emitted.
+    pub fn on_event(mut self, callback: impl Fn(&WatchEvent) + Send + Sync + 'static) -> Self {
+        self.callbacks.push(Arc::new(callback));
+        self
+    }
+
+    /// Returns the key buffer of the link to `sae_id` as last seen.
+    pub fn stats(&self, sae_id: &str) -> Option<LinkStats> {
+        self.state.lock().unwrap().stats(sae_id)
+    }
+
+    /// Requests the status of every watched link once, passes the events
+    /// it emits to the callbacks and returns them.
+    pub fn poll(&self) -> Vec<WatchEvent> {
+        let mut events = Vec::new();
+        for sae_id in &self.config.sae_ids {
+            let result = self
+                .pqkd_client
+                .kme_execute_request(PqkdRequest::new(PqkdMethod::Status, sae_id))
+                .and_then(|response| response.as_status().ok_or(PqkdError::ErrorKmeRequest));
+            let mut state = self.state.lock().unwrap();
+            events.extend(state.update(
+                sae_id,
+                &self.config.thresholds,
+                result.as_ref(),
+                Instant::now(),
+            ));
+        }
+        for event in &events {
+            for callback in &self.callbacks {
+                callback(event);
+            }
+        }
+        events
+    }
+
+    /// Polls the watched links every `interval` of the config until
+    /// [stop](Self::stop) is called, so run it on a thread of its own.
+    pub fn run(self) {
+        let (stopped, wake) = &*self.stopped;
+        while !*stopped.lock().unwrap() {
+            self.poll();
+            let guard = stopped.lock().unwrap();
+            let _ = wake
+                .wait_timeout_while(guard, self.config.interval, |stopped| !*stopped)
+                .unwrap();
+        }
+    }
+
+    /// Makes [run](Self::run) of this watcher and its clones return after
+    /// the poll in progress, if any. Watchers cannot be restarted.
+    pub fn stop(&self) {
+        let (stopped, wake) = &*self.stopped;
+        *stopped.lock().unwrap() = true;
+        wake.notify_all();
+    }
+}
@@ -0,0 +1,302 @@
+//! Circuit breakers around the KME and QRNG endpoints.
+//!
+//! With `with_circuit_breaker` of the client builders, a client stops
+//! sending requests to an endpoint after `failure_threshold` consecutive
+//! failures and fails them at once with [PqkdError::CircuitOpen]. After
+//! `reset_timeout` a single probe request is let through (half-open): if it
+//! succeeds the circuit closes again, otherwise it stays open for another
+//! `reset_timeout`. The KME and the QRNG have circuits of their own, shared
+//! by all clones of the client.
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::error::PqkdError;
+
+/// When a circuit opens and how long it stays open.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BreakerPolicy {
+    /// Consecutive failed requests after which the circuit opens.
+    pub failure_threshold: u32,
+    /// Time after which an open circuit lets a probe request through.
+    pub reset_timeout: Duration,
+}
+
+impl Default for BreakerPolicy {
+    fn default() -> Self {
+        Self {
+            failure_threshold: 5,
+            reset_timeout: Duration::from_secs(30),
+        }
+    }
+}
+
+/// Endpoint of the pQKD device guarded by a circuit.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Endpoint {
+    Kme,
+    Qrng,
+}
+
+/// State of a circuit.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BreakerState {
+    /// Requests are sent.
+    Closed,
+    /// Requests fail without being sent.
+    Open,
+    /// A probe request is in flight or may be sent.
+    HalfOpen,
+}
+
+/// Circuits of the KME and the QRNG of a client.
+#[derive(Clone)]
+pub(crate) struct CircuitBreakers {
+    kme: CircuitBreaker,
+    qrng: CircuitBreaker,
+}
+
+impl CircuitBreakers {
+    pub(crate) fn new(policy: BreakerPolicy) -> Self {
+        Self {
+            kme: CircuitBreaker::new(Endpoint::Kme, policy),
+            qrng: CircuitBreaker::new(Endpoint::Qrng, policy),
+        }
+    }
+
+    pub(crate) fn get(&self, endpoint: Endpoint) -> &CircuitBreaker {
+        match endpoint {
+            Endpoint::Kme => &self.kme,
+            Endpoint::Qrng => &self.qrng,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct CircuitBreaker {
+    endpoint: Endpoint,
+    policy: BreakerPolicy,
+    circuit: Arc<Mutex<Circuit>>,
+}
+
+struct Circuit {
+    state: BreakerState,
+    failures: u32,
+    opened_at: Instant,
+    probing: bool,
+}
+
+impl CircuitBreaker {
+    fn new(endpoint: Endpoint, policy: BreakerPolicy) -> Self {
+        Self {
+            endpoint,
+            policy,
+            circuit: Arc::new(Mutex::new(Circuit {
+                state: BreakerState::Closed,
+                failures: 0,
+                opened_at: Instant::now(),
+                probing: false,
+            })),
+        }
+    }
+
+    pub(crate) fn state(&self) -> BreakerState {
+        let circuit = self.circuit.lock().unwrap();
+        match circuit.state {
+            BreakerState::Open if circuit.opened_at.elapsed() >= self.policy.reset_timeout => {
+                BreakerState::HalfOpen
+            }
+            state => state,
+        }
+    }
+
+    /// Lets a request through, or fails if the circuit is open or a probe
+    /// is already in flight.
+    pub(crate) fn acquire(&self) -> Result<Attempt, PqkdError> {
+        self.acquire_at(Instant::now())
+    }
+
+    fn acquire_at(&self, now: Instant) -> Result<Attempt, PqkdError> {
+        let mut circuit = self.circuit.lock().unwrap();
+        if circuit.state == BreakerState::Open
+            && now.saturating_duration_since(circuit.opened_at) >= self.policy.reset_timeout
+        {
+            circuit.state = BreakerState::HalfOpen;
+        }
+        let probe = match circuit.state {
+            BreakerState::Closed => false,
+            BreakerState::HalfOpen if !circuit.probing => true,
+            _ => return Err(PqkdError::CircuitOpen(self.endpoint)),
+        };
+        circuit.probing |= probe;
+        Ok(Attempt {
+            breaker: Some(self.clone()),
+            probe,
+        })
+    }
+}
+
+/// A request let through by a circuit breaker.
+pub(crate) struct Attempt {
+    breaker: Option<CircuitBreaker>,
+    probe: bool,
+}
+
+impl Attempt {
+    /// An attempt of a client without circuit breakers.
+    pub(crate) fn unguarded() -> Self {
+        Self {
+            breaker: None,
+            probe: false,
+        }
+    }
+
+    /// Records the outcome of the request.
+    pub(crate) fn finish<T>(self, result: Result<T, PqkdError>) -> Result<T, PqkdError> {
+        self.finish_at(result, Instant::now())
+    }
+
+    /// Records the outcome of the request, finished at `now`. A failure
+    /// opens the circuit for `reset_timeout` from `now`, however long the
+    /// request took.
+    fn finish_at<T>(mut self, result: Result<T, PqkdError>, now: Instant) -> Result<T, PqkdError> {
+        if let Some(breaker) = self.breaker.take() {
+            let failed = result.as_ref().is_err_and(is_failure);
+            let mut circuit = breaker.circuit.lock().unwrap();
+            if self.probe {
+                circuit.probing = false;
+            }
+            if !failed {
+                circuit.failures = 0;
+                circuit.state = BreakerState::Closed;
+            } else {
+                circuit.failures = circuit.failures.saturating_add(1);
+                if self.probe || circuit.failures >= breaker.policy.failure_threshold {
+                    circuit.state = BreakerState::Open;
+                    circuit.opened_at = now.max(circuit.opened_at);
+                }
+            }
+        }
+        result
+    }
+}
+
+impl Drop for Attempt {
+    fn drop(&mut self) {
+        // A probe dropped before it finished lets the next request probe.
+        if let Some(breaker) = self.breaker.take() {
+            let mut circuit = breaker.circuit.lock().unwrap();
+            if self.probe {
+                circuit.probing = false;
+            }
+        }
+    }
+}
+
+/// Returns true if `err` shows that the endpoint is unavailable. Answers
+/// of the device other than server errors count as successes, as does
+/// `503 Service Unavailable`, which the KME answers when it has no keys.
+fn is_failure(err: &PqkdError) -> bool {
+    match err {
+        PqkdError::RequestError(_) | PqkdError::TransportError(_) => true,
+        PqkdError::HttpStatus(status) => {
+            status.is_server_error() && *status != reqwest::StatusCode::SERVICE_UNAVAILABLE
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn failure() -> Result<(), PqkdError> {
+        Err(PqkdError::TransportError("connection refused".to_string()))
+    }
+
+    #[test]
+    fn opens_and_closes() {
+        let breaker = CircuitBreaker::new(
+            Endpoint::Kme,
+            BreakerPolicy {
+                failure_threshold: 2,
+                reset_timeout: Duration::from_secs(10),
+            },
+        );
+        let start = Instant::now();
+        let attempt = |at| breaker.acquire_at(at).unwrap();
+        assert!(attempt(start).finish_at(failure(), start).is_err());
+        attempt(start).finish_at(Ok(()), start).unwrap();
+        assert!(attempt(start).finish_at(failure(), start).is_err());
+        assert_eq!(breaker.state(), BreakerState::Closed);
+        assert!(attempt(start).finish_at(failure(), start).is_err());
+        assert_eq!(breaker.state(), BreakerState::Open);
+        assert!(matches!(
+            breaker.acquire_at(start + Duration::from_secs(5)),
+            Err(PqkdError::CircuitOpen(Endpoint::Kme))
+        ));
+
+        // One probe at a time; a failed probe opens the circuit again.
+        let later = start + Duration::from_secs(10);
+        let probe = attempt(later);
+        assert!(breaker.acquire_at(later).is_err());
+        assert!(probe.finish_at(failure(), later).is_err());
+        assert!(breaker.acquire_at(later + Duration::from_secs(5)).is_err());
+
+        let probe = attempt(later + Duration::from_secs(10));
+        probe
+            .finish_at(Ok(()), later + Duration::from_secs(10))
+            .unwrap();
+        assert_eq!(breaker.state(), BreakerState::Closed);
+    }
+
+    #[test]
+    fn slow_failure() {
+        // A request failing after more than reset_timeout, e.g. on a
+        // timeout, opens the circuit for reset_timeout from its failure.
+        let breaker = CircuitBreaker::new(
+            Endpoint::Kme,
+            BreakerPolicy {
+                failure_threshold: 1,
+                reset_timeout: Duration::from_secs(10),
+            },
+        );
+        let start = Instant::now();
+        let failed = start + Duration::from_secs(30);
+        let attempt = breaker.acquire_at(start).unwrap();
+        assert!(attempt.finish_at(failure(), failed).is_err());
+        assert!(breaker.acquire_at(failed).is_err());
+        assert!(breaker.acquire_at(failed + Duration::from_secs(5)).is_err());
+
+        // So does a slow probe.
+        let probe = breaker.acquire_at(failed + Duration::from_secs(10)).unwrap();
+        let failed = failed + Duration::from_secs(40);
+        assert!(probe.finish_at(failure(), failed).is_err());
+        assert!(breaker.acquire_at(failed + Duration::from_secs(5)).is_err());
+        assert!(breaker.acquire_at(failed + Duration::from_secs(10)).is_ok());
+    }
+
+    #[test]
+    fn dropped_probe() {
+        let breaker = CircuitBreaker::new(
+            Endpoint::Qrng,
+            BreakerPolicy {
+                failure_threshold: 1,
+                reset_timeout: Duration::ZERO,
+            },
+        );
+        assert!(breaker.acquire().unwrap().finish(failure()).is_err());
+        drop(breaker.acquire().unwrap());
+        assert!(breaker.acquire().is_ok());
+    }
+
+    #[test]
+    fn failures() {
+        use reqwest::StatusCode;
+        assert!(is_failure(&PqkdError::HttpStatus(StatusCode::BAD_GATEWAY)));
+        assert!(!is_failure(&PqkdError::HttpStatus(
+            StatusCode::SERVICE_UNAVAILABLE
+        )));
+        assert!(!is_failure(&PqkdError::HttpStatus(StatusCode::NOT_FOUND)));
+        assert!(!is_failure(&PqkdError::ErrorKmeRequest));
+    }
+}
@@ -0,0 +1,385 @@
+//! Client-side quotas on key consumption.
+//!
+//! Quotas are set with `with_quota` (for all requests of a client and its
+//! clones) and `with_sae_quota` (for the requests to one target SAE) of the
+//! client builders. They are enforced before a request is sent, so a
+//! request over the quota fails with [PqkdError::BudgetExceeded] without
+//! consuming keys of the link.
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::error::PqkdError;
+use crate::request::{PqkdMethod, PqkdRequest};
+
+const HOUR: Duration = Duration::from_secs(3600);
+
+/// Limits of key consumption. Limits left as `None` are not enforced.
+///
+/// The rate limits are token buckets: a client can request up to
+/// `keys_per_second` keys at once, and then again as many keys as the
+/// bucket has refilled in the meantime. Keys of requests refused by the
+/// KME are given back.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Quota {
+    /// Keys requested with `enc_keys` per second.
+    pub keys_per_second: Option<u32>,
+    /// Bits of key material requested with `enc_keys` per hour.
+    pub bits_per_hour: Option<u64>,
+    /// Key requests (`enc_keys` and `dec_keys`) in flight at the same time.
+    pub max_concurrent: Option<u32>,
+}
+
+/// Limit of a [Quota] exceeded by a request.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BudgetLimit {
+    KeysPerSecond,
+    BitsPerHour,
+    MaxConcurrent,
+}
+
+/// Quotas of a client and of its target SAEs, shared by its clones.
+#[derive(Clone, Default)]
+pub(crate) struct KeyBudget {
+    limiters: Arc<Mutex<Limiters>>,
+}
+
+#[derive(Default)]
+struct Limiters {
+    client: Option<Limiter>,
+    saes: HashMap<String, Limiter>,
+}
+
+struct Limiter {
+    keys: Option<Bucket>,
+    bits: Option<Bucket>,
+    max_concurrent: Option<u32>,
+    in_flight: u32,
+}
+
+impl Limiter {
+    fn new(quota: Quota, now: Instant) -> Self {
+        Self {
+            keys: quota
+                .keys_per_second
+                .map(|keys| Bucket::new(keys as f64, Duration::from_secs(1), now)),
+            bits: quota
+                .bits_per_hour
+                .map(|bits| Bucket::new(bits as f64, HOUR, now)),
+            max_concurrent: quota.max_concurrent,
+            in_flight: 0,
+        }
+    }
+
+    /// Returns the limit that `cost` exceeds and the remaining allowance.
+    fn check(&mut self, cost: &Cost, now: Instant) -> Option<(BudgetLimit, u64)> {
+        if let Some(max_concurrent) = self.max_concurrent {
+            if self.in_flight >= max_concurrent {
+                return Some((BudgetLimit::MaxConcurrent, 0));
+            }
+        }
+        if let Some(keys) = &mut self.keys {
+            keys.refill(now);
+            if keys.tokens < cost.keys as f64 {
+                return Some((BudgetLimit::KeysPerSecond, keys.tokens as u64));
+            }
+        }
+        if let Some(bits) = &mut self.bits {
+            bits.refill(now);
+            if bits.tokens < cost.bits as f64 {
+                return Some((BudgetLimit::BitsPerHour, bits.tokens as u64));
+            }
+        }
+        None
+    }
+
+    fn take(&mut self, cost: &Cost) {
+        self.in_flight += 1;
+        if let Some(keys) = &mut self.keys {
+            keys.tokens -= cost.keys as f64;
+        }
+        if let Some(bits) = &mut self.bits {
+            bits.tokens -= cost.bits as f64;
+        }
+    }
+
+    fn refund(&mut self, cost: &Cost) {
+        if let Some(keys) = &mut self.keys {
+            keys.tokens = (keys.tokens + cost.keys as f64).min(keys.capacity);
+        }
+        if let Some(bits) = &mut self.bits {
+            bits.tokens = (bits.tokens + cost.bits as f64).min(bits.capacity);
+        }
+    }
+}
+
+struct Bucket {
+    capacity: f64,
+    per_second: f64,
+    tokens: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    fn new(capacity: f64, period: Duration, now: Instant) -> Self {
+        Self {
+            capacity,
+            per_second: capacity / period.as_secs_f64(),
+            tokens: capacity,
+            updated: now,
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.per_second).min(self.capacity);
+        self.updated = now;
+    }
+}
+
+/// Keys and bits a request consumes.
+struct Cost {
+    keys: u64,
+    bits: u64,
+}
+
+impl Cost {
+    fn of(request: &PqkdRequest) -> Self {
+        match request.pqkd_method() {
+            PqkdMethod::EncKeys => {
+                let keys = match request.key_ids().len() {
+                    0 => request.number() as u64,
+                    ids => ids as u64,
+                };
+                Self {
+                    keys,
+                    bits: keys * request.size() as u64,
+                }
+            }
+            _ => Self { keys: 0, bits: 0 },
+        }
+    }
+}
+
+impl KeyBudget {
+    /// Sets the quota of all requests, or of the requests to `sae_id`.
+    pub(crate) fn with_quota(self, sae_id: Option<&str>, quota: Quota) -> Self {
+        {
+            let mut limiters = self.limiters.lock().unwrap();
+            let limiter = Limiter::new(quota, Instant::now());
+            match sae_id {
+                Some(sae_id) => {
+                    limiters.saes.insert(sae_id.to_string(), limiter);
+                }
+                None => limiters.client = Some(limiter),
+            }
+        }
+        self
+    }
+
+    /// Takes the keys, bits and concurrency `request` needs from the
+    /// quotas, or fails if one of them is exhausted.
+    pub(crate) fn acquire(&self, request: &PqkdRequest) -> Result<Permit, PqkdError> {
+        self.acquire_at(request, Instant::now())
+    }
+
+    fn acquire_at(&self, request: &PqkdRequest, now: Instant) -> Result<Permit, PqkdError> {
+        if matches!(request.pqkd_method(), PqkdMethod::Status) {
+            return Ok(Permit { taken: None });
+        }
+        let cost = Cost::of(request);
+        let mut guard = self.limiters.lock().unwrap();
+        let limiters = &mut *guard;
+        if let Some(client) = &mut limiters.client {
+            if let Some((limit, remaining)) = client.check(&cost, now) {
+                return Err(PqkdError::BudgetExceeded {
+                    limit,
+                    sae_id: None,
+                    remaining,
+                });
+            }
+        }
+        if let Some(sae) = limiters.saes.get_mut(request.sae_id()) {
+            if let Some((limit, remaining)) = sae.check(&cost, now) {
+                return Err(PqkdError::BudgetExceeded {
+                    limit,
+                    sae_id: Some(request.sae_id().to_string()),
+                    remaining,
+                });
+            }
+        }
+        if let Some(client) = &mut limiters.client {
+            client.take(&cost);
+        }
+        if let Some(sae) = limiters.saes.get_mut(request.sae_id()) {
+            sae.take(&cost);
+        }
+        Ok(Permit {
+            taken: Some((self.clone(), request.sae_id().to_string(), cost)),
+        })
+    }
+
+    fn release(&self, sae_id: &str, cost: &Cost, refund: bool) {
+        let mut guard = self.limiters.lock().unwrap();
+        let limiters = &mut *guard;
+        for limiter in limiters
+            .client
+            .iter_mut()
+            .chain(limiters.saes.get_mut(sae_id))
+        {
+            limiter.in_flight = limiter.in_flight.saturating_sub(1);
+            if refund {
+                limiter.refund(cost);
+            }
+        }
+    }
+}
+
+/// Share of the quotas held by a request in flight.
+pub(crate) struct Permit {
+    taken: Option<(KeyBudget, String, Cost)>,
+}
+
+impl Permit {
+    /// Gives the share back once the request finished with `result`.
+    /// Keys and bits are only given back if the KME certainly did not
+    /// serve the request: an open circuit breaker rejected it, connecting
+    /// to the KME failed, or the KME refused it with a client error (4xx)
+    /// or 503 Service Unavailable. Requests are only retried after the
+    /// latter errors, so none of their attempts was served either. Other
+    /// server errors and transport errors may have been returned after the
+    /// keys were handed out.
+    pub(crate) fn release<T>(mut self, result: Result<T, PqkdError>) -> Result<T, PqkdError> {
+        if let Some((budget, sae_id, cost)) = self.taken.take() {
+            let refund = match &result {
+                Err(PqkdError::HttpStatus(status)) => {
+                    status.is_client_error() || *status == reqwest::StatusCode::SERVICE_UNAVAILABLE
+                }
+                Err(PqkdError::RequestError(err)) => err.is_connect(),
+                Err(PqkdError::CircuitOpen(_)) => true,
+                _ => false,
+            };
+            budget.release(&sae_id, &cost, refund);
+        }
+        result
+    }
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        // Requests dropped before they finished may have been served.
+        if let Some((budget, sae_id, cost)) = self.taken.take() {
+            budget.release(&sae_id, &cost, false);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::breaker::Endpoint;
+
+    fn enc_keys(sae_id: &str, number: u32) -> PqkdRequest {
+        let mut request = PqkdRequest::new(PqkdMethod::EncKeys, sae_id);
+        request.set_number(number);
+        request
+    }
+
+    fn exceeded(result: Result<Permit, PqkdError>) -> (BudgetLimit, Option<String>, u64) {
+        match result {
+            Err(PqkdError::BudgetExceeded {
+                limit,
+                sae_id,
+                remaining,
+            }) => (limit, sae_id, remaining),
+            _ => panic!("budget not exceeded"),
+        }
+    }
+
+    #[test]
+    fn keys_per_second() {
+        let budget = KeyBudget::default().with_quota(
+            None,
+            Quota {
+                keys_per_second: Some(10),
+                ..Quota::default()
+            },
+        );
+        let start = Instant::now();
+        drop(budget.acquire_at(&enc_keys("B", 8), start).unwrap());
+        let result = budget.acquire_at(&enc_keys("B", 3), start);
+        assert_eq!(exceeded(result), (BudgetLimit::KeysPerSecond, None, 2));
+        let later = start + Duration::from_millis(100);
+        drop(budget.acquire_at(&enc_keys("B", 3), later).unwrap());
+        // Status requests are not limited.
+        let status = PqkdRequest::new(PqkdMethod::Status, "B");
+        assert!(budget.acquire_at(&status, later).is_ok());
+    }
+
+    #[test]
+    fn sae_quota() {
+        let budget = KeyBudget::default().with_quota(
+            Some("B"),
+            Quota {
+                bits_per_hour: Some(2048),
+                max_concurrent: Some(1),
+                ..Quota::default()
+            },
+        );
+        let start = Instant::now();
+        let permit = budget.acquire_at(&enc_keys("B", 2), start).unwrap();
+        let result = budget.acquire_at(&enc_keys("B", 1), start);
+        assert_eq!(exceeded(result).0, BudgetLimit::MaxConcurrent);
+        assert!(budget.acquire_at(&enc_keys("C", 100), start).is_ok());
+
+        // Refused requests give their bits back.
+        let status = reqwest::StatusCode::SERVICE_UNAVAILABLE;
+        assert!(permit
+            .release::<()>(Err(PqkdError::HttpStatus(status)))
+            .is_err());
+        drop(budget.acquire_at(&enc_keys("B", 4), start).unwrap());
+        let result = budget.acquire_at(&enc_keys("B", 1), start);
+        assert_eq!(
+            exceeded(result),
+            (BudgetLimit::BitsPerHour, Some("B".to_string()), 0)
+        );
+    }
+
+    #[test]
+    fn refund_statuses() {
+        let quota = Quota {
+            keys_per_second: Some(1),
+            ..Quota::default()
+        };
+        let start = Instant::now();
+        for (status, refund) in [
+            (reqwest::StatusCode::BAD_REQUEST, true),
+            (reqwest::StatusCode::UNAUTHORIZED, true),
+            (reqwest::StatusCode::SERVICE_UNAVAILABLE, true),
+            (reqwest::StatusCode::INTERNAL_SERVER_ERROR, false),
+            (reqwest::StatusCode::BAD_GATEWAY, false),
+            (reqwest::StatusCode::GATEWAY_TIMEOUT, false),
+        ] {
+            let budget = KeyBudget::default().with_quota(None, quota);
+            let permit = budget.acquire_at(&enc_keys("B", 1), start).unwrap();
+            let _ = permit.release::<()>(Err(PqkdError::HttpStatus(status)));
+            assert_eq!(
+                budget.acquire_at(&enc_keys("B", 1), start).is_ok(),
+                refund,
+                "{}",
+                status
+            );
+        }
+
+        // Requests rejected by an open circuit breaker never reached the KME.
+        let budget = KeyBudget::default().with_quota(None, quota);
+        let permit = budget.acquire_at(&enc_keys("B", 1), start).unwrap();
+        let _ = permit.release::<()>(Err(PqkdError::CircuitOpen(Endpoint::Kme)));
+        assert!(budget.acquire_at(&enc_keys("B", 1), start).is_ok());
+
+        let budget = KeyBudget::default().with_quota(None, quota);
+        let permit = budget.acquire_at(&enc_keys("B", 1), start).unwrap();
+        let _ = permit.release::<()>(Err(PqkdError::TransportError("reset".to_string())));
+        assert!(budget.acquire_at(&enc_keys("B", 1), start).is_err());
+    }
+}
@@ -0,0 +1,192 @@
+//! Keys combined from several independent links.
+//!
+//! A key delivered over one link is known to every trusted node of that
+//! link. `KeyCombiner` requests keys of the same size over several links,
+//! e.g. to different KMEs, and XORs them into one key, which stays secret
+//! as long as one of the links is not compromised.
+//!
+//! The master SAE requests the keys with `KeyCombiner::enc_keys` and sends
+//! the [CompositeKeyId]s to the slave SAE, which fetches the same keys with
+//! `KeyCombiner::dec_keys` on the same links, in the same order.
+use std::fmt;
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+
+use crate::error::PqkdError;
+use crate::Key;
+
+/// IDs of the keys a [CompositeKey] is combined from, one per link in the
+/// order of the links.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct CompositeKeyId {
+    pub key_ids: Vec<String>,
+}
+
+impl fmt::Display for CompositeKeyId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.key_ids.join("+"))
+    }
+}
+
+/// Key combined from keys delivered over several links.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CompositeKey {
+    key_id: CompositeKeyId,
+    key: String,
+}
+
+impl CompositeKey {
+    /// Returns the IDs of the combined keys.
+    pub fn key_id(&self) -> &CompositeKeyId {
+        &self.key_id
+    }
+
+    /// Returns the key (base64).
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+}
+
+/// Combines the `i`-th keys of every link into the `i`-th composite key.
+/// Every link must deliver as many keys as the first, of the same sizes.
+pub(crate) fn combine(links: Vec<Vec<Key>>) -> Result<Vec<CompositeKey>, PqkdError> {
+    let number = links.first().map_or(0, Vec::len);
+    if let Some((link, keys)) = links
+        .iter()
+        .enumerate()
+        .find(|(_, keys)| keys.len() != number)
+    {
+        return Err(PqkdError::CombineError(format!(
+            "link {} delivered {} keys instead of {}",
+            link,
+            keys.len(),
+            number
+        )));
+    }
+    (0..number)
+        .map(|index| {
+            let mut key_ids = Vec::with_capacity(links.len());
+            let mut combined: Vec<u8> = Vec::new();
+            for keys in &links {
+                let key = &keys[index];
+                let material = key.material()?;
+                if key_ids.is_empty() {
+                    combined = material;
+                } else if material.len() != combined.len() {
+                    return Err(PqkdError::CombineError(format!(
+                        "key {} has {} bytes instead of {}",
+                        key.key_id(),
+                        material.len(),
+                        combined.len()
+                    )));
+                } else {
+                    combined
+                        .iter_mut()
+                        .zip(&material)
+                        .for_each(|(byte, other)| *byte ^= other);
+                }
+                key_ids.push(key.key_id().to_string());
+            }
+            Ok(CompositeKey {
+                key_id: CompositeKeyId { key_ids },
+                key: BASE64.encode(combined),
+            })
+        })
+        .collect()
+}
+
+/// Returns the IDs of the keys to fetch over the link `link` to
+/// reconstruct `key_ids`.
+pub(crate) fn link_key_ids(
+    key_ids: &[CompositeKeyId],
+    link: usize,
+    links: usize,
+) -> Result<Vec<&str>, PqkdError> {
+    key_ids
+        .iter()
+        .map(|key_id| {
+            if key_id.key_ids.len() != links {
+                return Err(PqkdError::CombineError(format!(
+                    "key {} is combined from {} links instead of {}",
+                    key_id,
+                    key_id.key_ids.len(),
+                    links
+                )));
+            }
+            Ok(key_id.key_ids[link].as_str())
+        })
+        .collect()
+}
+
+/// Puts the keys delivered by `dec_keys` in the order of `key_ids`.
+pub(crate) fn in_order(mut keys: Vec<Key>, key_ids: &[&str]) -> Result<Vec<Key>, PqkdError> {
+    key_ids
+        .iter()
+        .map(|key_id| {
+            let index = keys
+                .iter()
+                .position(|key| key.key_id() == *key_id)
+                .ok_or_else(|| PqkdError::CombineError(format!("key {} is missing", key_id)))?;
+            Ok(keys.swap_remove(index))
+        })
+        .collect()
+}
+
+/// Fails if a combiner has fewer than two links.
+pub(crate) fn check_links(links: usize) -> Result<(), PqkdError> {
+    if links < 2 {
+        return Err(PqkdError::CombineError(format!(
+            "{} links, at least 2 are needed",
+            links
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(key_id: &str, key: &[u8]) -> Key {
+        serde_json::from_value(serde_json::json!({"key_ID": key_id, "key": BASE64.encode(key)}))
+            .unwrap()
+    }
+
+    #[test]
+    fn xor() {
+        let combined = combine(vec![
+            vec![key("a1", &[0x0f, 0xf0]), key("a2", &[1, 2])],
+            vec![key("b1", &[0xff, 0xff]), key("b2", &[1, 2])],
+            vec![key("c1", &[0x01, 0x10]), key("c2", &[3, 3])],
+        ])
+        .unwrap();
+        assert_eq!(combined.len(), 2);
+        assert_eq!(combined[0].key_id().to_string(), "a1+b1+c1");
+        assert_eq!(combined[0].key(), BASE64.encode([0xf1, 0x1f]));
+        assert_eq!(combined[1].key(), BASE64.encode([3, 3]));
+    }
+
+    #[test]
+    fn mismatch() {
+        let result = combine(vec![vec![key("a1", &[1, 2])], vec![key("b1", &[1])]]);
+        assert!(matches!(result, Err(PqkdError::CombineError(_))));
+        let result = combine(vec![vec![key("a1", &[1])], vec![]]);
+        assert!(matches!(result, Err(PqkdError::CombineError(_))));
+        assert!(check_links(1).is_err());
+    }
+
+    #[test]
+    fn reconstruct() {
+        let key_ids = vec![CompositeKeyId {
+            key_ids: vec!["a1".to_string(), "b1".to_string()],
+        }];
+        assert_eq!(link_key_ids(&key_ids, 1, 2).unwrap(), ["b1"]);
+        assert!(link_key_ids(&key_ids, 0, 3).is_err());
+
+        let keys = in_order(vec![key("b2", &[2]), key("b1", &[1])], &["b1", "b2"]).unwrap();
+        assert_eq!(keys[0].key_id(), "b1");
+        assert!(in_order(keys, &["b3"]).is_err());
+    }
+}
@@ -0,0 +1,459 @@
+//! Client settings loaded from configuration files and the environment.
+//!
+//! A configuration file (TOML, YAML or JSON, chosen by the file extension)
+//! contains settings shared by all links at the top level and one named
+//! profile per link. Settings of a profile take precedence over the shared ones.
+//!
+//! ```toml
+//! default_profile = "alice"
+//! ca_cert = "certs/ca.pem"
+//! timeout_ms = 10000
+//!
+//! [retry]
+//! max_retries = 3
+//! backoff_ms = 200
+//!
+//! [profiles.alice]
+//! kme_addr = "https://172.16.0.154:8082"
+//! qrng_addr = "https://172.16.0.154:8085"
+//! client_cert = "certs/alice.pem"
+//! client_key = "certs/alice.key"
+//! local_sae_id = "Alice_SAE"
+//!
+//! [profiles.bob]
+//! kme_addr = "https://172.16.0.155:8082"
+//! ```
+//!
+//! Relative certificate paths are resolved against the directory of the file.
+//! The environment variables listed in [ClientConfig::with_env_overrides]
+//! override the settings of the selected profile.
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serde::Deserialize;
+
+use crate::error::PqkdError;
+use crate::retry::RetryPolicy;
+use crate::tls::TlsFiles;
+
+/// Settings of a connection to one pQKD device.
+///
+/// The `Debug` output redacts `pkcs12_password`.
+#[derive(Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct ClientConfig {
+    pub kme_addr: Option<String>,
+    pub qrng_addr: Option<String>,
+    /// CA certificate (PEM) used to verify the device.
+    pub ca_cert: Option<PathBuf>,
+    /// Client certificate (PEM).
+    pub client_cert: Option<PathBuf>,
+    /// Client key (PKCS#8, RSA or EC PEM).
+    pub client_key: Option<PathBuf>,
+    /// Client identity as PKCS#12 bundle, instead of `client_cert` and `client_key`.
+    pub pkcs12: Option<PathBuf>,
+    pub pkcs12_password: Option<String>,
+    /// Name the certificate of the device is verified against (see
+    /// [BuilderPqkdClient::with_server_name](crate::BuilderPqkdClient::with_server_name)).
+    pub server_name: Option<String>,
+    /// Interval in milliseconds at which the certificate files are checked
+    /// for changes. Certificates are not reloaded if unset.
+    pub tls_reload_interval_ms: Option<u64>,
+    pub local_sae_id: Option<String>,
+    /// ID of the KME responses are accepted from (see
+    /// [BuilderPqkdClient::with_kme_id](crate::BuilderPqkdClient::with_kme_id)).
+    pub kme_id: Option<String>,
+    /// SHA-256 fingerprint of the certificate of the KME (see
+    /// [BuilderPqkdClient::with_kme_fingerprint](crate::BuilderPqkdClient::with_kme_fingerprint)).
+    pub kme_fingerprint: Option<String>,
+    /// Timeout of whole requests in milliseconds.
+    pub timeout_ms: Option<u64>,
+    /// Timeout of establishing connections in milliseconds.
+    pub connect_timeout_ms: Option<u64>,
+    #[serde(default)]
+    pub retry: RetryConfig,
+}
+
+impl fmt::Debug for ClientConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ClientConfig")
+            .field("kme_addr", &self.kme_addr)
+            .field("qrng_addr", &self.qrng_addr)
+            .field("ca_cert", &self.ca_cert)
+            .field("client_cert", &self.client_cert)
+            .field("client_key", &self.client_key)
+            .field("pkcs12", &self.pkcs12)
+            .field("pkcs12_password", &self.pkcs12_password.as_ref().map(|_| "<redacted>"))
+            .field("server_name", &self.server_name)
+            .field("tls_reload_interval_ms", &self.tls_reload_interval_ms)
+            .field("local_sae_id", &self.local_sae_id)
+            .field("kme_id", &self.kme_id)
+            .field("kme_fingerprint", &self.kme_fingerprint)
+            .field("timeout_ms", &self.timeout_ms)
+            .field("connect_timeout_ms", &self.connect_timeout_ms)
+            .field("retry", &self.retry)
+            .finish()
+    }
+}
+
+/// Settings of the [RetryPolicy] of a profile.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+pub struct RetryConfig {
+    pub max_retries: Option<u32>,
+    pub backoff_ms: Option<u64>,
+    pub max_backoff_ms: Option<u64>,
+}
+
+/// Format of a configuration file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConfigFormat {
+    Toml,
+    Yaml,
+    Json,
+}
+
+impl ConfigFormat {
+    /// Returns the format of a file from its extension.
+    pub fn from_path(path: &Path) -> Result<Self, PqkdError> {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => Ok(ConfigFormat::Toml),
+            Some("yaml" | "yml") => Ok(ConfigFormat::Yaml),
+            Some("json") => Ok(ConfigFormat::Json),
+            _ => Err(PqkdError::ConfigError(format!(
+                "unknown format of {} (expected .toml, .yaml, .yml or .json)",
+                path.display()
+            ))),
+        }
+    }
+}
+
+/// Contents of a configuration file.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+pub struct ConfigFile {
+    /// Profile used when none is selected.
+    pub default_profile: Option<String>,
+    /// Settings shared by all profiles.
+    #[serde(flatten)]
+    pub shared: ClientConfig,
+    #[serde(default)]
+    pub profiles: HashMap<String, ClientConfig>,
+}
+
+impl ConfigFile {
+    /// Reads a configuration file and resolves relative certificate paths
+    /// against its directory.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, PqkdError> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path)?;
+        let mut config = Self::parse(&contents, ConfigFormat::from_path(path)?)?;
+        if let Some(dir) = path.parent() {
+            config.shared.resolve_paths(dir);
+            config
+                .profiles
+                .values_mut()
+                .for_each(|profile| profile.resolve_paths(dir));
+        }
+        Ok(config)
+    }
+
+    pub fn parse(contents: &str, format: ConfigFormat) -> Result<Self, PqkdError> {
+        let config = match format {
+            ConfigFormat::Toml => toml::from_str(contents).map_err(|err| err.to_string()),
+            ConfigFormat::Yaml => serde_yaml::from_str(contents).map_err(|err| err.to_string()),
+            ConfigFormat::Json => serde_json::from_str(contents).map_err(|err| err.to_string()),
+        };
+        config.map_err(|err| PqkdError::ConfigError(format!("invalid configuration: {}", err)))
+    }
+
+    /// Returns the settings of the profile `name`, or of the default profile
+    /// if `name` is `None`, merged with the shared settings.
+    /// Without a profile name and a default profile only the shared settings are returned.
+    pub fn profile(&self, name: Option<&str>) -> Result<ClientConfig, PqkdError> {
+        match name.or(self.default_profile.as_deref()) {
+            Some(name) => {
+                let profile = self
+                    .profiles
+                    .get(name)
+                    .ok_or_else(|| PqkdError::ConfigError(format!("unknown profile {:?}", name)))?;
+                Ok(self.shared.clone().merge(profile.clone()))
+            }
+            None => Ok(self.shared.clone()),
+        }
+    }
+}
+
+impl ClientConfig {
+    /// Loads the profile `name` from a configuration file and applies the
+    /// environment overrides. If `name` is `None`, the profile named by
+    /// `PQKD_PROFILE` or the default profile of the file is used.
+    pub fn load(path: impl AsRef<Path>, name: Option<&str>) -> Result<Self, PqkdError> {
+        let env_profile = std::env::var("PQKD_PROFILE").ok();
+        ConfigFile::load(path)?
+            .profile(name.or(env_profile.as_deref()))?
+            .with_env_overrides()
+    }
+
+    /// Overrides settings with the environment variables `PQKD_KME_ADDR`,
+    /// `PQKD_QRNG_ADDR`, `PQKD_CA_CERT`, `PQKD_CLIENT_CERT`, `PQKD_CLIENT_KEY`,
+    /// `PQKD_PKCS12`, `PQKD_PKCS12_PASSWORD`, `PQKD_SERVER_NAME`,
+    /// `PQKD_TLS_RELOAD_INTERVAL_MS`, `PQKD_LOCAL_SAE_ID`, `PQKD_KME_ID`,
+    /// `PQKD_KME_FINGERPRINT`, `PQKD_TIMEOUT_MS`, `PQKD_CONNECT_TIMEOUT_MS`,
+    /// `PQKD_RETRY_MAX_RETRIES`, `PQKD_RETRY_BACKOFF_MS` and `PQKD_RETRY_MAX_BACKOFF_MS`.
+    pub fn with_env_overrides(self) -> Result<Self, PqkdError> {
+        self.with_overrides(|name| std::env::var(name).ok())
+    }
+
+    pub(crate) fn with_overrides(
+        self,
+        var: impl Fn(&str) -> Option<String>,
+    ) -> Result<Self, PqkdError> {
+        let number = |name: &str| -> Result<Option<u64>, PqkdError> {
+            var(name)
+                .map(|value| {
+                    value.trim().parse().map_err(|_| {
+                        PqkdError::ConfigError(format!("{} must be a number, found {:?}", name, value))
+                    })
+                })
+                .transpose()
+        };
+        let overrides = ClientConfig {
+            kme_addr: var("PQKD_KME_ADDR"),
+            qrng_addr: var("PQKD_QRNG_ADDR"),
+            ca_cert: var("PQKD_CA_CERT").map(PathBuf::from),
+            client_cert: var("PQKD_CLIENT_CERT").map(PathBuf::from),
+            client_key: var("PQKD_CLIENT_KEY").map(PathBuf::from),
+            pkcs12: var("PQKD_PKCS12").map(PathBuf::from),
+            pkcs12_password: var("PQKD_PKCS12_PASSWORD"),
+            server_name: var("PQKD_SERVER_NAME"),
+            tls_reload_interval_ms: number("PQKD_TLS_RELOAD_INTERVAL_MS")?,
+            local_sae_id: var("PQKD_LOCAL_SAE_ID"),
+            kme_id: var("PQKD_KME_ID"),
+            kme_fingerprint: var("PQKD_KME_FINGERPRINT"),
+            timeout_ms: number("PQKD_TIMEOUT_MS")?,
+            connect_timeout_ms: number("PQKD_CONNECT_TIMEOUT_MS")?,
+            retry: RetryConfig {
+                max_retries: number("PQKD_RETRY_MAX_RETRIES")?
+                    .map(|retries| retries.min(u32::MAX as u64) as u32),
+                backoff_ms: number("PQKD_RETRY_BACKOFF_MS")?,
+                max_backoff_ms: number("PQKD_RETRY_MAX_BACKOFF_MS")?,
+            },
+        };
+        Ok(self.merge(overrides))
+    }
+
+    /// Returns the settings of `self` overridden by those set in `other`.
+    pub fn merge(self, other: ClientConfig) -> Self {
+        Self {
+            kme_addr: other.kme_addr.or(self.kme_addr),
+            qrng_addr: other.qrng_addr.or(self.qrng_addr),
+            ca_cert: other.ca_cert.or(self.ca_cert),
+            client_cert: other.client_cert.or(self.client_cert),
+            client_key: other.client_key.or(self.client_key),
+            pkcs12: other.pkcs12.or(self.pkcs12),
+            pkcs12_password: other.pkcs12_password.or(self.pkcs12_password),
+            server_name: other.server_name.or(self.server_name),
+            tls_reload_interval_ms: other.tls_reload_interval_ms.or(self.tls_reload_interval_ms),
+            local_sae_id: other.local_sae_id.or(self.local_sae_id),
+            kme_id: other.kme_id.or(self.kme_id),
+            kme_fingerprint: other.kme_fingerprint.or(self.kme_fingerprint),
+            timeout_ms: other.timeout_ms.or(self.timeout_ms),
+            connect_timeout_ms: other.connect_timeout_ms.or(self.connect_timeout_ms),
+            retry: RetryConfig {
+                max_retries: other.retry.max_retries.or(self.retry.max_retries),
+                backoff_ms: other.retry.backoff_ms.or(self.retry.backoff_ms),
+                max_backoff_ms: other.retry.max_backoff_ms.or(self.retry.max_backoff_ms),
+            },
+        }
+    }
+
+    pub(crate) fn kme_addr(&self) -> Result<&str, PqkdError> {
+        self.kme_addr
+            .as_deref()
+            .ok_or_else(|| PqkdError::ConfigError("kme_addr is not set".to_string()))
+    }
+
+    /// Returns the certificate files, which must be set either as
+    /// `ca_cert`, `client_cert` and `client_key`, or as `ca_cert` and `pkcs12`.
+    pub(crate) fn tls_files(&self) -> Result<Option<TlsFiles>, PqkdError> {
+        match (&self.ca_cert, &self.client_cert, &self.client_key, &self.pkcs12) {
+            (Some(ca_cert), Some(client_cert), Some(client_key), None) => {
+                Ok(Some(TlsFiles::pem(ca_cert, client_cert, client_key)))
+            }
+            (Some(ca_cert), None, None, Some(pkcs12)) => Ok(Some(TlsFiles::pkcs12(
+                ca_cert,
+                pkcs12,
+                self.pkcs12_password.as_deref().unwrap_or_default(),
+            ))),
+            (None, None, None, None) => Ok(None),
+            _ => Err(PqkdError::ConfigError(
+                "ca_cert must be set together with either client_cert and client_key, or pkcs12".to_string(),
+            )),
+        }
+    }
+
+    pub(crate) fn tls_reload_interval(&self) -> Option<Duration> {
+        self.tls_reload_interval_ms.map(Duration::from_millis)
+    }
+
+    pub(crate) fn timeout(&self) -> Option<Duration> {
+        self.timeout_ms.map(Duration::from_millis)
+    }
+
+    pub(crate) fn connect_timeout(&self) -> Option<Duration> {
+        self.connect_timeout_ms.map(Duration::from_millis)
+    }
+
+    pub(crate) fn retry_policy(&self) -> RetryPolicy {
+        let default = RetryPolicy::default();
+        RetryPolicy {
+            max_retries: self.retry.max_retries.unwrap_or(default.max_retries),
+            backoff: self.retry.backoff_ms.map_or(default.backoff, Duration::from_millis),
+            max_backoff: self
+                .retry
+                .max_backoff_ms
+                .map_or(default.max_backoff, Duration::from_millis),
+        }
+    }
+
+    fn resolve_paths(&mut self, dir: &Path) {
+        for path in [
+            &mut self.ca_cert,
+            &mut self.client_cert,
+            &mut self.client_key,
+            &mut self.pkcs12,
+        ]
+            .into_iter()
+            .flatten()
+        {
+            if path.is_relative() {
+                *path = dir.join(&*path);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOML: &str = r#"
+        default_profile = "alice"
+        ca_cert = "ca.pem"
+        timeout_ms = 5000
+
+        [retry]
+        max_retries = 3
+
+        [profiles.alice]
+        kme_addr = "https://172.16.0.154:8082"
+        local_sae_id = "Alice_SAE"
+
+        [profiles.bob]
+        kme_addr = "https://172.16.0.155:8082"
+        timeout_ms = 1000
+        retry = { backoff_ms = 50 }
+    "#;
+
+    #[test]
+    fn profiles() {
+        let config = ConfigFile::parse(TOML, ConfigFormat::Toml).unwrap();
+
+        let alice = config.profile(None).unwrap();
+        assert_eq!(alice.kme_addr.as_deref(), Some("https://172.16.0.154:8082"));
+        assert_eq!(alice.local_sae_id.as_deref(), Some("Alice_SAE"));
+        assert_eq!(alice.ca_cert, Some(PathBuf::from("ca.pem")));
+        assert_eq!(alice.timeout(), Some(Duration::from_secs(5)));
+
+        let bob = config.profile(Some("bob")).unwrap();
+        assert_eq!(bob.kme_addr.as_deref(), Some("https://172.16.0.155:8082"));
+        assert_eq!(bob.local_sae_id, None);
+        assert_eq!(bob.timeout(), Some(Duration::from_secs(1)));
+        assert_eq!(bob.retry_policy().max_retries, 3);
+        assert_eq!(bob.retry_policy().backoff, Duration::from_millis(50));
+
+        assert!(matches!(config.profile(Some("carol")), Err(PqkdError::ConfigError(_))));
+    }
+
+    #[test]
+    fn formats() {
+        let yaml = "
+            profiles:
+              alice:
+                kme_addr: https://172.16.0.154:8082
+                retry:
+                  max_retries: 2
+        ";
+        let json = r#"{"profiles": {"alice": {"kme_addr": "https://172.16.0.154:8082", "retry": {"max_retries": 2}}}}"#;
+        let yaml = ConfigFile::parse(yaml, ConfigFormat::Yaml).unwrap();
+        let json = ConfigFile::parse(json, ConfigFormat::Json).unwrap();
+        assert_eq!(yaml, json);
+        assert_eq!(json.profile(Some("alice")).unwrap().retry_policy().max_retries, 2);
+
+        assert!(ConfigFile::parse("kme_addr = ", ConfigFormat::Toml).is_err());
+        assert!(ConfigFormat::from_path(Path::new("pqkd.ini")).is_err());
+        assert_eq!(ConfigFormat::from_path(Path::new("pqkd.yml")).unwrap(), ConfigFormat::Yaml);
+    }
+
+    #[test]
+    fn env_overrides() {
+        let vars = HashMap::from([
+            ("PQKD_KME_ADDR", "http://127.0.0.1:8082"),
+            ("PQKD_TIMEOUT_MS", "250"),
+            ("PQKD_RETRY_MAX_RETRIES", "5"),
+            ("PQKD_KME_ID", "Test_1KME"),
+        ]);
+        let config = ConfigFile::parse(TOML, ConfigFormat::Toml)
+            .unwrap()
+            .profile(None)
+            .unwrap()
+            .with_overrides(|name| vars.get(name).map(|value| value.to_string()))
+            .unwrap();
+        assert_eq!(config.kme_addr.as_deref(), Some("http://127.0.0.1:8082"));
+        assert_eq!(config.local_sae_id.as_deref(), Some("Alice_SAE"));
+        assert_eq!(config.timeout(), Some(Duration::from_millis(250)));
+        assert_eq!(config.retry_policy().max_retries, 5);
+        assert_eq!(config.kme_id.as_deref(), Some("Test_1KME"));
+
+        let invalid = ClientConfig::default()
+            .with_overrides(|name| (name == "PQKD_TIMEOUT_MS").then(|| "soon".to_string()));
+        assert!(matches!(invalid, Err(PqkdError::ConfigError(_))));
+    }
+
+    #[test]
+    fn debug_redacts_password() {
+        let config = ClientConfig {
+            ca_cert: Some(PathBuf::from("ca.pem")),
+            pkcs12: Some(PathBuf::from("client.p12")),
+            pkcs12_password: Some("secret".to_string()),
+            ..Default::default()
+        };
+        let debug = format!("{:?}", config);
+        assert!(debug.contains("<redacted>"));
+        assert!(!debug.contains("secret"));
+        let files = format!("{:?}", config.tls_files().unwrap());
+        assert!(files.contains("client.p12"));
+        assert!(!files.contains("secret"));
+    }
+
+    #[test]
+    fn tls_paths() {
+        let mut config = ClientConfig {
+            ca_cert: Some(PathBuf::from("ca.pem")),
+            client_cert: Some(PathBuf::from("/etc/pqkd/client.pem")),
+            ..Default::default()
+        };
+        config.resolve_paths(Path::new("/opt/pqkd"));
+        assert_eq!(config.ca_cert, Some(PathBuf::from("/opt/pqkd/ca.pem")));
+        assert_eq!(config.client_cert, Some(PathBuf::from("/etc/pqkd/client.pem")));
+        assert!(matches!(config.tls_files(), Err(PqkdError::ConfigError(_))));
+
+        config.client_cert = None;
+        config.pkcs12 = Some(PathBuf::from("client.p12"));
+        config.resolve_paths(Path::new("/opt/pqkd"));
+        assert_eq!(
+            config.tls_files().unwrap(),
+            Some(TlsFiles::pkcs12("/opt/pqkd/ca.pem", "/opt/pqkd/client.p12", ""))
+        );
+    }
+}
@@ -0,0 +1,207 @@
+//! HMAC_DRBG (NIST SP 800-90A) with SHA-256.
+//!
+//! [HmacDrbg] is the deterministic part of the generator. The clients wrap
+//! it in `PqkdDrbg`, which takes the entropy input and nonce from the QRNG
+//! of the pQKD device and reseeds from it when needed.
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+use crate::error::PqkdError;
+
+type HmacSha256 = Hmac<Sha256>;
+
+const OUT_LEN: usize = 32;
+
+/// Number of bytes of entropy input requested from the QRNG
+/// (security strength of 256 bits).
+pub const ENTROPY_INPUT_LEN: usize = 32;
+/// Number of bytes of nonce requested from the QRNG at instantiation.
+pub const NONCE_LEN: usize = 16;
+/// Maximum number of bytes returned by a single generate call.
+pub const MAX_BYTES_PER_REQUEST: usize = 1 << 16;
+/// Maximum number of generate calls between reseeds allowed by SP 800-90A.
+pub const MAX_RESEED_INTERVAL: u64 = 1 << 48;
+
+/// Settings of a DRBG seeded from the QRNG.
+#[derive(Clone, Debug)]
+pub struct DrbgConfig {
+    /// Number of generate calls after which the DRBG is reseeded from the QRNG.
+    pub reseed_interval: u64,
+    /// Reseed from the QRNG before every generate call.
+    pub prediction_resistance: bool,
+    /// Personalization string mixed into the initial state.
+    pub personalization: Vec<u8>,
+}
+
+impl Default for DrbgConfig {
+    fn default() -> Self {
+        Self {
+            reseed_interval: 1024,
+            prediction_resistance: false,
+            personalization: Vec::new(),
+        }
+    }
+}
+
+impl DrbgConfig {
+    pub(crate) fn check(&self) -> Result<(), PqkdError> {
+        if self.reseed_interval == 0 || self.reseed_interval > MAX_RESEED_INTERVAL {
+            return Err(PqkdError::DrbgError(format!(
+                "reseed interval must be between 1 and {}",
+                MAX_RESEED_INTERVAL
+            )));
+        }
+        Ok(())
+    }
+}
+
+pub(crate) fn check_entropy_len(entropy: &[u8], expected: usize) -> Result<(), PqkdError> {
+    if entropy.len() != expected {
+        return Err(PqkdError::DrbgError(format!(
+            "expected {} bytes of entropy from QRNG, found {}",
+            expected,
+            entropy.len()
+        )));
+    }
+    Ok(())
+}
+
+/// Internal state of HMAC_DRBG.
+pub struct HmacDrbg {
+    key: [u8; OUT_LEN],
+    value: [u8; OUT_LEN],
+    reseed_counter: u64,
+    reseed_interval: u64,
+}
+
+impl HmacDrbg {
+    /// Instantiates the DRBG from the entropy input, nonce
+    /// and personalization string.
+    pub fn new(entropy: &[u8], nonce: &[u8], personalization: &[u8], reseed_interval: u64) -> Self {
+        let mut drbg = HmacDrbg {
+            key: [0x00; OUT_LEN],
+            value: [0x01; OUT_LEN],
+            reseed_counter: 1,
+            reseed_interval,
+        };
+        drbg.update(&[entropy, nonce, personalization]);
+        drbg
+    }
+
+    /// Reseeds the DRBG with fresh entropy input and optional additional input.
+    pub fn reseed(&mut self, entropy: &[u8], additional_input: &[u8]) {
+        self.update(&[entropy, additional_input]);
+        self.reseed_counter = 1;
+    }
+
+    /// Returns true if the reseed interval has been reached.
+    pub fn reseed_required(&self) -> bool {
+        self.reseed_counter > self.reseed_interval
+    }
+
+    /// Returns the number of generate calls since the last (re)seed plus one.
+    pub fn reseed_counter(&self) -> u64 {
+        self.reseed_counter
+    }
+
+    /// Fills `output` with pseudorandom bytes.
+    /// Fails if a reseed is required or more than
+    /// [MAX_BYTES_PER_REQUEST] bytes are requested.
+    pub fn generate(&mut self, output: &mut [u8], additional_input: &[u8]) -> Result<(), PqkdError> {
+        if self.reseed_required() {
+            return Err(PqkdError::DrbgError("reseed required".to_string()));
+        }
+        if output.len() > MAX_BYTES_PER_REQUEST {
+            return Err(PqkdError::DrbgError(format!(
+                "at most {} bytes per request",
+                MAX_BYTES_PER_REQUEST
+            )));
+        }
+        if !additional_input.is_empty() {
+            self.update(&[additional_input]);
+        }
+        for chunk in output.chunks_mut(OUT_LEN) {
+            self.value = self.hmac(&[&self.value]);
+            chunk.copy_from_slice(&self.value[..chunk.len()]);
+        }
+        self.update(&[additional_input]);
+        self.reseed_counter += 1;
+        Ok(())
+    }
+
+    fn update(&mut self, provided_data: &[&[u8]]) {
+        self.key = self.hmac(&[&self.value, &[0x00], &provided_data.concat()]);
+        self.value = self.hmac(&[&self.value]);
+        if provided_data.iter().all(|data| data.is_empty()) {
+            return;
+        }
+        self.key = self.hmac(&[&self.value, &[0x01], &provided_data.concat()]);
+        self.value = self.hmac(&[&self.value]);
+    }
+
+    fn hmac(&self, data: &[&[u8]]) -> [u8; OUT_LEN] {
+        let mut mac = HmacSha256::new_from_slice(&self.key).expect("HMAC accepts keys of any size");
+        for part in data {
+            mac.update(part);
+        }
+        mac.finalize().into_bytes().into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // NIST CAVP HMAC_DRBG, SHA-256, no prediction resistance,
+    // no reseed, no personalization string and no additional input, COUNT = 0.
+    #[test]
+    fn hmac_drbg_sha256_test_vector() {
+        let entropy = hex::decode("ca851911349384bffe89de1cbdc46e6831e44d34a4fb935ee285dd14b71a7488").unwrap();
+        let nonce = hex::decode("659ba96c601dc69fc902940805ec0ca8").unwrap();
+        let expected = "e528e9abf2dece54d47c7e75e5fe302149f817ea9fb4bee6f4199697d04d5b89\
+                        d54fbb978a15b5c443c9ec21036d2460b6f73ebad0dc2aba6e624abf07745bc1\
+                        07694bb7547bb0995f70de25d6b29e2d3011bb19d27676c07162c8b5ccde0668\
+                        961df86803482cb37ed6d5c0bb8d50cf1f50d476aa0458bdaba806f48be9dcb8";
+
+        let mut drbg = HmacDrbg::new(&entropy, &nonce, &[], MAX_RESEED_INTERVAL);
+        let mut output = [0u8; 128];
+        drbg.generate(&mut output, &[]).unwrap();
+        drbg.generate(&mut output, &[]).unwrap();
+
+        assert_eq!(hex::encode(output), expected);
+    }
+
+    #[test]
+    fn reseed_interval() {
+        let mut drbg = HmacDrbg::new(&[1; ENTROPY_INPUT_LEN], &[2; NONCE_LEN], &[], 2);
+        let mut output = [0u8; 16];
+        assert!(drbg.generate(&mut output, &[]).is_ok());
+        assert!(drbg.generate(&mut output, &[]).is_ok());
+        assert!(drbg.reseed_required());
+        assert!(drbg.generate(&mut output, &[]).is_err());
+
+        drbg.reseed(&[3; ENTROPY_INPUT_LEN], &[]);
+        assert_eq!(drbg.reseed_counter(), 1);
+        assert!(drbg.generate(&mut output, &[]).is_ok());
+    }
+
+    #[test]
+    fn request_too_large() {
+        let mut drbg = HmacDrbg::new(&[1; ENTROPY_INPUT_LEN], &[2; NONCE_LEN], &[], 10);
+        let mut output = vec![0u8; MAX_BYTES_PER_REQUEST + 1];
+        assert!(drbg.generate(&mut output, &[]).is_err());
+        assert!(drbg.generate(&mut output[..MAX_BYTES_PER_REQUEST], &[]).is_ok());
+    }
+
+    #[test]
+    fn reseed_changes_output() {
+        let mut first = HmacDrbg::new(&[1; ENTROPY_INPUT_LEN], &[2; NONCE_LEN], b"pqkd", 10);
+        let mut second = HmacDrbg::new(&[1; ENTROPY_INPUT_LEN], &[2; NONCE_LEN], b"pqkd", 10);
+        second.reseed(&[3; ENTROPY_INPUT_LEN], &[]);
+
+        let (mut a, mut b) = ([0u8; 32], [0u8; 32]);
+        first.generate(&mut a, &[]).unwrap();
+        second.generate(&mut b, &[]).unwrap();
+        assert_ne!(a, b);
+    }
+}
@@ -0,0 +1,123 @@
+//! Combining QRNG output with entropy from the operating system.
+//!
+//! When [EntropyMode::Mixed] is selected, every block of random data
+//! received from the pQKD device is hashed together with a block of the
+//! same length read from the OS random number generator (`getrandom`).
+//! Each 32-byte output block is
+//!
+//! ```text
+//! SHA-256("pqkd/entropy-mix/v1" || counter || qrng_block || os_block)
+//! ```
+//!
+//! Modelling SHA-256 as a random oracle, an output block can only be
+//! predicted by someone who can predict *both* input blocks, so the mixed
+//! output is never weaker than the stronger of the two sources.
+use sha2::{Digest, Sha256};
+
+use crate::error::PqkdError;
+
+const MIX_DOMAIN: &[u8] = b"pqkd/entropy-mix/v1";
+const BLOCK_SIZE: usize = 32;
+
+/// Selects how random values fetched from the QRNG are returned.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum EntropyMode {
+    /// Values are returned exactly as produced by the QRNG.
+    #[default]
+    Raw,
+    /// Values are combined with OS entropy before being returned.
+    Mixed,
+}
+
+/// Mixes `qrng` with the same amount of fresh OS entropy.
+/// The result has the same length as `qrng`.
+pub fn mix_with_os_entropy(qrng: &[u8]) -> Result<Vec<u8>, PqkdError> {
+    let mut os = vec![0u8; qrng.len()];
+    getrandom::getrandom(&mut os)?;
+    Ok(mix(qrng, &os))
+}
+
+/// Block-wise hash extractor combining two equally long entropy inputs.
+/// If the inputs differ in length, the shorter one is treated as
+/// padded with zeros. The result has the length of the longer input.
+pub fn mix(qrng: &[u8], os: &[u8]) -> Vec<u8> {
+    let len = qrng.len().max(os.len());
+    let mut out = Vec::with_capacity(len);
+    for (counter, offset) in (0..len).step_by(BLOCK_SIZE).enumerate() {
+        let end = (offset + BLOCK_SIZE).min(len);
+        let block = Sha256::new()
+            .chain_update(MIX_DOMAIN)
+            .chain_update((counter as u64).to_be_bytes())
+            .chain_update(padded_block(qrng, offset, end))
+            .chain_update(padded_block(os, offset, end))
+            .finalize();
+        out.extend_from_slice(&block[..end - offset]);
+    }
+    out
+}
+
+fn padded_block(input: &[u8], offset: usize, end: usize) -> [u8; BLOCK_SIZE] {
+    let mut block = [0u8; BLOCK_SIZE];
+    if offset < input.len() {
+        let end = end.min(input.len());
+        block[..end - offset].copy_from_slice(&input[offset..end]);
+    }
+    block
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mix_preserves_length() {
+        for len in [0, 1, 31, 32, 33, 1000] {
+            assert_eq!(mix(&vec![7u8; len], &vec![9u8; len]).len(), len);
+            assert_eq!(mix_with_os_entropy(&vec![7u8; len]).unwrap().len(), len);
+        }
+    }
+
+    #[test]
+    fn mix_is_deterministic_for_the_same_inputs() {
+        let qrng = [1u8; 100];
+        let os = [2u8; 100];
+        assert_eq!(mix(&qrng, &os), mix(&qrng, &os));
+    }
+
+    // A broken QRNG (constant output) must not make the mixed output
+    // predictable: it still carries the OS entropy.
+    #[test]
+    fn mixed_output_not_weaker_than_os_entropy() {
+        let broken_qrng = [0u8; 64];
+        let first = mix_with_os_entropy(&broken_qrng).unwrap();
+        let second = mix_with_os_entropy(&broken_qrng).unwrap();
+        assert_ne!(first, second);
+        assert_ne!(first, broken_qrng);
+    }
+
+    // A broken OS RNG (constant output) must not make the mixed output
+    // predictable: every QRNG byte influences the output block it is in.
+    #[test]
+    fn mixed_output_not_weaker_than_qrng_entropy() {
+        let broken_os = [0u8; 64];
+        let qrng = [0x5au8; 64];
+        let reference = mix(&qrng, &broken_os);
+        for i in 0..qrng.len() {
+            let mut flipped = qrng;
+            flipped[i] ^= 1;
+            let mixed = mix(&flipped, &broken_os);
+            let block = i / BLOCK_SIZE * BLOCK_SIZE;
+            assert_ne!(mixed[block..block + BLOCK_SIZE], reference[block..block + BLOCK_SIZE]);
+        }
+    }
+
+    #[test]
+    fn mixed_output_differs_from_both_inputs() {
+        let qrng = [3u8; 48];
+        let os = [4u8; 48];
+        let mixed = mix(&qrng, &os);
+        assert_ne!(mixed, qrng);
+        assert_ne!(mixed, os);
+        assert_ne!(mix(&qrng, &os), mix(&os, &qrng));
+    }
+}
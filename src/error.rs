@@ -1,5 +1,8 @@
 use thiserror::Error;
 
+use crate::breaker::Endpoint;
+use crate::budget::BudgetLimit;
+
 #[derive(Error, Debug)]
 pub enum PqkdError {
     #[error("Failed request")]
@@ -24,4 +27,91 @@ pub enum PqkdError {
     ErrorQrngRequest,
     #[error("Failed request to KME server.")]
     ErrorKmeRequest,
-}
\ No newline at end of file
+    #[error("Failed to read OS entropy")]
+    EntropyError(#[from] getrandom::Error),
+    #[error("Invalid {0} data received from QRNG server.")]
+    InvalidQrngData(String),
+    #[error("invalid size of random data (requested {requested:?}, found {found:?})")]
+    QrngSizeMismatch {
+        requested: u32,
+        found: u32,
+    },
+    #[error("size reported by the QRNG does not match its data (reported {reported:?}, found {found:?})")]
+    QrngReportedSizeMismatch {
+        reported: u32,
+        found: u32,
+    },
+    #[error("DRBG error: {0}")]
+    DrbgError(String),
+    #[error("Invalid parameters for random value: {0}")]
+    RandomParameterError(String),
+    #[error("Configuration error: {0}")]
+    ConfigError(String),
+    #[error("Server answered with HTTP status {0}")]
+    HttpStatus(reqwest::StatusCode),
+    #[error("Transport error: {0}")]
+    TransportError(String),
+    #[error("not enough keys stored (stored {stored:?}, required {required:?})")]
+    NotEnoughKeys {
+        stored: u32,
+        required: u32,
+    },
+    #[error("KME identity verification failed: {0}")]
+    KmeIdentityMismatch(String),
+    #[error("key budget exceeded ({limit:?} of {}, remaining {remaining:?})", sae_id.as_deref().unwrap_or("client"))]
+    BudgetExceeded {
+        limit: BudgetLimit,
+        sae_id: Option<String>,
+        remaining: u64,
+    },
+    #[error("circuit breaker of the {0:?} endpoint is open")]
+    CircuitOpen(Endpoint),
+    #[error("invalid audit log: {0}")]
+    AuditLogError(String),
+    #[error("key synchronisation failed: {0}")]
+    KeySyncError(String),
+    #[error("key {0} is not valid base64")]
+    InvalidKey(String),
+    #[error("key {0} differs from the key of the peer")]
+    KeyMismatch(String),
+    #[error("combining keys failed: {0}")]
+    CombineError(String),
+    #[error("key relay failed: {0}")]
+    RelayError(String),
+}
+
+impl PqkdError {
+    /// Returns the name of the variant, e.g. for labelling metrics.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            PqkdError::RequestError(_) => "RequestError",
+            PqkdError::SerdeJsonError(_) => "SerdeJsonError",
+            PqkdError::InvalidSize { .. } => "InvalidSize",
+            PqkdError::IoError(_) => "IoError",
+            PqkdError::NumberOfKeysError => "NumberOfKeysError",
+            PqkdError::SizeOfKeysError => "SizeOfKeysError",
+            PqkdError::BuildPqkdError(_) => "BuildPqkdError",
+            PqkdError::ErrorQrngRequest => "ErrorQrngRequest",
+            PqkdError::ErrorKmeRequest => "ErrorKmeRequest",
+            PqkdError::EntropyError(_) => "EntropyError",
+            PqkdError::InvalidQrngData(_) => "InvalidQrngData",
+            PqkdError::QrngSizeMismatch { .. } => "QrngSizeMismatch",
+            PqkdError::QrngReportedSizeMismatch { .. } => "QrngReportedSizeMismatch",
+            PqkdError::DrbgError(_) => "DrbgError",
+            PqkdError::RandomParameterError(_) => "RandomParameterError",
+            PqkdError::ConfigError(_) => "ConfigError",
+            PqkdError::HttpStatus(_) => "HttpStatus",
+            PqkdError::TransportError(_) => "TransportError",
+            PqkdError::NotEnoughKeys { .. } => "NotEnoughKeys",
+            PqkdError::KmeIdentityMismatch(_) => "KmeIdentityMismatch",
+            PqkdError::BudgetExceeded { .. } => "BudgetExceeded",
+            PqkdError::CircuitOpen(_) => "CircuitOpen",
+            PqkdError::AuditLogError(_) => "AuditLogError",
+            PqkdError::KeySyncError(_) => "KeySyncError",
+            PqkdError::InvalidKey(_) => "InvalidKey",
+            PqkdError::KeyMismatch(_) => "KeyMismatch",
+            PqkdError::CombineError(_) => "CombineError",
+            PqkdError::RelayError(_) => "RelayError",
+        }
+    }
+}
@@ -1,6 +1,8 @@
 // #![deny(missing_docs)]
 // #![deny(missing_debug_implementations)]
 // #![cfg_attr(test, deny(rust_2018_idioms))]
+// Helpers shared by the clients are unused when both are disabled.
+#![cfg_attr(not(any(feature = "async", feature = "blocking")), allow(dead_code, unused_imports))]
 //! # pqkd
 //!
 //! pqkd is a client implementation in and for Rust for pQKD from
@@ -14,17 +16,89 @@
 //! pqkd allows you to send keys to other pQKD devices,
 //! receive them and also receive random values from the
 //! pQKD device in hex, bytes, base64 format. 
+//!
+//! ## TLS
+//!
+//! Connections to the pQKD device use `native-tls` by default. Build with
+//! `default-features = false, features = ["rustls-tls", "async"]` to use
+//! rustls instead, e.g. for static musl builds without OpenSSL. One of the
+//! two features must be enabled.
+//!
+//! To make sure keys come from the expected KME, pin its ID or certificate
+//! with `BuilderPqkdClient::with_kme_id` and `with_kme_fingerprint`.
+//!
+//! ## Clients
+//!
+//! The asynchronous client (`PqkdClient`, feature `async`) and the
+//! blocking client (`blocking::PqkdClient`, feature `blocking`) are both
+//! enabled by default. Disable default features to compile only the one
+//! you use.
+//!
+//! ## Configuration files
+//!
+//! With the `config` feature, `BuilderPqkdClient::from_config` creates a
+//! client from a profile of a TOML, YAML or JSON file (see [config]).
+//!
+//! ## Tracing
+//!
+//! With the `tracing` feature, requests to the KME and QRNG are recorded as
+//! `tracing` spans and events (method, SAE ID, key IDs, sizes, status codes,
+//! latencies). Keys and random values are never recorded.
+//!
+//! ## Metrics
+//!
+//! Pass a [metrics::Metrics] registry to `with_metrics` of the client builders
+//! to count keys requested and delivered per SAE, key bits, QRNG bytes and
+//! errors, and to record request latencies. It renders them in the
+//! Prometheus text format.
+#[cfg(not(any(feature = "native-tls", feature = "rustls-tls")))]
+compile_error!("pqkd requires the `native-tls` or `rustls-tls` feature");
+
+#[cfg(feature = "async")]
+pub use crate::async_impl::combiner::KeyCombiner;
+#[cfg(feature = "async")]
+pub use crate::async_impl::drbg::PqkdDrbg;
+#[cfg(feature = "async")]
 pub use crate::async_impl::pqkd::BuilderPqkdClient;
+#[cfg(feature = "async")]
 pub use crate::async_impl::pqkd::PqkdClient;
+#[cfg(feature = "async")]
+pub use crate::async_impl::relay::KeyRelay;
+#[cfg(feature = "async")]
 pub use crate::async_impl::request_builder::PqkdRequestBuilder;
+#[cfg(feature = "async")]
+pub use crate::async_impl::watcher::StatusWatcher;
 pub use crate::response::{PqkdStatus, Key, PqkdResponse};
 pub(crate) use crate::response::Keys;
 
 
 pub mod qrng;
+pub mod entropy;
+pub mod drbg;
+#[cfg(feature = "config")]
+pub mod config;
+pub mod retry;
+pub mod budget;
+pub mod breaker;
+pub mod audit;
+pub mod sync;
+pub mod combiner;
+pub mod relay;
+pub mod metrics;
+pub mod watcher;
+pub mod tls;
+pub mod transport;
+mod trace;
+mod random;
 pub mod error;
+#[cfg(feature = "blocking")]
 pub mod blocking;
+#[cfg(feature = "async")]
 mod async_impl;
 pub mod request;
 pub mod response;
+#[cfg(feature = "tower")]
+pub mod service;
+#[cfg(feature = "sim")]
+pub mod sim;
 
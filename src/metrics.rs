@@ -0,0 +1,410 @@
+//! Metrics of key consumption, latency and errors.
+//!
+//! A [Metrics] registry is attached to clients with `with_metrics` on
+//! their builders; clients sharing a registry add to the same metrics.
+//! [Metrics::prometheus] renders them in the Prometheus text format, e.g.
+//! to be served on a `/metrics` endpoint:
+//!
+//! ```text
+//! pqkd_keys_requested_total{sae_id="Bob_SAE",method="enc_keys"} 12
+//! pqkd_keys_delivered_total{sae_id="Bob_SAE",method="enc_keys"} 12
+//! pqkd_key_bits_total{sae_id="Bob_SAE"} 3072
+//! pqkd_qrng_bytes_total 4096
+//! pqkd_errors_total{operation="enc_keys",error="HttpStatus"} 1
+//! pqkd_stored_key_count{sae_id="Bob_SAE"} 24988
+//! pqkd_request_duration_seconds_bucket{operation="status",le="0.005"} 3
+//! ```
+//!
+//! The `stored_key_count` gauge is updated with every status response.
+//! Use `poll_status` of the clients to keep it up to date.
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::error::PqkdError;
+use crate::qrng::QrngResponse;
+use crate::request::{PqkdMethod, PqkdRequest};
+use crate::response::PqkdResponse;
+
+/// Upper bounds in seconds of the buckets of the latency histograms.
+const LATENCY_BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// Registry of the metrics of one or more clients.
+#[derive(Clone, Default)]
+pub struct Metrics {
+    registry: Arc<Mutex<Registry>>,
+}
+
+#[derive(Default)]
+struct Registry {
+    keys_requested: BTreeMap<(String, &'static str), u64>,
+    keys_delivered: BTreeMap<(String, &'static str), u64>,
+    key_bits: BTreeMap<String, u64>,
+    qrng_bytes: u64,
+    errors: BTreeMap<(&'static str, &'static str), u64>,
+    latency: BTreeMap<&'static str, Histogram>,
+    stored_key_count: BTreeMap<String, u32>,
+}
+
+#[derive(Default)]
+struct Histogram {
+    /// Number of observations per bucket (not cumulative).
+    buckets: [u64; LATENCY_BUCKETS.len()],
+    count: u64,
+    sum: f64,
+}
+
+impl Histogram {
+    fn observe(&mut self, seconds: f64) {
+        if let Some(bucket) = LATENCY_BUCKETS.iter().position(|le| seconds <= *le) {
+            self.buckets[bucket] += 1;
+        }
+        self.count += 1;
+        self.sum += seconds;
+    }
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the number of keys requested for `sae_id` (with `enc_keys`
+    /// and `dec_keys`).
+    pub fn keys_requested(&self, sae_id: &str) -> u64 {
+        sum_for(&self.registry.lock().unwrap().keys_requested, sae_id)
+    }
+
+    /// Returns the number of keys delivered for `sae_id`.
+    pub fn keys_delivered(&self, sae_id: &str) -> u64 {
+        sum_for(&self.registry.lock().unwrap().keys_delivered, sae_id)
+    }
+
+    /// Returns the bits of key material delivered for `sae_id`.
+    pub fn key_bits(&self, sae_id: &str) -> u64 {
+        let registry = self.registry.lock().unwrap();
+        registry.key_bits.get(sae_id).copied().unwrap_or_default()
+    }
+
+    /// Returns the number of random bytes fetched from the QRNG.
+    pub fn qrng_bytes(&self) -> u64 {
+        self.registry.lock().unwrap().qrng_bytes
+    }
+
+    /// Returns the number of failed requests with errors of `kind`
+    /// (see [PqkdError::kind]).
+    pub fn errors(&self, kind: &str) -> u64 {
+        let registry = self.registry.lock().unwrap();
+        registry
+            .errors
+            .iter()
+            .filter(|((_, error), _)| *error == kind)
+            .map(|(_, count)| count)
+            .sum()
+    }
+
+    /// Returns the number of requests of `operation` (`status`,
+    /// `enc_keys`, `dec_keys` or `qrng`), including failed ones.
+    pub fn requests(&self, operation: &str) -> u64 {
+        let registry = self.registry.lock().unwrap();
+        registry
+            .latency
+            .get(operation)
+            .map_or(0, |histogram| histogram.count)
+    }
+
+    /// Returns the `stored_key_count` last reported for the link to `sae_id`.
+    pub fn stored_key_count(&self, sae_id: &str) -> Option<u32> {
+        self.registry
+            .lock()
+            .unwrap()
+            .stored_key_count
+            .get(sae_id)
+            .copied()
+    }
+
+    /// Renders the metrics in the Prometheus text exposition format.
+    pub fn prometheus(&self) -> String {
+        let registry = self.registry.lock().unwrap();
+        let mut out = String::new();
+
+        header(
+            &mut out,
+            "pqkd_keys_requested_total",
+            "counter",
+            "Keys requested per SAE.",
+        );
+        for ((sae_id, method), count) in &registry.keys_requested {
+            let _ = writeln!(
+                out,
+                "pqkd_keys_requested_total{{sae_id=\"{}\",method=\"{}\"}} {}",
+                escape(sae_id),
+                method,
+                count
+            );
+        }
+        header(
+            &mut out,
+            "pqkd_keys_delivered_total",
+            "counter",
+            "Keys delivered per SAE.",
+        );
+        for ((sae_id, method), count) in &registry.keys_delivered {
+            let _ = writeln!(
+                out,
+                "pqkd_keys_delivered_total{{sae_id=\"{}\",method=\"{}\"}} {}",
+                escape(sae_id),
+                method,
+                count
+            );
+        }
+        header(
+            &mut out,
+            "pqkd_key_bits_total",
+            "counter",
+            "Bits of key material delivered per SAE.",
+        );
+        for (sae_id, bits) in &registry.key_bits {
+            let _ = writeln!(
+                out,
+                "pqkd_key_bits_total{{sae_id=\"{}\"}} {}",
+                escape(sae_id),
+                bits
+            );
+        }
+        header(
+            &mut out,
+            "pqkd_qrng_bytes_total",
+            "counter",
+            "Random bytes fetched from the QRNG.",
+        );
+        let _ = writeln!(out, "pqkd_qrng_bytes_total {}", registry.qrng_bytes);
+        header(
+            &mut out,
+            "pqkd_errors_total",
+            "counter",
+            "Failed requests per error.",
+        );
+        for ((operation, error), count) in &registry.errors {
+            let _ = writeln!(
+                out,
+                "pqkd_errors_total{{operation=\"{}\",error=\"{}\"}} {}",
+                operation, error, count
+            );
+        }
+        header(
+            &mut out,
+            "pqkd_stored_key_count",
+            "gauge",
+            "Keys stored on the link, as last reported.",
+        );
+        for (sae_id, count) in &registry.stored_key_count {
+            let _ = writeln!(
+                out,
+                "pqkd_stored_key_count{{sae_id=\"{}\"}} {}",
+                escape(sae_id),
+                count
+            );
+        }
+        header(
+            &mut out,
+            "pqkd_request_duration_seconds",
+            "histogram",
+            "Duration of requests, including retries.",
+        );
+        for (operation, histogram) in &registry.latency {
+            let mut cumulative = 0;
+            for (le, count) in LATENCY_BUCKETS.iter().zip(histogram.buckets) {
+                cumulative += count;
+                let _ = writeln!(
+                    out,
+                    "pqkd_request_duration_seconds_bucket{{operation=\"{}\",le=\"{}\"}} {}",
+                    operation, le, cumulative
+                );
+            }
+            let _ = writeln!(
+                out,
+                "pqkd_request_duration_seconds_bucket{{operation=\"{}\",le=\"+Inf\"}} {}",
+                operation, histogram.count
+            );
+            let _ = writeln!(
+                out,
+                "pqkd_request_duration_seconds_sum{{operation=\"{}\"}} {}",
+                operation, histogram.sum
+            );
+            let _ = writeln!(
+                out,
+                "pqkd_request_duration_seconds_count{{operation=\"{}\"}} {}",
+                operation, histogram.count
+            );
+        }
+        out
+    }
+
+    /// Records a request to the KME, including its retries.
+    pub(crate) fn record_kme(
+        &self,
+        request: &PqkdRequest,
+        result: &Result<PqkdResponse, PqkdError>,
+        elapsed: Duration,
+    ) {
+        let operation = match request.pqkd_method() {
+            PqkdMethod::Status => "status",
+            PqkdMethod::EncKeys => "enc_keys",
+            PqkdMethod::DesKeys => "dec_keys",
+        };
+        let sae_id = request.sae_id().to_string();
+        let mut registry = self.registry.lock().unwrap();
+        registry
+            .latency
+            .entry(operation)
+            .or_default()
+            .observe(elapsed.as_secs_f64());
+        if !matches!(request.pqkd_method(), PqkdMethod::Status) {
+            let requested = match request.key_ids().len() {
+                0 => request.number() as u64,
+                ids => ids as u64,
+            };
+            *registry
+                .keys_requested
+                .entry((sae_id.clone(), operation))
+                .or_default() += requested;
+        }
+        match result {
+            Ok(PqkdResponse::Status(status)) => {
+                registry
+                    .stored_key_count
+                    .insert(sae_id, status.stored_key_count);
+            }
+            Ok(PqkdResponse::Keys(keys)) => {
+                let bits: u64 = keys.iter().map(|key| decoded_len(key.key()) * 8).sum();
+                *registry
+                    .keys_delivered
+                    .entry((sae_id.clone(), operation))
+                    .or_default() += keys.len() as u64;
+                *registry.key_bits.entry(sae_id).or_default() += bits;
+            }
+            Err(err) => *registry.errors.entry((operation, err.kind())).or_default() += 1,
+        }
+    }
+
+    /// Records a request to the QRNG, including its retries.
+    pub(crate) fn record_qrng(
+        &self,
+        size: u32,
+        result: &Result<QrngResponse, PqkdError>,
+        elapsed: Duration,
+    ) {
+        let mut registry = self.registry.lock().unwrap();
+        registry
+            .latency
+            .entry("qrng")
+            .or_default()
+            .observe(elapsed.as_secs_f64());
+        match result {
+            Ok(_) => registry.qrng_bytes += size as u64,
+            Err(err) => *registry.errors.entry(("qrng", err.kind())).or_default() += 1,
+        }
+    }
+}
+
+impl std::fmt::Debug for Metrics {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Metrics").finish_non_exhaustive()
+    }
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+fn sum_for(counters: &BTreeMap<(String, &'static str), u64>, sae_id: &str) -> u64 {
+    counters
+        .iter()
+        .filter(|((id, _), _)| id == sae_id)
+        .map(|(_, count)| count)
+        .sum()
+}
+
+/// Escapes a label value of the Prometheus text format.
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Returns the number of bytes encoded in a base64 string, without decoding it.
+pub(crate) fn decoded_len(base64: &str) -> u64 {
+    let padding = base64
+        .bytes()
+        .rev()
+        .take_while(|byte| *byte == b'=')
+        .count();
+    (base64.len() / 4 * 3).saturating_sub(padding) as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::StatusCode;
+
+    #[test]
+    fn histogram() {
+        let mut histogram = Histogram::default();
+        histogram.observe(0.001);
+        histogram.observe(0.3);
+        histogram.observe(60.0);
+        assert_eq!(histogram.buckets[0], 1);
+        assert_eq!(histogram.buckets[6], 1);
+        assert_eq!(histogram.buckets.iter().sum::<u64>(), 2);
+        assert_eq!(histogram.count, 3);
+    }
+
+    #[test]
+    fn key_bits() {
+        assert_eq!(
+            decoded_len("wHHVxRwDJs3/bXd38GHP3oe4svTuRpZS0yCC7x4Ly+s="),
+            32
+        );
+        assert_eq!(decoded_len("AAA="), 2);
+        assert_eq!(decoded_len(""), 0);
+    }
+
+    #[test]
+    fn prometheus() {
+        let metrics = Metrics::new();
+        let mut request = PqkdRequest::new(PqkdMethod::EncKeys, "Bob \"SAE\"");
+        request.set_number(3);
+        let err = PqkdError::HttpStatus(StatusCode::SERVICE_UNAVAILABLE);
+        metrics.record_kme(&request, &Err(err), Duration::from_millis(20));
+        metrics.record_qrng(
+            64,
+            &Err(PqkdError::ErrorQrngRequest),
+            Duration::from_millis(1),
+        );
+
+        let text = metrics.prometheus();
+        assert!(text.contains(
+            "pqkd_keys_requested_total{sae_id=\"Bob \\\"SAE\\\"\",method=\"enc_keys\"} 3\n"
+        ));
+        assert!(text.contains("pqkd_errors_total{operation=\"enc_keys\",error=\"HttpStatus\"} 1\n"));
+        assert!(
+            text.contains("pqkd_errors_total{operation=\"qrng\",error=\"ErrorQrngRequest\"} 1\n")
+        );
+        assert!(text.contains(
+            "pqkd_request_duration_seconds_bucket{operation=\"enc_keys\",le=\"0.01\"} 0\n"
+        ));
+        assert!(text.contains(
+            "pqkd_request_duration_seconds_bucket{operation=\"enc_keys\",le=\"0.025\"} 1\n"
+        ));
+        assert!(text.contains("pqkd_request_duration_seconds_count{operation=\"qrng\"} 1\n"));
+        assert!(text.contains("pqkd_qrng_bytes_total 0\n"));
+        assert_eq!(metrics.errors("HttpStatus"), 1);
+        assert_eq!(metrics.keys_delivered("Bob \"SAE\""), 0);
+    }
+}
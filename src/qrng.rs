@@ -1,11 +1,17 @@
+use std::collections::HashMap;
 use std::fmt::Display;
 
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use serde::Deserialize;
+
+use crate::entropy;
 use crate::error::PqkdError;
 
 pub const MAX_SIZE_FOR_BYTES_FORMAT: u32 = 16 * 1024 * 1024;
 pub const MAX_SIZE_FOR_STRING_FORMAT: u32 = 256 * 1024;
 
-#[derive(Clone)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum QrngFormat {
     Hex,
     Base64,
@@ -48,6 +54,7 @@ impl QrngFormat {
     }
 }
 
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum QrngReturnFormat {
     Hex(String),
     Bytes(Vec<u8>),
@@ -75,6 +82,221 @@ impl QrngReturnFormat {
             _ => None,
         }
     }
+
+    /// Returns the format of the random value.
+    pub fn format(&self) -> QrngFormat {
+        match self {
+            QrngReturnFormat::Hex(_) => QrngFormat::Hex,
+            QrngReturnFormat::Bytes(_) => QrngFormat::Bytes,
+            QrngReturnFormat::Base64(_) => QrngFormat::Base64,
+        }
+    }
+
+    /// Returns the random value as a lowercase hex string.
+    pub fn to_hex(&self) -> Result<String, PqkdError> {
+        Ok(hex::encode(self.to_bytes()?))
+    }
+
+    /// Returns the random value as a base64 string (standard alphabet, padded).
+    pub fn to_base64(&self) -> Result<String, PqkdError> {
+        Ok(BASE64.encode(self.to_bytes()?))
+    }
+
+    /// Converts the random value to the given format without loss.
+    /// Fails if the value is not validly encoded, even if it already has
+    /// the given format.
+    pub fn convert(self, format: QrngFormat) -> Result<QrngReturnFormat, PqkdError> {
+        if self.format() == format {
+            self.to_bytes()?;
+            return Ok(self);
+        }
+        Ok(match format {
+            QrngFormat::Hex => QrngReturnFormat::Hex(self.to_hex()?),
+            QrngFormat::Bytes => QrngReturnFormat::Bytes(self.to_bytes()?),
+            QrngFormat::Base64 => QrngReturnFormat::Base64(self.to_base64()?),
+        })
+    }
+
+    /// Combines the random value with OS entropy
+    /// (see [entropy](crate::entropy)), keeping its format and length.
+    pub fn mix_with_os_entropy(self) -> Result<QrngReturnFormat, PqkdError> {
+        let mixed = entropy::mix_with_os_entropy(&self.to_bytes()?)?;
+        Ok(match self {
+            QrngReturnFormat::Hex(_) => QrngReturnFormat::Hex(hex::encode(mixed)),
+            QrngReturnFormat::Bytes(_) => QrngReturnFormat::Bytes(mixed),
+            QrngReturnFormat::Base64(_) => QrngReturnFormat::Base64(BASE64.encode(mixed)),
+        })
+    }
+
+    /// Returns the random value as bytes, decoding the hex and base64 formats.
+    /// Fails if the value received from the device is not validly encoded.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, PqkdError> {
+        match self {
+            QrngReturnFormat::Hex(val) => hex::decode(val)
+                .map_err(|_| PqkdError::InvalidQrngData(QrngFormat::Hex.to_string())),
+            QrngReturnFormat::Bytes(val) => Ok(val.clone()),
+            QrngReturnFormat::Base64(val) => BASE64
+                .decode(val)
+                .map_err(|_| PqkdError::InvalidQrngData(QrngFormat::Base64.to_string())),
+        }
+    }
+}
+
+/// Random value received from the QRNG together with
+/// the metadata reported by the pQKD device.
+#[derive(Clone, Debug)]
+pub struct QrngResponse {
+    result: QrngReturnFormat,
+    size: u32,
+    reported_size: Option<u32>,
+    execute_time: Option<u64>,
+    headers: HashMap<String, String>,
+}
+
+#[derive(Deserialize)]
+struct QrngJson {
+    result: String,
+    size: Option<QrngJsonSize>,
+    format: Option<String>,
+    #[serde(rename = "executeTime")]
+    execute_time: Option<u64>,
+}
+
+/// Size reported by the QRNG, as a number or a string of digits.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum QrngJsonSize {
+    Number(u32),
+    Text(String),
+}
+
+impl QrngJsonSize {
+    fn value(self, format: QrngFormat) -> Result<u32, PqkdError> {
+        match self {
+            QrngJsonSize::Number(size) => Ok(size),
+            QrngJsonSize::Text(size) => size
+                .trim()
+                .parse()
+                .map_err(|_| PqkdError::InvalidQrngData(format.to_string())),
+        }
+    }
+}
+
+impl QrngResponse {
+    /// Parses the JSON body returned for the hex and base64 formats.
+    pub(crate) fn from_json(
+        format: QrngFormat,
+        size: u32,
+        headers: HashMap<String, String>,
+        body: &str,
+    ) -> Result<Self, PqkdError> {
+        let json: QrngJson = serde_json::from_str(body)?;
+        if json.format.is_some_and(|found| found != format.to_string()) {
+            return Err(PqkdError::InvalidQrngData(format.to_string()));
+        }
+        let result = match format {
+            QrngFormat::Hex => QrngReturnFormat::Hex(json.result),
+            QrngFormat::Base64 => QrngReturnFormat::Base64(json.result),
+            QrngFormat::Bytes => return Err(PqkdError::InvalidQrngData(format.to_string())),
+        };
+        let response = Self {
+            result,
+            size,
+            reported_size: json.size.map(|size| size.value(format)).transpose()?,
+            execute_time: json.execute_time,
+            headers,
+        };
+        response.check_size()?;
+        Ok(response)
+    }
+
+    /// Wraps the raw body returned for the bytes format.
+    pub(crate) fn from_bytes(
+        size: u32,
+        headers: HashMap<String, String>,
+        body: Vec<u8>,
+    ) -> Result<Self, PqkdError> {
+        let response = Self {
+            result: QrngReturnFormat::Bytes(body),
+            size,
+            reported_size: None,
+            execute_time: None,
+            headers,
+        };
+        response.check_size()?;
+        Ok(response)
+    }
+
+    fn check_size(&self) -> Result<(), PqkdError> {
+        let found = self.result.to_bytes()?.len();
+        if found != self.size as usize {
+            return Err(PqkdError::QrngSizeMismatch {
+                requested: self.size,
+                found: found as u32,
+            });
+        }
+        if let Some(reported) = self.reported_size {
+            if found != reported as usize {
+                return Err(PqkdError::QrngReportedSizeMismatch {
+                    reported,
+                    found: found as u32,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the random value.
+    pub fn result(&self) -> &QrngReturnFormat {
+        &self.result
+    }
+
+    /// Consumes the response and returns the random value.
+    pub fn into_result(self) -> QrngReturnFormat {
+        self.result
+    }
+
+    /// Returns the format of the random value.
+    pub fn format(&self) -> QrngFormat {
+        self.result.format()
+    }
+
+    /// Returns the number of random bytes, which is checked to be equal
+    /// to the requested size and to the [reported size](Self::reported_size).
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    /// Returns the number of random bytes reported by the QRNG (`size`).
+    /// Only available for the hex and base64 formats.
+    pub fn reported_size(&self) -> Option<u32> {
+        self.reported_size
+    }
+
+    /// Returns the execution time reported by the QRNG (`executeTime`).
+    /// Only available for the hex and base64 formats.
+    pub fn execute_time(&self) -> Option<u64> {
+        self.execute_time
+    }
+
+    /// Returns the headers of the HTTP response, with lowercase names.
+    pub fn headers(&self) -> &HashMap<String, String> {
+        &self.headers
+    }
+
+    pub(crate) fn headers_from(headers: &reqwest::header::HeaderMap) -> HashMap<String, String> {
+        headers
+            .iter()
+            .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+            .collect()
+    }
+
+    pub(crate) fn mix_with_os_entropy(self) -> Result<Self, PqkdError> {
+        Ok(Self {
+            result: self.result.mix_with_os_entropy()?,
+            ..self
+        })
+    }
 }
 
 #[cfg(test)]
@@ -117,5 +339,104 @@ mod tests {
         assert!(QrngReturnFormat::Base64("123".to_string()).as_base64().is_some());
         assert!(QrngReturnFormat::Bytes(vec![1,2,3]).as_base64().is_none());
     }
+
+    #[test]
+    fn qrng_return_format_mix_with_os_entropy() {
+        let hex = "5ede863536f9c2cb29e2ca26d5aef0e2dffe44c7".to_string();
+        let mixed = QrngReturnFormat::Hex(hex.clone()).mix_with_os_entropy().unwrap().as_hex().unwrap();
+        assert_eq!(mixed.len(), hex.len());
+        assert_ne!(mixed, hex);
+
+        let base64 = "w7jYDPNv789HHJTJg7iwkg4AYI0=".to_string();
+        let mixed = QrngReturnFormat::Base64(base64.clone()).mix_with_os_entropy().unwrap().as_base64().unwrap();
+        assert_eq!(mixed.len(), base64.len());
+        assert_ne!(mixed, base64);
+
+        let bytes = vec![1u8; 100];
+        let mixed = QrngReturnFormat::Bytes(bytes.clone()).mix_with_os_entropy().unwrap().as_bytes().unwrap();
+        assert_eq!(mixed.len(), bytes.len());
+        assert_ne!(mixed, bytes);
+
+        assert!(QrngReturnFormat::Hex("xyz".to_string()).mix_with_os_entropy().is_err());
+        assert!(QrngReturnFormat::Base64("!!!".to_string()).mix_with_os_entropy().is_err());
+    }
+
+    #[test]
+    fn qrng_return_format_convert() {
+        let bytes = vec![0x5e, 0xde, 0x86, 0x35, 0xff, 0x00];
+        let hex = QrngReturnFormat::Bytes(bytes.clone()).convert(QrngFormat::Hex).unwrap();
+        assert_eq!(hex, QrngReturnFormat::Hex("5ede8635ff00".to_string()));
+        let base64 = hex.convert(QrngFormat::Base64).unwrap();
+        assert_eq!(base64, QrngReturnFormat::Base64("Xt6GNf8A".to_string()));
+        let back = base64.convert(QrngFormat::Bytes).unwrap();
+        assert_eq!(back, QrngReturnFormat::Bytes(bytes));
+
+        assert_eq!(QrngReturnFormat::Hex("5EDE".to_string()).to_bytes().unwrap(), vec![0x5e, 0xde]);
+        assert!(QrngReturnFormat::Hex("5ed".to_string()).to_bytes().is_err());
+        assert!(QrngReturnFormat::Hex("zz".to_string()).convert(QrngFormat::Bytes).is_err());
+        assert!(QrngReturnFormat::Hex("zz".to_string()).convert(QrngFormat::Hex).is_err());
+        assert!(QrngReturnFormat::Base64("Xt6GNf8".to_string()).convert(QrngFormat::Base64).is_err());
+        // Values already in the format are kept as they are.
+        let upper = QrngReturnFormat::Hex("5EDE".to_string());
+        assert_eq!(upper.clone().convert(QrngFormat::Hex).unwrap(), upper);
+        assert!(QrngReturnFormat::Base64("Xt6GNf8".to_string()).to_bytes().is_err());
+    }
+
+    #[test]
+    fn qrng_response_from_json() {
+        let body = r#"{"result": "5ede863536f9c2cb29e2ca26d5aef0e2dffe44c7", "size": "20", "format": "hex", "executeTime": 335}"#;
+        let response = QrngResponse::from_json(QrngFormat::Hex, 20, HashMap::new(), body).unwrap();
+        assert_eq!(response.size(), 20);
+        assert_eq!(response.reported_size(), Some(20));
+        assert_eq!(response.format(), QrngFormat::Hex);
+        assert_eq!(response.execute_time(), Some(335));
+        assert_eq!(response.into_result().as_hex().unwrap(), "5ede863536f9c2cb29e2ca26d5aef0e2dffe44c7");
+
+        let body = r#"{"result": "w7jYDPNv789HHJTJg7iwkg4AYI0=", "size": 20, "format": "base64"}"#;
+        let response = QrngResponse::from_json(QrngFormat::Base64, 20, HashMap::new(), body).unwrap();
+        assert_eq!(response.reported_size(), Some(20));
+        assert_eq!(response.execute_time(), None);
+        assert_eq!(response.into_result().as_base64().unwrap(), "w7jYDPNv789HHJTJg7iwkg4AYI0=");
+    }
+
+    #[test]
+    fn qrng_response_size_mismatch() {
+        let body = r#"{"result": "5ede8635", "format": "hex"}"#;
+        assert!(matches!(
+            QrngResponse::from_json(QrngFormat::Hex, 20, HashMap::new(), body),
+            Err(PqkdError::QrngSizeMismatch { requested: 20, found: 4 })
+        ));
+        assert!(matches!(
+            QrngResponse::from_bytes(20, HashMap::new(), vec![0; 19]),
+            Err(PqkdError::QrngSizeMismatch { requested: 20, found: 19 })
+        ));
+        assert!(QrngResponse::from_bytes(20, HashMap::new(), vec![0; 20]).is_ok());
+
+        let body = r#"{"result": "5ede8635", "size": "5", "format": "hex"}"#;
+        assert!(matches!(
+            QrngResponse::from_json(QrngFormat::Hex, 4, HashMap::new(), body),
+            Err(PqkdError::QrngReportedSizeMismatch { reported: 5, found: 4 })
+        ));
+        let body = r#"{"result": "5ede8635", "size": 4, "format": "hex"}"#;
+        assert!(QrngResponse::from_json(QrngFormat::Hex, 4, HashMap::new(), body).is_ok());
+        let body = r#"{"result": "5ede8635", "format": "hex"}"#;
+        let response = QrngResponse::from_json(QrngFormat::Hex, 4, HashMap::new(), body).unwrap();
+        assert_eq!(response.reported_size(), None);
+    }
+
+    #[test]
+    fn qrng_response_invalid_data() {
+        let body = r#"{"result": "not hex!", "format": "hex"}"#;
+        assert!(QrngResponse::from_json(QrngFormat::Hex, 4, HashMap::new(), body).is_err());
+        let body = r#"{"result": "5ede8635", "format": "base64"}"#;
+        assert!(QrngResponse::from_json(QrngFormat::Hex, 4, HashMap::new(), body).is_err());
+        let body = r#"{"size": 4, "format": "hex"}"#;
+        assert!(QrngResponse::from_json(QrngFormat::Hex, 4, HashMap::new(), body).is_err());
+        let body = r#"{"result": "5ede8635", "size": "four", "format": "hex"}"#;
+        assert!(matches!(
+            QrngResponse::from_json(QrngFormat::Hex, 4, HashMap::new(), body),
+            Err(PqkdError::InvalidQrngData(_))
+        ));
+    }
 }
  
\ No newline at end of file
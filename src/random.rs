@@ -0,0 +1,203 @@
+//! Conversion of QRNG output into typed random values.
+//!
+//! Samplers read the random bytes bit by bit, so a value in a range of
+//! size `n` consumes only `ceil(log2(n))` bits per attempt. Out-of-range
+//! candidates are rejected instead of reduced modulo `n`, which keeps
+//! the result unbiased. A sampler returns `None` when the bytes run out;
+//! the client then fetches more bytes from the QRNG, appends them and
+//! runs the sampler again from the start.
+use crate::error::PqkdError;
+
+/// Reads bits from a byte slice, most significant bit first.
+pub(crate) struct BitReader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> BitReader<'a> {
+    pub(crate) fn new(bytes: &'a [u8]) -> Self {
+        BitReader { bytes, position: 0 }
+    }
+
+    /// Returns the next `bits` bits (at most 64) as an integer.
+    pub(crate) fn read(&mut self, bits: u32) -> Option<u64> {
+        if self.position + bits as usize > self.bytes.len() * 8 {
+            return None;
+        }
+        let mut value = 0u64;
+        for _ in 0..bits {
+            let byte = self.bytes[self.position / 8];
+            let bit = (byte >> (7 - self.position % 8)) & 1;
+            value = (value << 1) | bit as u64;
+            self.position += 1;
+        }
+        Some(value)
+    }
+}
+
+fn bits_for(range: u64) -> u32 {
+    64 - (range - 1).leading_zeros()
+}
+
+/// Expected number of bits consumed when sampling a value below `range`.
+pub(crate) fn expected_bits(range: u64) -> f64 {
+    if range <= 1 {
+        return 0.0;
+    }
+    let bits = bits_for(range);
+    bits as f64 * 2f64.powi(bits as i32) / range as f64
+}
+
+/// Number of bytes to fetch for the first attempt of a sampler
+/// expected to consume `bits` bits.
+pub(crate) fn bytes_for(bits: f64) -> usize {
+    let bytes = (bits / 8.0).ceil() as usize;
+    bytes + bytes / 10 + 1
+}
+
+/// Samples an integer uniformly distributed in `0..range` by rejection.
+pub(crate) fn uniform_below(reader: &mut BitReader, range: u64) -> Option<u64> {
+    if range <= 1 {
+        return Some(0);
+    }
+    let bits = bits_for(range);
+    loop {
+        let candidate = reader.read(bits)?;
+        if candidate < range {
+            return Some(candidate);
+        }
+    }
+}
+
+/// Samples a float uniformly distributed in `[0, 1)` with 53 bits of precision.
+pub(crate) fn unit_f64(reader: &mut BitReader) -> Option<f64> {
+    Some(reader.read(53)? as f64 / (1u64 << 53) as f64)
+}
+
+/// Formats 16 random bytes as a version 4 UUID (RFC 4122).
+pub(crate) fn uuid_v4(bytes: &[u8]) -> Option<String> {
+    let mut bytes: [u8; 16] = bytes.get(..16)?.try_into().ok()?;
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex = hex::encode(bytes);
+    Some(format!(
+        "{}-{}-{}-{}-{}",
+        &hex[0..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..32]
+    ))
+}
+
+/// Samples the swaps of a Fisher-Yates shuffle of `len` elements.
+/// `swaps[k]` is the index exchanged with index `len - 1 - k`.
+pub(crate) fn shuffle_swaps(reader: &mut BitReader, len: usize) -> Option<Vec<usize>> {
+    let mut swaps = Vec::with_capacity(len.saturating_sub(1));
+    for i in (1..len).rev() {
+        swaps.push(uniform_below(reader, i as u64 + 1)? as usize);
+    }
+    Some(swaps)
+}
+
+/// Expected number of bits consumed by [shuffle_swaps].
+pub(crate) fn shuffle_expected_bits(len: usize) -> f64 {
+    (2..=len as u64).map(expected_bits).sum()
+}
+
+/// Applies the swaps returned by [shuffle_swaps] to `items`.
+pub(crate) fn apply_swaps<T>(items: &mut [T], swaps: &[usize]) {
+    for (i, j) in (1..items.len()).rev().zip(swaps) {
+        items.swap(i, *j);
+    }
+}
+
+pub(crate) fn check_range(start: u64, end: u64) -> Result<(), PqkdError> {
+    if start >= end {
+        return Err(PqkdError::RandomParameterError(format!(
+            "empty range {}..{}",
+            start, end
+        )));
+    }
+    Ok(())
+}
+
+pub(crate) fn check_alphabet(alphabet: &[char]) -> Result<(), PqkdError> {
+    if alphabet.is_empty() {
+        return Err(PqkdError::RandomParameterError("empty alphabet".to_string()));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bit_reader() {
+        let mut reader = BitReader::new(&[0b1010_0000, 0xff]);
+        assert_eq!(reader.read(3), Some(0b101));
+        assert_eq!(reader.read(5), Some(0));
+        assert_eq!(reader.read(8), Some(0xff));
+        assert_eq!(reader.read(1), None);
+    }
+
+    #[test]
+    fn uniform_below_rejects_out_of_range() {
+        // range 5 uses 3 bits: 0b111 and 0b110 are rejected, 0b011 accepted.
+        let mut reader = BitReader::new(&[0b1111_1001, 0b1000_0000]);
+        assert_eq!(uniform_below(&mut reader, 5), Some(3));
+        // Two rejected candidates leave two bits, too few for another attempt.
+        let mut reader = BitReader::new(&[0b1111_1111]);
+        assert_eq!(uniform_below(&mut reader, 5), None);
+        assert_eq!(uniform_below(&mut BitReader::new(&[]), 1), Some(0));
+    }
+
+    #[test]
+    fn uniform_below_is_unbiased() {
+        // Every 3-bit pattern once: values 0..5 must each appear exactly once.
+        let bytes = [0b0000_0101, 0b0011_1001, 0b0111_0111];
+        let mut reader = BitReader::new(&bytes);
+        let mut counts = [0; 5];
+        while let Some(value) = uniform_below(&mut reader, 5) {
+            counts[value as usize] += 1;
+        }
+        assert_eq!(counts, [1; 5]);
+    }
+
+    #[test]
+    fn unit_f64_in_range() {
+        assert_eq!(unit_f64(&mut BitReader::new(&[0; 7])), Some(0.0));
+        let max = unit_f64(&mut BitReader::new(&[0xff; 7])).unwrap();
+        assert!(max < 1.0);
+        assert!(unit_f64(&mut BitReader::new(&[0xff; 6])).is_none());
+    }
+
+    #[test]
+    fn uuid_v4_format() {
+        let uuid = uuid_v4(&[0xff; 16]).unwrap();
+        assert_eq!(uuid, "ffffffff-ffff-4fff-bfff-ffffffffffff");
+        let uuid = uuid_v4(&[0x00; 16]).unwrap();
+        assert_eq!(uuid, "00000000-0000-4000-8000-000000000000");
+        assert!(uuid_v4(&[0; 15]).is_none());
+    }
+
+    #[test]
+    fn shuffle_is_a_permutation() {
+        let bytes: Vec<u8> = (0..64).map(|i| (i * 37 + 11) as u8).collect();
+        let swaps = shuffle_swaps(&mut BitReader::new(&bytes), 10).unwrap();
+        let mut items: Vec<usize> = (0..10).collect();
+        apply_swaps(&mut items, &swaps);
+        let mut sorted = items.clone();
+        sorted.sort();
+        assert_eq!(sorted, (0..10).collect::<Vec<usize>>());
+    }
+
+    #[test]
+    fn check_parameters() {
+        assert!(check_range(0, 1).is_ok());
+        assert!(check_range(1, 1).is_err());
+        assert!(check_alphabet(&['a']).is_ok());
+        assert!(check_alphabet(&[]).is_err());
+    }
+}
@@ -0,0 +1,234 @@
+//! Key relay over a path of links through trusted nodes.
+//!
+//! SAEs that do not share a link can agree on a key through the nodes of a
+//! path of links, e.g. A–B and B–C. The source draws a random end-to-end
+//! key and encrypts it with a one-time pad, a QKD key of the first link.
+//! Every node of the path decrypts it with the key of the link it received
+//! it on and encrypts it again with a key of the next link, until the
+//! destination decrypts it. The nodes learn the end-to-end key, so they
+//! must be trusted; the links only carry ciphertexts.
+//!
+//! The key travels in a [RelayMessage], sent to the next node over a
+//! channel of the application (the pQKD does not carry it). The clients
+//! implement the steps as `relay_key` (source), `receive_relayed_key` and
+//! `forward_relayed_key` (nodes and destination), and `KeyRelay` runs all
+//! of them for a path of clients in one process.
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use hmac::Mac;
+use serde::{Deserialize, Serialize};
+
+use crate::error::PqkdError;
+use crate::response::{confirmation_mac, HmacSha256};
+use crate::Key;
+
+/// Version of the messages created by this crate.
+pub const RELAY_VERSION: u32 = 1;
+
+const MAC_LABEL: &[u8] = b"pqkd key relay v1";
+
+/// End-to-end key in transit over one link of the path.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RelayMessage {
+    pub version: u32,
+    /// ID of the end-to-end key, the same at the source and the destination.
+    pub key_id: String,
+    pub source_sae_id: String,
+    pub destination_sae_id: String,
+    /// ID of the key of the link the message is sent over.
+    pub hop_key_id: String,
+    /// End-to-end key (base64), XORed with the key of the link.
+    pub ciphertext: String,
+    /// HMAC-SHA256 (hex) of the IDs, keyed with a key derived from the
+    /// end-to-end key.
+    pub mac: String,
+}
+
+impl RelayMessage {
+    /// Encrypts a new random end-to-end key of the size of `hop_key`, a key
+    /// of the first link of the path.
+    pub(crate) fn start(
+        source_sae_id: &str,
+        destination_sae_id: &str,
+        hop_key: &Key,
+    ) -> Result<(Key, Self), PqkdError> {
+        let mut key = vec![0u8; hop_key.material()?.len()];
+        getrandom::getrandom(&mut key)?;
+        let mut key_id = [0u8; 16];
+        getrandom::getrandom(&mut key_id)?;
+        let key = Key::new(&uuid(key_id), &BASE64.encode(key));
+        let message = Self {
+            version: RELAY_VERSION,
+            key_id: key.key_id().to_string(),
+            source_sae_id: source_sae_id.to_string(),
+            destination_sae_id: destination_sae_id.to_string(),
+            hop_key_id: String::new(),
+            ciphertext: String::new(),
+            mac: String::new(),
+        };
+        let message = Self {
+            mac: hex::encode(message.mac(&key)?.finalize().into_bytes()),
+            ..message
+        };
+        let message = message.forward(&key, hop_key)?;
+        Ok((key, message))
+    }
+
+    /// Returns the message to send over the next link, encrypted with
+    /// `hop_key`, a key of that link.
+    pub(crate) fn forward(&self, key: &Key, hop_key: &Key) -> Result<Self, PqkdError> {
+        Ok(Self {
+            hop_key_id: hop_key.key_id().to_string(),
+            ciphertext: BASE64.encode(xor(&key.material()?, &hop_key.material()?)?),
+            ..self.clone()
+        })
+    }
+
+    /// Decrypts the end-to-end key with `hop_key`, the key of the link the
+    /// message was received on, and checks its MAC.
+    pub(crate) fn open(&self, hop_key: &Key) -> Result<Key, PqkdError> {
+        check_version(self.version)?;
+        if hop_key.key_id() != self.hop_key_id {
+            return Err(PqkdError::RelayError(format!(
+                "key {} is not the key {} of the link",
+                hop_key.key_id(),
+                self.hop_key_id
+            )));
+        }
+        let ciphertext = BASE64
+            .decode(&self.ciphertext)
+            .map_err(|_| PqkdError::RelayError("ciphertext is not base64".to_string()))?;
+        let key = Key::new(
+            &self.key_id,
+            &BASE64.encode(xor(&ciphertext, &hop_key.material()?)?),
+        );
+        let mac =
+            hex::decode(&self.mac).map_err(|_| PqkdError::RelayError("invalid MAC".to_string()))?;
+        self.mac(&key)?
+            .verify_slice(&mac)
+            .map_err(|_| PqkdError::RelayError("key confirmation failed".to_string()))?;
+        Ok(key)
+    }
+
+    /// Decodes a message received from the previous node of the path.
+    pub fn decode(bytes: &[u8]) -> Result<Self, PqkdError> {
+        let message: Self = serde_json::from_slice(bytes)
+            .map_err(|err| PqkdError::RelayError(format!("invalid message: {}", err)))?;
+        check_version(message.version)?;
+        Ok(message)
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        serde_json::to_vec(self).expect("relay message is serializable")
+    }
+
+    fn mac(&self, key: &Key) -> Result<HmacSha256, PqkdError> {
+        let fields = [&self.key_id, &self.source_sae_id, &self.destination_sae_id];
+        Ok(confirmation_mac(
+            &key.material()?,
+            MAC_LABEL,
+            fields.map(|field| field.as_bytes()),
+        ))
+    }
+}
+
+fn check_version(version: u32) -> Result<(), PqkdError> {
+    if version != RELAY_VERSION {
+        return Err(PqkdError::RelayError(format!(
+            "unsupported version {}",
+            version
+        )));
+    }
+    Ok(())
+}
+
+fn xor(data: &[u8], pad: &[u8]) -> Result<Vec<u8>, PqkdError> {
+    if data.len() != pad.len() {
+        return Err(PqkdError::RelayError(format!(
+            "key of the link has {} bytes instead of {}",
+            pad.len(),
+            data.len()
+        )));
+    }
+    Ok(data.iter().zip(pad).map(|(byte, pad)| byte ^ pad).collect())
+}
+
+/// Formats random bytes as a version 4 UUID, like the key IDs of the KME.
+fn uuid(mut bytes: [u8; 16]) -> String {
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex = hex::encode(bytes);
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(key_id: &str, key: &str) -> Key {
+        Key::new(key_id, key)
+    }
+
+    #[test]
+    fn relay() {
+        let hop_1 = key(
+            "bc490419-7d60-487f-adc1-4ddcc177c139",
+            "wHHVxRwDJs3/bXd38GHP3oe4svTuRpZS0yCC7x4Ly+s=",
+        );
+        let hop_2 = key(
+            "0a782fb5-3434-48fe-aa4d-14f41d46cf92",
+            "OeGMPxh1+2RpJpNCYixWHFLYRubpOKCw94FcCI7VdJA=",
+        );
+        let (source_key, message) = RelayMessage::start("Test_1SAE", "Test_3SAE", &hop_1).unwrap();
+        assert_eq!(source_key.key_id().len(), 36);
+        assert_eq!(message.hop_key_id, hop_1.key_id());
+        assert_ne!(message.ciphertext, source_key.key());
+
+        let received = RelayMessage::decode(&message.encode()).unwrap();
+        let node_key = received.open(&hop_1).unwrap();
+        assert_eq!(node_key, source_key);
+        let forwarded = received.forward(&node_key, &hop_2).unwrap();
+        assert_eq!(forwarded.open(&hop_2).unwrap(), source_key);
+    }
+
+    #[test]
+    fn tampering() {
+        let hop = key(
+            "bc490419-7d60-487f-adc1-4ddcc177c139",
+            "wHHVxRwDJs3/bXd38GHP3oe4svTuRpZS0yCC7x4Ly+s=",
+        );
+        let (_, message) = RelayMessage::start("Test_1SAE", "Test_3SAE", &hop).unwrap();
+        let changed = RelayMessage {
+            destination_sae_id: "Test_4SAE".to_string(),
+            ..message.clone()
+        };
+        assert!(matches!(
+            changed.open(&hop),
+            Err(PqkdError::RelayError(reason)) if reason == "key confirmation failed"
+        ));
+        let other = key(
+            "bc490419-7d60-487f-adc1-4ddcc177c139",
+            "OeGMPxh1+2RpJpNCYixWHFLYRubpOKCw94FcCI7VdJA=",
+        );
+        assert!(message.open(&other).is_err());
+        let short = key("bc490419-7d60-487f-adc1-4ddcc177c139", "AAAA");
+        assert!(message.open(&short).is_err());
+
+        let mut message = message;
+        message.version = 2;
+        assert!(RelayMessage::decode(&message.encode()).is_err());
+    }
+
+    #[test]
+    fn uuid_format() {
+        let id = uuid([0xff; 16]);
+        assert_eq!(id, "ffffffff-ffff-4fff-bfff-ffffffffffff");
+    }
+}
@@ -1,9 +1,11 @@
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum PqkdMethod {
     Status,
     EncKeys,
     DesKeys,
 }
 
+#[derive(Clone, Debug)]
 pub struct PqkdRequest {
     pub(crate) pqkd_method: PqkdMethod,
     pub(crate) sae_id: String,
@@ -1,5 +1,18 @@
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use hkdf::Hkdf;
+use hmac::{Hmac, Mac};
 use serde::{Serialize, Deserialize};
+use sha2::Sha256;
 
+use crate::error::PqkdError;
+
+pub(crate) type HmacSha256 = Hmac<Sha256>;
+
+/// Domain separation label of key confirmation tags.
+const CONFIRMATION_LABEL: &[u8] = b"pqkd key confirmation v1";
+/// Length of the nonces returned by [Key::confirmation_nonce].
+pub const CONFIRMATION_NONCE_LEN: usize = 16;
 
 #[derive(Debug)]
 pub enum PqkdResponse {
@@ -51,6 +64,13 @@ pub struct Key {
 }
 
 impl Key {
+    pub(crate) fn new(key_id: &str, key: &str) -> Self {
+        Self {
+            key_id: key_id.to_string(),
+            key: key.to_string(),
+        }
+    }
+
     /// Returns the id of key.
     pub fn key_id(&self) -> &str {
         &self.key_id
@@ -60,9 +80,110 @@ impl Key {
     pub fn key(&self) -> &str {
         &self.key
     }
+
+    /// Returns a random nonce for [confirmation_tag](Self::confirmation_tag).
+    pub fn confirmation_nonce() -> Result<[u8; CONFIRMATION_NONCE_LEN], PqkdError> {
+        let mut nonce = [0u8; CONFIRMATION_NONCE_LEN];
+        getrandom::getrandom(&mut nonce)?;
+        Ok(nonce)
+    }
+
+    /// Computes a tag (hex) proving to a peer that the SAE `sae_id` holds
+    /// this key, without revealing it: HMAC-SHA256 keyed with a key derived
+    /// from this key over a domain separation label, the key ID, `sae_id`
+    /// and `nonce`.
+    ///
+    /// The peer sends a fresh nonce, the SAE answers with the tag and the
+    /// peer checks it with [verify_confirmation_tag](Self::verify_confirmation_tag)
+    /// of its own copy of the key. Binding the tag to the SAE computing it
+    /// keeps a peer from passing a nonce back to get a tag of its own.
+    pub fn confirmation_tag(&self, sae_id: &str, nonce: &[u8]) -> Result<String, PqkdError> {
+        Ok(hex::encode(self.confirmation_mac(sae_id, nonce)?.finalize().into_bytes()))
+    }
+
+    /// Checks a tag computed by the SAE `sae_id` for `nonce`. Fails with
+    /// [PqkdError::KeyMismatch] if the SAE holds another key under this ID.
+    pub fn verify_confirmation_tag(&self, sae_id: &str, nonce: &[u8], tag: &str) -> Result<(), PqkdError> {
+        let mismatch = || PqkdError::KeyMismatch(self.key_id.clone());
+        let tag = hex::decode(tag).map_err(|_| mismatch())?;
+        self.confirmation_mac(sae_id, nonce)?
+            .verify_slice(&tag)
+            .map_err(|_| mismatch())
+    }
+
+    fn confirmation_mac(&self, sae_id: &str, nonce: &[u8]) -> Result<HmacSha256, PqkdError> {
+        Ok(confirmation_mac(
+            &self.material()?,
+            CONFIRMATION_LABEL,
+            [self.key_id.as_bytes(), sae_id.as_bytes(), nonce],
+        ))
+    }
+
+    /// Returns the decoded key.
+    pub(crate) fn material(&self) -> Result<Vec<u8>, PqkdError> {
+        BASE64
+            .decode(&self.key)
+            .map_err(|_| PqkdError::InvalidKey(self.key_id.clone()))
+    }
+}
+
+/// Returns an HMAC-SHA256 proving possession of `material`, keyed with a
+/// key derived from it with HKDF-SHA256 and `label`, over `label` and the
+/// length-prefixed `fields`. Shared by the key confirmation, key sync and
+/// key relay messages, which use distinct labels.
+pub(crate) fn confirmation_mac<'a>(
+    material: &[u8],
+    label: &[u8],
+    fields: impl IntoIterator<Item = &'a [u8]>,
+) -> HmacSha256 {
+    let mut mac_key = [0u8; 32];
+    Hkdf::<Sha256>::new(None, material)
+        .expand(label, &mut mac_key)
+        .expect("32 bytes are a valid HKDF-SHA256 output length");
+    let mut mac = HmacSha256::new_from_slice(&mac_key).expect("HMAC takes keys of any size");
+    mac.update(label);
+    for field in fields {
+        mac.update(&(field.len() as u32).to_be_bytes());
+        mac.update(field);
+    }
+    mac
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 pub(crate) struct Keys {
     pub(crate) keys: Vec<Key>
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(key: &str) -> Key {
+        Key {
+            key_id: "bc490419-7d60-487f-adc1-4ddcc177c139".to_string(),
+            key: key.to_string(),
+        }
+    }
+
+    #[test]
+    fn confirmation_tag() {
+        let key_a = key("wHHVxRwDJs3/bXd38GHP3oe4svTuRpZS0yCC7x4Ly+s=");
+        let key_b = key("wHHVxRwDJs3/bXd38GHP3oe4svTuRpZS0yCC7x4Ly+s=");
+        let nonce = Key::confirmation_nonce().unwrap();
+
+        let tag = key_b.confirmation_tag("Test_2SAE", &nonce).unwrap();
+        assert_eq!(tag.len(), 64);
+        key_a.verify_confirmation_tag("Test_2SAE", &nonce, &tag).unwrap();
+        // The tag is bound to the SAE and the nonce.
+        assert!(key_a.verify_confirmation_tag("Test_1SAE", &nonce, &tag).is_err());
+        assert!(key_a.verify_confirmation_tag("Test_2SAE", &[0; 16], &tag).is_err());
+
+        let other = key("OeGMPxh1+2RpJpNCYixWHFLYRubpOKCw94FcCI7VdJA=");
+        let tag = other.confirmation_tag("Test_2SAE", &nonce).unwrap();
+        assert!(matches!(
+            key_a.verify_confirmation_tag("Test_2SAE", &nonce, &tag),
+            Err(PqkdError::KeyMismatch(key_id)) if key_id == "bc490419-7d60-487f-adc1-4ddcc177c139"
+        ));
+        assert!(matches!(key("not base64!").confirmation_tag("Test_2SAE", &nonce), Err(PqkdError::InvalidKey(_))));
+    }
+}
@@ -0,0 +1,90 @@
+//! Retrying of failed requests to the pQKD.
+use std::time::Duration;
+
+use crate::error::PqkdError;
+
+/// How often and how fast failed requests to the KME and QRNG are retried.
+///
+/// Only failures after which the device has certainly not served the
+/// request are retried: connection errors and `503 Service Unavailable`
+/// answers (e.g. when no keys are available). Retrying them cannot
+/// consume keys twice.
+///
+/// The delay before the n-th retry is `backoff * 2^(n-1)`, capped at `max_backoff`.
+/// By default requests are not retried.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 0,
+            backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(5),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Retries up to `max_retries` times with the default backoff.
+    pub fn new(max_retries: u32) -> Self {
+        Self {
+            max_retries,
+            ..Self::default()
+        }
+    }
+
+    /// Returns the delay before the retry following the failed `attempt` (counted from 0).
+    pub fn delay(&self, attempt: u32) -> Duration {
+        self.backoff
+            .checked_mul(1 << attempt.min(31))
+            .unwrap_or(self.max_backoff)
+            .min(self.max_backoff)
+    }
+
+    /// Returns true if the failed `attempt` (counted from 0) must be retried.
+    pub(crate) fn retry(&self, attempt: u32, err: &PqkdError) -> bool {
+        attempt < self.max_retries && is_retryable(err)
+    }
+}
+
+fn is_retryable(err: &PqkdError) -> bool {
+    match err {
+        PqkdError::RequestError(err) => err.is_connect(),
+        PqkdError::HttpStatus(status) => *status == reqwest::StatusCode::SERVICE_UNAVAILABLE,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn delay() {
+        let policy = RetryPolicy {
+            max_retries: 10,
+            backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(1),
+        };
+        assert_eq!(policy.delay(0), Duration::from_millis(100));
+        assert_eq!(policy.delay(2), Duration::from_millis(400));
+        assert_eq!(policy.delay(4), Duration::from_secs(1));
+        assert_eq!(policy.delay(u32::MAX), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn retry() {
+        let policy = RetryPolicy::new(1);
+        assert!(!policy.retry(0, &PqkdError::ErrorKmeRequest));
+        assert!(!RetryPolicy::default().retry(0, &PqkdError::ErrorKmeRequest));
+        let unavailable = PqkdError::HttpStatus(reqwest::StatusCode::SERVICE_UNAVAILABLE);
+        assert!(policy.retry(0, &unavailable));
+        assert!(!policy.retry(1, &unavailable));
+        assert!(!policy.retry(0, &PqkdError::HttpStatus(reqwest::StatusCode::BAD_REQUEST)));
+    }
+}
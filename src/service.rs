@@ -0,0 +1,189 @@
+//! Integration with [tower](https://docs.rs/tower).
+//!
+//! With the `tower` feature, [PqkdClient] implements
+//! `tower::Service<PqkdRequest>`, so generic middleware (timeouts, rate
+//! and concurrency limits, retries, tracing) can be stacked on top of it
+//! with `tower::ServiceBuilder`. [KeyBackpressure] is a middleware specific
+//! to the pQKD, which holds back key requests until the device has stored
+//! enough keys.
+//!
+//! # Example
+//!
+//! ```
+//! use pqkd::service::KeyBackpressureLayer;
+//! use pqkd::BuilderPqkdClient;
+//! use std::time::Duration;
+//! use tower::ServiceBuilder;
+//!
+//! # fn main() -> Result<(), pqkd::error::PqkdError> {
+//! let pqkd_client = BuilderPqkdClient::with_addr("http://172.16.0.154:8082")?.build();
+//! let service = ServiceBuilder::new()
+//!     .concurrency_limit(4)
+//!     .timeout(Duration::from_secs(10))
+//!     .layer(KeyBackpressureLayer::new().with_reserve(100))
+//!     .service(pqkd_client);
+//! # Ok(())
+//! # }
+//! ```
+use std::future::poll_fn;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use tower_layer::Layer;
+use tower_service::Service;
+
+use crate::error::PqkdError;
+use crate::request::{PqkdMethod, PqkdRequest};
+use crate::transport::BoxFuture;
+use crate::{PqkdClient, PqkdResponse, PqkdStatus};
+
+impl Service<PqkdRequest> for PqkdClient {
+    type Response = PqkdResponse;
+    type Error = PqkdError;
+    type Future = BoxFuture<'static, Result<PqkdResponse, PqkdError>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), PqkdError>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: PqkdRequest) -> Self::Future {
+        let client = self.clone();
+        Box::pin(async move { client.kme_execute_request(request).await })
+    }
+}
+
+/// `tower::Layer` wrapping services in [KeyBackpressure].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct KeyBackpressureLayer {
+    reserve: u32,
+    poll_interval: Duration,
+    max_wait: Duration,
+}
+
+impl Default for KeyBackpressureLayer {
+    fn default() -> Self {
+        Self {
+            reserve: 0,
+            poll_interval: Duration::from_millis(100),
+            max_wait: Duration::from_secs(5),
+        }
+    }
+}
+
+impl KeyBackpressureLayer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Keeps `reserve` keys stored on the device, e.g. for other applications.
+    pub fn with_reserve(self, reserve: u32) -> Self {
+        Self { reserve, ..self }
+    }
+
+    /// Sets how often the status is checked while waiting for keys.
+    pub fn with_poll_interval(self, poll_interval: Duration) -> Self {
+        Self {
+            poll_interval,
+            ..self
+        }
+    }
+
+    /// Sets how long a key request waits for keys before it fails
+    /// with [PqkdError::NotEnoughKeys].
+    pub fn with_max_wait(self, max_wait: Duration) -> Self {
+        Self { max_wait, ..self }
+    }
+
+}
+
+impl<S> Layer<S> for KeyBackpressureLayer {
+    type Service = KeyBackpressure<S>;
+
+    fn layer(&self, inner: S) -> KeyBackpressure<S> {
+        KeyBackpressure {
+            inner,
+            config: *self,
+        }
+    }
+}
+
+/// Middleware delaying `enc_keys` requests until the KME has stored enough
+/// keys to serve them, instead of letting them fail with
+/// `503 Service Unavailable`.
+///
+/// Before each `enc_keys` request the status of the link is requested
+/// through the inner service. The request is sent once the stored keys
+/// (counted in keys of the `key_size` of the link) cover the requested keys
+/// plus the reserve. Other requests are passed through unchanged.
+#[derive(Clone, Debug)]
+pub struct KeyBackpressure<S> {
+    inner: S,
+    config: KeyBackpressureLayer,
+}
+
+impl<S> Service<PqkdRequest> for KeyBackpressure<S>
+where
+    S: Service<PqkdRequest, Response = PqkdResponse, Error = PqkdError> + Clone + Send + 'static,
+    S::Future: Send,
+{
+    type Response = PqkdResponse;
+    type Error = PqkdError;
+    type Future = BoxFuture<'static, Result<PqkdResponse, PqkdError>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), PqkdError>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: PqkdRequest) -> Self::Future {
+        if !matches!(request.pqkd_method(), PqkdMethod::EncKeys) {
+            return Box::pin(self.inner.call(request));
+        }
+        // Use the service that was driven to readiness and leave a clone behind.
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let config = self.config;
+        Box::pin(async move {
+            let deadline = Instant::now() + config.max_wait;
+            loop {
+                let status = fetch_status(&mut inner, request.sae_id()).await?;
+                let required = required_keys(&request, &status).saturating_add(config.reserve);
+                if status.stored_key_count >= required {
+                    break;
+                }
+                if Instant::now() + config.poll_interval > deadline {
+                    return Err(PqkdError::NotEnoughKeys {
+                        stored: status.stored_key_count,
+                        required,
+                    });
+                }
+                tokio::time::sleep(config.poll_interval).await;
+            }
+            poll_fn(|cx| inner.poll_ready(cx)).await?;
+            inner.call(request).await
+        })
+    }
+}
+
+async fn fetch_status<S>(inner: &mut S, sae_id: &str) -> Result<PqkdStatus, PqkdError>
+where
+    S: Service<PqkdRequest, Response = PqkdResponse, Error = PqkdError>,
+{
+    poll_fn(|cx| inner.poll_ready(cx)).await?;
+    inner
+        .call(PqkdRequest::new(PqkdMethod::Status, sae_id))
+        .await?
+        .as_status()
+        .ok_or(PqkdError::ErrorKmeRequest)
+}
+
+/// Returns how many stored keys of the link are consumed by a key request.
+fn required_keys(request: &PqkdRequest, status: &PqkdStatus) -> u32 {
+    let number = if request.key_ids().is_empty() {
+        request.number()
+    } else {
+        request.key_ids().len() as u32
+    };
+    let key_size = status.key_size.max(1);
+    let per_key = (request.size() as u32 + key_size - 1) / key_size;
+    number.saturating_mul(per_key)
+}
@@ -0,0 +1,26 @@
+//! Simulated pQKD devices for local development and tests.
+//!
+//! [Simulator] runs two ETSI GS QKD 014 compatible KMEs in-process,
+//! connected by a simulated QKD link. Keys requested with `enc_keys` from
+//! one KME can be retrieved once with `dec_keys` from the other, the status
+//! reports the shared key buffer, and both KMEs serve the QRNG endpoints.
+//!
+//! A [FaultScenario] makes the KMEs misbehave on demand (latency,
+//! missing keys, malformed responses, broken connections) to test how
+//! applications cope with a failing device.
+//!
+//! Clients can also talk to a [SimLink] without networking, through the
+//! in-memory transport of [SimLink::transport].
+//!
+//! The simulator is available with the `sim` feature. The `pqkd-sim`
+//! binary (features `sim` and `cli`) runs it as a standalone process.
+mod config;
+mod fault;
+mod kme;
+mod memory;
+mod server;
+
+pub use config::SimConfig;
+pub use fault::{Endpoint, Fault, FaultRule, FaultScenario};
+pub use kme::{Side, SimLink, SimRequest, SimResponse};
+pub use server::Simulator;
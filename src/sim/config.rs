@@ -0,0 +1,49 @@
+use std::net::SocketAddr;
+
+/// Settings of a simulated pair of KMEs.
+///
+/// Side A is the KME of `sae_id_a`, side B the KME of `sae_id_b`.
+/// Both KMEs draw keys from one shared buffer of `stored_key_count`
+/// keys of `key_size` bits.
+#[derive(Clone, Debug)]
+pub struct SimConfig {
+    /// Address the KME of side A listens on (port 0 picks a free port).
+    pub addr_a: SocketAddr,
+    /// Address the KME of side B listens on (port 0 picks a free port).
+    pub addr_b: SocketAddr,
+    pub kme_id_a: String,
+    pub kme_id_b: String,
+    pub sae_id_a: String,
+    pub sae_id_b: String,
+    /// Default size of keys in bits.
+    pub key_size: u32,
+    pub min_key_size: u32,
+    pub max_key_size: u32,
+    /// Capacity of the shared key buffer.
+    pub max_key_count: u32,
+    /// Number of keys in the buffer when the simulator starts.
+    pub initial_key_count: u32,
+    pub max_key_per_request: u32,
+    /// Number of keys added to the buffer per second, up to `max_key_count`.
+    pub key_rate: u32,
+}
+
+impl Default for SimConfig {
+    fn default() -> Self {
+        Self {
+            addr_a: SocketAddr::from(([127, 0, 0, 1], 0)),
+            addr_b: SocketAddr::from(([127, 0, 0, 1], 0)),
+            kme_id_a: "Sim_1KME".to_string(),
+            kme_id_b: "Sim_2KME".to_string(),
+            sae_id_a: "Sim_1SAE".to_string(),
+            sae_id_b: "Sim_2SAE".to_string(),
+            key_size: 256,
+            min_key_size: 64,
+            max_key_size: 4096,
+            max_key_count: 4096,
+            initial_key_count: 4096,
+            max_key_per_request: 64,
+            key_rate: 0,
+        }
+    }
+}
@@ -0,0 +1,293 @@
+use std::time::Duration;
+
+use super::kme::{Side, SimRequest};
+
+/// Misbehaviour injected into the responses of a simulated KME.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Fault {
+    /// Delays the response.
+    Latency(Duration),
+    /// Answers with `503 Service Unavailable` ("no keys available")
+    /// without delivering keys.
+    NoKeysAvailable,
+    /// Cuts the JSON body of the response in half.
+    TruncatedJson,
+    /// Returns keys half as long as requested.
+    WrongKeySize,
+    /// Reports key IDs that differ from those known to the peer KME.
+    MismatchedKeyIds,
+    /// Answers new connections with a few garbage bytes and closes them,
+    /// and drops requests on established connections.
+    ///
+    /// The simulator speaks plain HTTP, so this does not go through a
+    /// real TLS handshake: clients see a malformed answer followed by a
+    /// connection reset.
+    GarbageReset,
+    /// Closes the connection without a response.
+    ConnectionReset,
+}
+
+/// Group of requests a [FaultRule] applies to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Endpoint {
+    Status,
+    EncKeys,
+    DecKeys,
+    Qrng,
+}
+
+impl Endpoint {
+    pub(crate) fn of(request: &SimRequest) -> Option<Endpoint> {
+        let path = request.path.trim_end_matches('/');
+        if path.ends_with("/status") {
+            Some(Endpoint::Status)
+        } else if path.ends_with("/enc_keys") {
+            Some(Endpoint::EncKeys)
+        } else if path.ends_with("/dec_keys") {
+            Some(Endpoint::DecKeys)
+        } else if path.trim_start_matches('/').starts_with("qrng/") {
+            Some(Endpoint::Qrng)
+        } else {
+            None
+        }
+    }
+}
+
+/// Injects a [Fault] into the matching requests.
+///
+/// By default the fault applies to every request of both KMEs, forever.
+#[derive(Clone, Debug)]
+pub struct FaultRule {
+    fault: Fault,
+    side: Option<Side>,
+    endpoint: Option<Endpoint>,
+    skip: u32,
+    every: u32,
+    counted: u32,
+    times: Option<u32>,
+}
+
+impl FaultRule {
+    pub fn new(fault: Fault) -> Self {
+        Self {
+            fault,
+            side: None,
+            endpoint: None,
+            skip: 0,
+            every: 1,
+            counted: 0,
+            times: None,
+        }
+    }
+
+    /// Applies the fault only to the KME of `side`.
+    pub fn on(self, side: Side) -> Self {
+        Self {
+            side: Some(side),
+            ..self
+        }
+    }
+
+    /// Applies the fault only to requests to `endpoint`.
+    pub fn endpoint(self, endpoint: Endpoint) -> Self {
+        Self {
+            endpoint: Some(endpoint),
+            ..self
+        }
+    }
+
+    /// Lets the first `requests` matching requests through unharmed.
+    pub fn after(self, requests: u32) -> Self {
+        Self {
+            skip: requests,
+            ..self
+        }
+    }
+
+    /// Applies the fault to every `n`-th matching request only.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `n` is 0.
+    pub fn every(self, n: u32) -> Self {
+        assert!(n > 0, "a fault cannot apply to every 0th request");
+        Self { every: n, ..self }
+    }
+
+    /// Applies the fault to `requests` matching requests only.
+    pub fn times(self, requests: u32) -> Self {
+        Self {
+            times: Some(requests),
+            ..self
+        }
+    }
+
+    fn matches(&self, side: Side, endpoint: Option<Endpoint>) -> bool {
+        self.side.map_or(true, |rule_side| rule_side == side)
+            && self.endpoint.map_or(true, |rule_endpoint| Some(rule_endpoint) == endpoint)
+    }
+
+    /// Counts a matching request and returns true if the fault applies to it.
+    fn trigger(&mut self) -> bool {
+        if self.skip > 0 {
+            self.skip -= 1;
+            return false;
+        }
+        self.counted = (self.counted + 1) % self.every;
+        if self.counted != 0 {
+            return false;
+        }
+        match self.times {
+            Some(0) => false,
+            Some(times) => {
+                self.times = Some(times - 1);
+                true
+            }
+            None => true,
+        }
+    }
+}
+
+/// Ordered set of [FaultRule]s installed with
+/// [SimLink::set_scenario](super::SimLink::set_scenario).
+///
+/// Every rule matching a request counts it, and the faults of all rules
+/// that apply are combined.
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+/// use pqkd::sim::{Endpoint, Fault, FaultRule, FaultScenario};
+///
+/// // Two "no keys available" answers to enc_keys, then a slow but working KME.
+/// let scenario = FaultScenario::new()
+///     .rule(FaultRule::new(Fault::NoKeysAvailable).endpoint(Endpoint::EncKeys).times(2))
+///     .rule(FaultRule::new(Fault::Latency(Duration::from_millis(50))));
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct FaultScenario {
+    rules: Vec<FaultRule>,
+}
+
+impl FaultScenario {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a rule to the scenario.
+    pub fn rule(mut self, rule: FaultRule) -> Self {
+        self.rules.push(rule);
+        self
+    }
+
+    /// The KME of `side` has no keys for the next `requests` key requests.
+    pub fn no_keys_burst(side: Side, requests: u32) -> Self {
+        Self::new().rule(
+            FaultRule::new(Fault::NoKeysAvailable)
+                .on(side)
+                .endpoint(Endpoint::EncKeys)
+                .times(requests),
+        )
+    }
+
+    /// Every response of both KMEs is delayed by `latency`.
+    pub fn slow_link(latency: Duration) -> Self {
+        Self::new().rule(FaultRule::new(Fault::Latency(latency)))
+    }
+
+    /// Every `n`-th request to the KME of `side` loses its connection.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `n` is 0.
+    pub fn flaky(side: Side, n: u32) -> Self {
+        Self::new().rule(FaultRule::new(Fault::ConnectionReset).on(side).every(n))
+    }
+
+    /// Returns the faults that apply to a request and counts it.
+    pub(crate) fn take(&mut self, side: Side, endpoint: Option<Endpoint>) -> Vec<Fault> {
+        self.rules
+            .iter_mut()
+            .filter(|rule| rule.matches(side, endpoint))
+            .filter_map(|rule| rule.trigger().then(|| rule.fault.clone()))
+            .collect()
+    }
+
+    /// Returns true if a new connection to the KME of `side`
+    /// must be answered with garbage and closed.
+    pub(crate) fn take_garbage_reset(&mut self, side: Side) -> bool {
+        self.rules
+            .iter_mut()
+            .filter(|rule| rule.fault == Fault::GarbageReset && rule.matches(side, None))
+            .any(|rule| rule.trigger())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rule_counts_requests() {
+        let mut scenario = FaultScenario::new()
+            .rule(FaultRule::new(Fault::TruncatedJson).endpoint(Endpoint::EncKeys).after(1).times(2));
+        assert!(scenario.take(Side::A, Some(Endpoint::EncKeys)).is_empty());
+        assert!(scenario.take(Side::A, Some(Endpoint::Status)).is_empty());
+        assert_eq!(scenario.take(Side::B, Some(Endpoint::EncKeys)), vec![Fault::TruncatedJson]);
+        assert_eq!(scenario.take(Side::A, Some(Endpoint::EncKeys)), vec![Fault::TruncatedJson]);
+        assert!(scenario.take(Side::A, Some(Endpoint::EncKeys)).is_empty());
+    }
+
+    #[test]
+    fn faults_combine() {
+        let latency = Fault::Latency(Duration::from_millis(10));
+        let mut scenario = FaultScenario::slow_link(Duration::from_millis(10))
+            .rule(FaultRule::new(Fault::NoKeysAvailable).on(Side::B));
+        assert_eq!(scenario.take(Side::A, None), vec![latency.clone()]);
+        assert_eq!(scenario.take(Side::B, None), vec![latency, Fault::NoKeysAvailable]);
+    }
+
+    #[test]
+    fn flaky() {
+        let mut scenario = FaultScenario::flaky(Side::A, 3);
+        let faults: Vec<bool> = (0..6)
+            .map(|_| !scenario.take(Side::A, Some(Endpoint::Status)).is_empty())
+            .collect();
+        assert_eq!(faults, vec![false, false, true, false, false, true]);
+
+        // Far beyond the first requests.
+        let mut scenario = FaultScenario::flaky(Side::A, 2);
+        let faults = (0..1000)
+            .filter(|_| !scenario.take(Side::A, None).is_empty())
+            .count();
+        assert_eq!(faults, 500);
+        let mut scenario = FaultScenario::flaky(Side::B, 1);
+        assert!((0..100).all(|_| !scenario.take(Side::B, None).is_empty()));
+    }
+
+    #[test]
+    #[should_panic]
+    fn flaky_every_0th() {
+        FaultScenario::flaky(Side::A, 0);
+    }
+
+    #[test]
+    fn every_after_times() {
+        let mut scenario = FaultScenario::new()
+            .rule(FaultRule::new(Fault::WrongKeySize).after(1).every(2).times(2));
+        let faults: Vec<bool> = (0..7)
+            .map(|_| !scenario.take(Side::A, None).is_empty())
+            .collect();
+        assert_eq!(faults, vec![false, false, true, false, true, false, false]);
+    }
+
+    #[test]
+    fn garbage_reset() {
+        let mut scenario = FaultScenario::new()
+            .rule(FaultRule::new(Fault::GarbageReset).on(Side::B).times(1));
+        assert!(!scenario.take_garbage_reset(Side::A));
+        assert!(scenario.take_garbage_reset(Side::B));
+        assert!(!scenario.take_garbage_reset(Side::B));
+    }
+}
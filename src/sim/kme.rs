@@ -0,0 +1,451 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use serde_json::{json, Value};
+
+use super::config::SimConfig;
+use super::fault::{Endpoint, Fault, FaultScenario};
+use crate::qrng::QrngFormat;
+use crate::random;
+
+/// One of the two KMEs of a simulated link.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Side {
+    A,
+    B,
+}
+
+impl Side {
+    fn index(self) -> usize {
+        match self {
+            Side::A => 0,
+            Side::B => 1,
+        }
+    }
+
+    /// Returns the KME at the other end of the link.
+    pub fn peer(self) -> Side {
+        match self {
+            Side::A => Side::B,
+            Side::B => Side::A,
+        }
+    }
+}
+
+/// HTTP request received by a simulated KME.
+#[derive(Clone, Debug)]
+pub struct SimRequest {
+    pub method: String,
+    pub path: String,
+    pub query: Option<String>,
+    pub body: Vec<u8>,
+}
+
+/// HTTP response of a simulated KME.
+#[derive(Clone, Debug)]
+pub struct SimResponse {
+    pub status: u16,
+    pub content_type: &'static str,
+    pub body: Vec<u8>,
+    /// Time to wait before answering.
+    pub delay: Duration,
+    /// Close the connection instead of answering.
+    pub drop_connection: bool,
+}
+
+impl SimResponse {
+    pub(crate) fn new(status: u16, content_type: &'static str, body: Vec<u8>) -> Self {
+        Self {
+            status,
+            content_type,
+            body,
+            delay: Duration::ZERO,
+            drop_connection: false,
+        }
+    }
+
+    pub(crate) fn json(status: u16, body: Value) -> Self {
+        Self::new(status, "application/json", body.to_string().into_bytes())
+    }
+
+    pub(crate) fn error(status: u16, message: &str) -> Self {
+        Self::json(status, json!({ "message": message }))
+    }
+}
+
+struct LinkState {
+    config: SimConfig,
+    stored_keys: f64,
+    refilled_at: Instant,
+    // Keys delivered by enc_keys on one side, waiting for dec_keys on the other.
+    pending: [HashMap<String, Vec<u8>>; 2],
+    faults: FaultScenario,
+}
+
+impl LinkState {
+    fn refill(&mut self) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.refilled_at).as_secs_f64();
+        self.stored_keys = (self.stored_keys + elapsed * self.config.key_rate as f64)
+            .min(self.config.max_key_count as f64);
+        self.refilled_at = now;
+    }
+
+    fn sae_id(&self, side: Side) -> &str {
+        match side {
+            Side::A => &self.config.sae_id_a,
+            Side::B => &self.config.sae_id_b,
+        }
+    }
+
+    fn kme_id(&self, side: Side) -> &str {
+        match side {
+            Side::A => &self.config.kme_id_a,
+            Side::B => &self.config.kme_id_b,
+        }
+    }
+}
+
+/// Shared state of a simulated pair of KMEs.
+///
+/// [SimLink::handle] answers the ETSI GS QKD 014 and QRNG requests of
+/// either side without any networking, so it can be driven by the HTTP
+/// server of [Simulator](super::Simulator) or called directly.
+#[derive(Clone)]
+pub struct SimLink {
+    state: Arc<Mutex<LinkState>>,
+}
+
+impl SimLink {
+    pub fn new(config: SimConfig) -> Self {
+        let stored_keys = config.initial_key_count.min(config.max_key_count) as f64;
+        Self {
+            state: Arc::new(Mutex::new(LinkState {
+                config,
+                stored_keys,
+                refilled_at: Instant::now(),
+                pending: [HashMap::new(), HashMap::new()],
+                faults: FaultScenario::new(),
+            })),
+        }
+    }
+
+    /// Returns the settings of the link.
+    pub fn config(&self) -> SimConfig {
+        self.state.lock().unwrap().config.clone()
+    }
+
+    /// Returns the number of keys in the shared buffer.
+    pub fn stored_key_count(&self) -> u32 {
+        let mut state = self.state.lock().unwrap();
+        state.refill();
+        state.stored_keys as u32
+    }
+
+    /// Sets the number of keys in the shared buffer.
+    pub fn set_stored_key_count(&self, count: u32) {
+        let mut state = self.state.lock().unwrap();
+        state.refill();
+        state.stored_keys = count.min(state.config.max_key_count) as f64;
+    }
+
+    /// Returns the number of keys the SAE of `side` can still fetch with dec_keys.
+    pub fn pending_key_count(&self, side: Side) -> usize {
+        self.state.lock().unwrap().pending[side.index()].len()
+    }
+
+    /// Replaces the injected faults with those of `scenario`.
+    pub fn set_scenario(&self, scenario: FaultScenario) {
+        self.state.lock().unwrap().faults = scenario;
+    }
+
+    /// Removes all injected faults.
+    pub fn clear_faults(&self) {
+        self.set_scenario(FaultScenario::new());
+    }
+
+    pub(crate) fn take_garbage_reset(&self, side: Side) -> bool {
+        self.state.lock().unwrap().faults.take_garbage_reset(side)
+    }
+
+    /// Answers a request sent to the KME (or QRNG) of `side`.
+    pub fn handle(&self, side: Side, request: &SimRequest) -> SimResponse {
+        let faults = self
+            .state
+            .lock()
+            .unwrap()
+            .faults
+            .take(side, Endpoint::of(request));
+        let mut response = if faults.contains(&Fault::NoKeysAvailable) {
+            SimResponse::error(503, "no keys available")
+        } else {
+            self.route(side, request)
+        };
+        for fault in faults {
+            match fault {
+                Fault::Latency(latency) => response.delay += latency,
+                Fault::NoKeysAvailable => {}
+                Fault::TruncatedJson => response.body.truncate(response.body.len() / 2),
+                Fault::WrongKeySize => map_keys(&mut response, |key| {
+                    let bytes = BASE64.decode(key["key"].as_str().unwrap_or_default()).unwrap_or_default();
+                    key["key"] = json!(BASE64.encode(&bytes[..bytes.len() / 2]));
+                }),
+                Fault::MismatchedKeyIds => map_keys(&mut response, |key| key["key_ID"] = json!(random_uuid())),
+                Fault::GarbageReset | Fault::ConnectionReset => response.drop_connection = true,
+            }
+        }
+        response
+    }
+
+    fn route(&self, side: Side, request: &SimRequest) -> SimResponse {
+        let segments: Vec<&str> = request.path.trim_matches('/').split('/').collect();
+        match (request.method.as_str(), segments.as_slice()) {
+            ("GET", ["api", "v1", "keys", sae_id, "status"]) => self.status(side, sae_id),
+            ("POST", ["api", "v1", "keys", sae_id, "enc_keys"]) => {
+                self.enc_keys(side, sae_id, &request.body)
+            }
+            ("POST", ["api", "v1", "keys", sae_id, "dec_keys"]) => {
+                self.dec_keys(side, sae_id, &request.body)
+            }
+            ("GET", ["qrng", format]) => random_response(format, request.query.as_deref()),
+            _ => SimResponse::error(404, "not found"),
+        }
+    }
+
+    fn status(&self, side: Side, slave_sae_id: &str) -> SimResponse {
+        let mut state = self.state.lock().unwrap();
+        if slave_sae_id != state.sae_id(side.peer()) {
+            return SimResponse::error(401, "unknown SAE ID");
+        }
+        state.refill();
+        SimResponse::json(
+            200,
+            json!({
+                "source_KME_ID": state.kme_id(side),
+                "target_KME_ID": state.kme_id(side.peer()),
+                "master_SAE_ID": state.sae_id(side),
+                "slave_SAE_ID": state.sae_id(side.peer()),
+                "key_size": state.config.key_size,
+                "stored_key_count": state.stored_keys as u32,
+                "max_key_count": state.config.max_key_count,
+                "max_key_per_request": state.config.max_key_per_request,
+                "max_key_size": state.config.max_key_size,
+                "min_key_size": state.config.min_key_size,
+                "max_SAE_ID_count": 0,
+            }),
+        )
+    }
+
+    fn enc_keys(&self, side: Side, slave_sae_id: &str, body: &[u8]) -> SimResponse {
+        let mut state = self.state.lock().unwrap();
+        if slave_sae_id != state.sae_id(side.peer()) {
+            return SimResponse::error(401, "unknown SAE ID");
+        }
+        let Ok(body) = serde_json::from_slice::<Value>(body) else {
+            return SimResponse::error(400, "invalid JSON");
+        };
+        let size = match body["size"].as_u64().map(u32::try_from) {
+            None => state.config.key_size,
+            Some(Ok(size)) => size,
+            Some(Err(_)) => return SimResponse::error(400, "invalid key size"),
+        };
+        if size < state.config.min_key_size || size > state.config.max_key_size || size % 8 != 0 {
+            return SimResponse::error(400, "invalid key size");
+        }
+        let key_ids: Vec<String> = match body["key_IDs"].as_array() {
+            Some(ids) => ids
+                .iter()
+                .filter_map(|id| id.as_str().or_else(|| id["key_ID"].as_str()))
+                .map(String::from)
+                .collect(),
+            None => {
+                let number = body["number"].as_u64().unwrap_or(1);
+                if number > state.config.max_key_per_request as u64 {
+                    return SimResponse::error(400, "invalid number of keys");
+                }
+                (0..number).map(|_| random_uuid()).collect()
+            }
+        };
+        if key_ids.is_empty() || key_ids.len() > state.config.max_key_per_request as usize {
+            return SimResponse::error(400, "invalid number of keys");
+        }
+        let pending = &state.pending[side.peer().index()];
+        if key_ids.iter().any(|key_id| pending.contains_key(key_id)) {
+            return SimResponse::error(400, "key ID already in use");
+        }
+        state.refill();
+        let keys_per_id = (size as u64 + state.config.key_size as u64 - 1) / state.config.key_size as u64;
+        let needed = (key_ids.len() as u64).saturating_mul(keys_per_id) as f64;
+        if state.stored_keys < needed {
+            return SimResponse::error(503, "not enough keys available");
+        }
+        state.stored_keys -= needed;
+
+        let mut keys = Vec::new();
+        for key_id in key_ids {
+            let key = random_bytes(size as usize / 8);
+            keys.push(json!({ "key_ID": key_id, "key": BASE64.encode(&key) }));
+            state.pending[side.peer().index()].insert(key_id, key);
+        }
+        SimResponse::json(200, json!({ "keys": keys }))
+    }
+
+    fn dec_keys(&self, side: Side, master_sae_id: &str, body: &[u8]) -> SimResponse {
+        let mut state = self.state.lock().unwrap();
+        if master_sae_id != state.sae_id(side.peer()) {
+            return SimResponse::error(401, "unknown SAE ID");
+        }
+        let Ok(body) = serde_json::from_slice::<Value>(body) else {
+            return SimResponse::error(400, "invalid JSON");
+        };
+        let key_ids: Vec<&str> = body["key_IDs"]
+            .as_array()
+            .map(|ids| ids.iter().filter_map(|id| id["key_ID"].as_str()).collect())
+            .unwrap_or_default();
+        let pending = &mut state.pending[side.index()];
+        if key_ids.is_empty() || key_ids.iter().any(|key_id| !pending.contains_key(*key_id)) {
+            return SimResponse::error(400, "unknown key ID");
+        }
+        let keys: Vec<Value> = key_ids
+            .iter()
+            .map(|key_id| {
+                let key = pending.remove(*key_id).unwrap();
+                json!({ "key_ID": key_id, "key": BASE64.encode(key) })
+            })
+            .collect();
+        SimResponse::json(200, json!({ "keys": keys }))
+    }
+}
+
+fn random_response(format: &str, query: Option<&str>) -> SimResponse {
+    let format = match format {
+        "hex" => QrngFormat::Hex,
+        "base64" => QrngFormat::Base64,
+        "bytes" => QrngFormat::Bytes,
+        _ => return SimResponse::error(404, "not found"),
+    };
+    let size = query
+        .unwrap_or_default()
+        .split('&')
+        .find_map(|param| param.strip_prefix("size="))
+        .and_then(|size| size.parse::<u32>().ok());
+    let Some(size) = size else {
+        return SimResponse::error(400, "invalid size");
+    };
+    if format.check_size(size).is_err() {
+        return SimResponse::error(400, "invalid size");
+    }
+    let bytes = random_bytes(size as usize);
+    let result = match format {
+        QrngFormat::Hex => hex::encode(bytes),
+        QrngFormat::Base64 => BASE64.encode(bytes),
+        QrngFormat::Bytes => return SimResponse::new(200, "application/octet-stream", bytes),
+    };
+    SimResponse::json(
+        200,
+        json!({ "result": result, "size": size.to_string(), "format": format.to_string(), "executeTime": 1 }),
+    )
+}
+
+/// Applies `f` to every key of a successful key response.
+fn map_keys(response: &mut SimResponse, f: impl Fn(&mut Value)) {
+    if response.status != 200 {
+        return;
+    }
+    let Ok(mut body) = serde_json::from_slice::<Value>(&response.body) else {
+        return;
+    };
+    if let Some(keys) = body["keys"].as_array_mut() {
+        keys.iter_mut().for_each(f);
+        response.body = body.to_string().into_bytes();
+    }
+}
+
+fn random_bytes(len: usize) -> Vec<u8> {
+    let mut bytes = vec![0u8; len];
+    getrandom::getrandom(&mut bytes).expect("OS random number generator failed");
+    bytes
+}
+
+fn random_uuid() -> String {
+    random::uuid_v4(&random_bytes(16)).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(method: &str, path: &str, body: Value) -> SimRequest {
+        SimRequest {
+            method: method.to_string(),
+            path: path.to_string(),
+            query: None,
+            body: body.to_string().into_bytes(),
+        }
+    }
+
+    fn body(response: &SimResponse) -> Value {
+        serde_json::from_slice(&response.body).unwrap()
+    }
+
+    #[test]
+    fn keys_delivered_to_peer() {
+        let link = SimLink::new(SimConfig::default());
+        let enc = link.handle(Side::A, &request("POST", "/api/v1/keys/Sim_2SAE/enc_keys", json!({"size": 512, "number": 2})));
+        assert_eq!(enc.status, 200);
+        let enc = body(&enc);
+        assert_eq!(link.pending_key_count(Side::B), 2);
+        assert_eq!(link.stored_key_count(), 4096 - 4);
+
+        let key_id = enc["keys"][1]["key_ID"].as_str().unwrap();
+        let dec = link.handle(Side::B, &request("POST", "/api/v1/keys/Sim_1SAE/dec_keys", json!({"key_IDs": [{"key_ID": key_id}]})));
+        assert_eq!(dec.status, 200);
+        assert_eq!(body(&dec)["keys"][0], enc["keys"][1]);
+        assert_eq!(link.pending_key_count(Side::B), 1);
+
+        // Keys can be fetched only once.
+        let dec = link.handle(Side::B, &request("POST", "/api/v1/keys/Sim_1SAE/dec_keys", json!({"key_IDs": [{"key_ID": key_id}]})));
+        assert_eq!(dec.status, 400);
+    }
+
+    #[test]
+    fn limits() {
+        let link = SimLink::new(SimConfig { initial_key_count: 3, ..Default::default() });
+        let path = "/api/v1/keys/Sim_2SAE/enc_keys";
+        assert_eq!(link.handle(Side::A, &request("POST", path, json!({"size": 100}))).status, 400);
+        assert_eq!(link.handle(Side::A, &request("POST", path, json!({"size": (1u64 << 32) + 256}))).status, 400);
+        assert_eq!(link.handle(Side::A, &request("POST", path, json!({"number": 65}))).status, 400);
+        assert_eq!(link.handle(Side::A, &request("POST", path, json!({"number": 4}))).status, 503);
+        assert_eq!(link.handle(Side::A, &request("POST", "/api/v1/keys/Other/enc_keys", json!({}))).status, 401);
+        assert_eq!(link.handle(Side::A, &request("POST", path, json!({"number": 3}))).status, 200);
+        assert_eq!(link.stored_key_count(), 0);
+    }
+
+    #[test]
+    fn status() {
+        let link = SimLink::new(SimConfig::default());
+        let status = link.handle(Side::B, &request("GET", "/api/v1/keys/Sim_1SAE/status", json!({})));
+        let status = body(&status);
+        assert_eq!(status["source_KME_ID"], "Sim_2KME");
+        assert_eq!(status["master_SAE_ID"], "Sim_2SAE");
+        assert_eq!(status["stored_key_count"], 4096);
+    }
+
+    #[test]
+    fn qrng() {
+        let link = SimLink::new(SimConfig::default());
+        let mut random = request("GET", "/qrng/hex", json!({}));
+        random.query = Some("size=20".to_string());
+        let response = link.handle(Side::A, &random);
+        assert_eq!(body(&response)["result"].as_str().unwrap().len(), 40);
+
+        random.path = "/qrng/bytes".to_string();
+        assert_eq!(link.handle(Side::A, &random).body.len(), 20);
+
+        random.query = None;
+        assert_eq!(link.handle(Side::A, &random).status, 400);
+    }
+}
@@ -0,0 +1,82 @@
+use reqwest::header::{HeaderValue, CONTENT_TYPE};
+use reqwest::StatusCode;
+
+use super::kme::{Side, SimLink, SimRequest};
+use crate::error::PqkdError;
+use crate::transport::{MemoryTransport, TransportRequest, TransportResponse};
+
+impl SimLink {
+    /// Returns a transport delivering requests straight to the KME of
+    /// `side` with [SimLink::handle], without any networking.
+    ///
+    /// Latency faults are not applied. Garbage and plain connection
+    /// resets fail the request with [PqkdError::TransportError].
+    pub fn transport(&self, side: Side) -> MemoryTransport {
+        let link = self.clone();
+        MemoryTransport::new(move |request| {
+            let response = link.handle(side, &SimRequest::from(request));
+            if response.drop_connection {
+                return Err(PqkdError::TransportError(format!(
+                    "connection to simulated KME {:?} closed",
+                    side
+                )));
+            }
+            let status = StatusCode::from_u16(response.status)
+                .map_err(|err| PqkdError::TransportError(err.to_string()))?;
+            let mut transport_response = TransportResponse::new(status, response.body);
+            transport_response
+                .headers
+                .insert(CONTENT_TYPE, HeaderValue::from_static(response.content_type));
+            Ok(transport_response)
+        })
+    }
+
+    /// Returns a client of the KME and QRNG of `side` using [SimLink::transport].
+    #[cfg(feature = "async")]
+    pub fn client(&self, side: Side) -> crate::PqkdClient {
+        crate::BuilderPqkdClient::with_addr(memory_addr(side))
+            .expect("simulator address is valid")
+            .with_local_sae_id(&self.sae_id(side))
+            .with_transport(self.transport(side))
+            .build()
+    }
+
+    /// Returns a blocking client of the KME and QRNG of `side` using [SimLink::transport].
+    #[cfg(feature = "blocking")]
+    pub fn blocking_client(&self, side: Side) -> crate::blocking::PqkdClient {
+        crate::blocking::BuilderPqkdClient::with_addr(memory_addr(side))
+            .expect("simulator address is valid")
+            .with_local_sae_id(&self.sae_id(side))
+            .with_transport(self.transport(side))
+            .build()
+    }
+
+    #[cfg(any(feature = "async", feature = "blocking"))]
+    fn sae_id(&self, side: Side) -> String {
+        let config = self.config();
+        match side {
+            Side::A => config.sae_id_a,
+            Side::B => config.sae_id_b,
+        }
+    }
+}
+
+/// Placeholder address of the KME of `side`, never resolved.
+#[cfg(any(feature = "async", feature = "blocking"))]
+fn memory_addr(side: Side) -> &'static str {
+    match side {
+        Side::A => "http://kme-a.sim",
+        Side::B => "http://kme-b.sim",
+    }
+}
+
+impl From<TransportRequest> for SimRequest {
+    fn from(request: TransportRequest) -> Self {
+        Self {
+            method: request.method.to_string(),
+            path: request.url.path().to_string(),
+            query: request.url.query().map(str::to_string),
+            body: request.body,
+        }
+    }
+}
@@ -0,0 +1,234 @@
+use std::net::{SocketAddr, TcpListener};
+use std::thread::JoinHandle;
+
+use hyper::server::conn::Http;
+use hyper::service::service_fn;
+use hyper::{Body, Request, Response};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::oneshot;
+
+use super::config::SimConfig;
+use super::kme::{Side, SimLink, SimRequest, SimResponse};
+use crate::error::PqkdError;
+
+/// Simulated pair of pQKD devices serving the KME and QRNG APIs over HTTP.
+///
+/// Each side listens on its own address and serves both the KME and the
+/// QRNG endpoints. The servers run on a background thread with their own
+/// runtime, so the simulator can be used from synchronous and
+/// asynchronous code alike. They are stopped when the simulator is dropped.
+///
+/// Faults can be injected with [SimLink::set_scenario] on [Simulator::link].
+///
+/// # Example
+///
+/// ```
+/// use pqkd::sim::{SimConfig, Simulator};
+///
+/// # #[cfg(feature = "async")]
+/// #[tokio::main]
+/// async fn main() {
+///     let simulator = Simulator::start(SimConfig::default()).unwrap();
+///     let alice = simulator.client_a();
+///     let bob = simulator.client_b();
+///
+///     let keys = alice.enc_keys(simulator.sae_id_b()).send().await.unwrap().keys();
+///     let peer_keys = bob
+///         .dec_keys(simulator.sae_id_a())
+///         .key_id(keys[0].key_id())
+///         .send()
+///         .await
+///         .unwrap()
+///         .keys();
+///     assert_eq!(keys, peer_keys);
+/// }
+/// # #[cfg(not(feature = "async"))]
+/// # fn main() {}
+/// ```
+pub struct Simulator {
+    link: SimLink,
+    sae_id_a: String,
+    sae_id_b: String,
+    addr_a: SocketAddr,
+    addr_b: SocketAddr,
+    shutdown: Option<oneshot::Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Simulator {
+    /// Starts the KMEs of both sides.
+    pub fn start(config: SimConfig) -> Result<Self, PqkdError> {
+        Self::start_link(SimLink::new(config))
+    }
+
+    /// Starts the KMEs of both sides serving the given link.
+    pub fn start_link(link: SimLink) -> Result<Self, PqkdError> {
+        let config = link.config();
+        let listener_a = bind(config.addr_a)?;
+        let listener_b = bind(config.addr_b)?;
+        let addr_a = listener_a.local_addr()?;
+        let addr_b = listener_b.local_addr()?;
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+        let (listener_a, listener_b) = {
+            let _runtime = runtime.enter();
+            (
+                tokio::net::TcpListener::from_std(listener_a)?,
+                tokio::net::TcpListener::from_std(listener_b)?,
+            )
+        };
+        let (shutdown, stopped) = oneshot::channel::<()>();
+        let server_link = link.clone();
+        let thread = std::thread::spawn(move || {
+            runtime.block_on(async move {
+                let server_a = serve(listener_a, server_link.clone(), Side::A);
+                let server_b = serve(listener_b, server_link, Side::B);
+                tokio::select! {
+                    _ = server_a => {},
+                    _ = server_b => {},
+                    _ = stopped => {},
+                }
+            });
+        });
+
+        Ok(Self {
+            link,
+            sae_id_a: config.sae_id_a,
+            sae_id_b: config.sae_id_b,
+            addr_a,
+            addr_b,
+            shutdown: Some(shutdown),
+            thread: Some(thread),
+        })
+    }
+
+    /// Returns the shared state of the simulated link.
+    pub fn link(&self) -> &SimLink {
+        &self.link
+    }
+
+    /// Returns the URL of the KME (and QRNG) of side A.
+    pub fn addr_a(&self) -> String {
+        format!("http://{}", self.addr_a)
+    }
+
+    /// Returns the URL of the KME (and QRNG) of side B.
+    pub fn addr_b(&self) -> String {
+        format!("http://{}", self.addr_b)
+    }
+
+    pub fn sae_id_a(&self) -> &str {
+        &self.sae_id_a
+    }
+
+    pub fn sae_id_b(&self) -> &str {
+        &self.sae_id_b
+    }
+
+    /// Returns a client connected to the KME and QRNG of side A.
+    #[cfg(feature = "async")]
+    pub fn client_a(&self) -> crate::PqkdClient {
+        crate::BuilderPqkdClient::with_addr(&self.addr_a())
+            .and_then(|builder| builder.with_qrng_addr(&self.addr_a()))
+            .expect("simulator address is valid")
+            .with_local_sae_id(&self.sae_id_a)
+            .build()
+    }
+
+    /// Returns a client connected to the KME and QRNG of side B.
+    #[cfg(feature = "async")]
+    pub fn client_b(&self) -> crate::PqkdClient {
+        crate::BuilderPqkdClient::with_addr(&self.addr_b())
+            .and_then(|builder| builder.with_qrng_addr(&self.addr_b()))
+            .expect("simulator address is valid")
+            .with_local_sae_id(&self.sae_id_b)
+            .build()
+    }
+
+    /// Returns a blocking client connected to the KME and QRNG of side A.
+    #[cfg(feature = "blocking")]
+    pub fn blocking_client_a(&self) -> crate::blocking::PqkdClient {
+        crate::blocking::BuilderPqkdClient::with_addr(&self.addr_a())
+            .and_then(|builder| builder.with_qrng_addr(&self.addr_a()))
+            .expect("simulator address is valid")
+            .with_local_sae_id(&self.sae_id_a)
+            .build()
+    }
+
+    /// Returns a blocking client connected to the KME and QRNG of side B.
+    #[cfg(feature = "blocking")]
+    pub fn blocking_client_b(&self) -> crate::blocking::PqkdClient {
+        crate::blocking::BuilderPqkdClient::with_addr(&self.addr_b())
+            .and_then(|builder| builder.with_qrng_addr(&self.addr_b()))
+            .expect("simulator address is valid")
+            .with_local_sae_id(&self.sae_id_b)
+            .build()
+    }
+}
+
+impl Drop for Simulator {
+    fn drop(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn bind(addr: SocketAddr) -> Result<TcpListener, PqkdError> {
+    let listener = TcpListener::bind(addr)?;
+    listener.set_nonblocking(true)?;
+    Ok(listener)
+}
+
+// Bytes no HTTP client can parse as a response (they happen to form a
+// TLS alert record).
+const GARBAGE_BYTES: [u8; 7] = [0x15, 0x03, 0x03, 0x00, 0x02, 0x02, 0x28];
+
+async fn serve(listener: tokio::net::TcpListener, link: SimLink, side: Side) {
+    while let Ok((mut stream, _)) = listener.accept().await {
+        let link = link.clone();
+        tokio::spawn(async move {
+            if link.take_garbage_reset(side) {
+                let _ = stream.write_all(&GARBAGE_BYTES).await;
+                let _ = stream.shutdown().await;
+                return;
+            }
+            let service = service_fn(move |request| {
+                let link = link.clone();
+                async move { handle(&link, side, request).await }
+            });
+            let _ = Http::new().http1_only(true).serve_connection(stream, service).await;
+        });
+    }
+}
+
+async fn handle(link: &SimLink, side: Side, request: Request<Body>) -> std::io::Result<Response<Body>> {
+    let (parts, body) = request.into_parts();
+    let body = hyper::body::to_bytes(body).await.unwrap_or_default();
+    let request = SimRequest {
+        method: parts.method.to_string(),
+        path: parts.uri.path().to_string(),
+        query: parts.uri.query().map(String::from),
+        body: body.to_vec(),
+    };
+    let response = link.handle(side, &request);
+    tokio::time::sleep(response.delay).await;
+    if response.drop_connection {
+        // Failing the service makes hyper close the connection without answering.
+        return Err(std::io::ErrorKind::ConnectionReset.into());
+    }
+    Ok(into_response(response))
+}
+
+fn into_response(response: SimResponse) -> Response<Body> {
+    Response::builder()
+        .status(response.status)
+        .header("content-type", response.content_type)
+        .body(Body::from(response.body))
+        .unwrap()
+}
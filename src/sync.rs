@@ -0,0 +1,280 @@
+//! Key synchronisation between the master and the slave SAE.
+//!
+//! The KME of the master SAE hands out keys with `enc_keys`; the slave SAE
+//! can only fetch them with `dec_keys` once it knows their IDs. This module
+//! defines the messages the SAEs exchange for that over a channel of their
+//! own (the pQKD does not carry them):
+//!
+//! 1. The master requests keys and sends a [KeyOffer] with their IDs and a
+//!    context label naming what they are for.
+//! 2. The slave fetches the keys with `dec_keys`, checks the MAC of the
+//!    offer and answers with a [KeyAccept].
+//! 3. The master checks the MAC of the acceptance.
+//!
+//! The MACs are computed like [Key::confirmation_tag], over the offer and
+//! with a label of each message: keyed with a key derived with HKDF-SHA256
+//! from the offered keys and the label, so a valid MAC proves that its
+//! sender holds the same keys, without revealing them. The messages are
+//! JSON objects with a `version` field; messages of other versions are
+//! rejected.
+//!
+//! The clients implement the steps as `offer_keys`, `accept_keys` and
+//! [KeyOffer::verify_accept].
+use hmac::Mac;
+use serde::{Deserialize, Serialize};
+
+use crate::error::PqkdError;
+use crate::response::{confirmation_mac, HmacSha256};
+use crate::Key;
+
+/// Version of the messages created by this crate.
+pub const SYNC_VERSION: u32 = 1;
+
+const NONCE_LEN: usize = 16;
+const OFFER_LABEL: &[u8] = b"pqkd key offer v1";
+const ACCEPT_LABEL: &[u8] = b"pqkd key accept v1";
+
+/// Announcement of keys by the master SAE.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyOffer {
+    pub version: u32,
+    pub master_sae_id: String,
+    pub slave_sae_id: String,
+    /// Label of what the keys are used for, chosen by the application.
+    pub context: String,
+    /// IDs of the keys, in the order they are used.
+    pub key_ids: Vec<String>,
+    /// Random nonce (hex) making the MACs of every offer unique.
+    pub nonce: String,
+    /// HMAC-SHA256 (hex) of the offer, keyed with the keys.
+    pub mac: String,
+}
+
+/// Acknowledgement of a [KeyOffer] by the slave SAE.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyAccept {
+    pub version: u32,
+    /// Nonce of the accepted offer.
+    pub nonce: String,
+    /// HMAC-SHA256 (hex) of the accepted offer, keyed with the keys.
+    pub mac: String,
+}
+
+impl KeyOffer {
+    /// Creates an offer of `keys`, received from the KME of the master SAE.
+    pub fn new(
+        master_sae_id: &str,
+        slave_sae_id: &str,
+        context: &str,
+        keys: &[Key],
+    ) -> Result<Self, PqkdError> {
+        if keys.is_empty() {
+            return Err(no_keys());
+        }
+        let mut nonce = [0u8; NONCE_LEN];
+        getrandom::getrandom(&mut nonce)?;
+        let mut offer = Self {
+            version: SYNC_VERSION,
+            master_sae_id: master_sae_id.to_string(),
+            slave_sae_id: slave_sae_id.to_string(),
+            context: context.to_string(),
+            key_ids: keys.iter().map(|key| key.key_id().to_string()).collect(),
+            nonce: hex::encode(nonce),
+            mac: String::new(),
+        };
+        offer.mac = hex::encode(offer.mac_with(OFFER_LABEL, keys)?.finalize().into_bytes());
+        Ok(offer)
+    }
+
+    /// Decodes an offer received from the master SAE.
+    pub fn decode(bytes: &[u8]) -> Result<Self, PqkdError> {
+        let offer: Self = decode(bytes)?;
+        check_version(offer.version)?;
+        if offer.key_ids.is_empty() {
+            return Err(no_keys());
+        }
+        Ok(offer)
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        serde_json::to_vec(self).expect("key offer is serializable")
+    }
+
+    /// Checks the offer against `keys`, fetched by the slave SAE with
+    /// `dec_keys`, and acknowledges it.
+    pub fn accept(&self, keys: &[Key]) -> Result<KeyAccept, PqkdError> {
+        check_version(self.version)?;
+        verify_mac(self.mac_with(OFFER_LABEL, keys)?, &self.mac)?;
+        Ok(KeyAccept {
+            version: SYNC_VERSION,
+            nonce: self.nonce.clone(),
+            mac: hex::encode(self.mac_with(ACCEPT_LABEL, keys)?.finalize().into_bytes()),
+        })
+    }
+
+    /// Checks that `accept` acknowledges this offer of `keys`.
+    pub fn verify_accept(&self, accept: &KeyAccept, keys: &[Key]) -> Result<(), PqkdError> {
+        check_version(accept.version)?;
+        if accept.nonce != self.nonce {
+            return Err(PqkdError::KeySyncError(
+                "acceptance of another offer".to_string(),
+            ));
+        }
+        verify_mac(self.mac_with(ACCEPT_LABEL, keys)?, &accept.mac)
+    }
+
+    /// Returns an HMAC over the offer (without its MAC), keyed with a key
+    /// derived from the offered keys, in the order of the offer, and `label`.
+    fn mac_with(&self, label: &[u8], keys: &[Key]) -> Result<HmacSha256, PqkdError> {
+        if self.key_ids.is_empty() {
+            return Err(no_keys());
+        }
+        let mut material = Vec::new();
+        for key_id in &self.key_ids {
+            let key = keys
+                .iter()
+                .find(|key| key.key_id() == key_id)
+                .ok_or_else(|| PqkdError::KeySyncError(format!("key {} is missing", key_id)))?;
+            material.extend_from_slice(&key.material()?);
+        }
+        let fields = [&self.master_sae_id, &self.slave_sae_id, &self.context]
+            .into_iter()
+            .chain(&self.key_ids)
+            .chain([&self.nonce])
+            .map(|field| field.as_bytes());
+        Ok(confirmation_mac(&material, label, fields))
+    }
+}
+
+impl KeyAccept {
+    /// Decodes an acceptance received from the slave SAE.
+    pub fn decode(bytes: &[u8]) -> Result<Self, PqkdError> {
+        let accept: Self = decode(bytes)?;
+        check_version(accept.version)?;
+        Ok(accept)
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        serde_json::to_vec(self).expect("key acceptance is serializable")
+    }
+}
+
+fn decode<T: serde::de::DeserializeOwned>(bytes: &[u8]) -> Result<T, PqkdError> {
+    serde_json::from_slice(bytes)
+        .map_err(|err| PqkdError::KeySyncError(format!("invalid message: {}", err)))
+}
+
+fn no_keys() -> PqkdError {
+    PqkdError::KeySyncError("no keys offered".to_string())
+}
+
+fn check_version(version: u32) -> Result<(), PqkdError> {
+    if version != SYNC_VERSION {
+        return Err(PqkdError::KeySyncError(format!(
+            "unsupported version {}",
+            version
+        )));
+    }
+    Ok(())
+}
+
+fn verify_mac(mac: HmacSha256, expected: &str) -> Result<(), PqkdError> {
+    let expected =
+        hex::decode(expected).map_err(|_| PqkdError::KeySyncError("invalid MAC".to_string()))?;
+    mac.verify_slice(&expected)
+        .map_err(|_| PqkdError::KeySyncError("key confirmation failed".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(key_id: &str, key: &str) -> Key {
+        serde_json::from_value(serde_json::json!({"key_ID": key_id, "key": key})).unwrap()
+    }
+
+    fn keys() -> Vec<Key> {
+        vec![
+            key(
+                "bc490419-7d60-487f-adc1-4ddcc177c139",
+                "wHHVxRwDJs3/bXd38GHP3oe4svTuRpZS0yCC7x4Ly+s=",
+            ),
+            key(
+                "0a782fb5-3434-48fe-aa4d-14f41d46cf92",
+                "OeGMPxh1+2RpJpNCYixWHFLYRubpOKCw94FcCI7VdJA=",
+            ),
+        ]
+    }
+
+    #[test]
+    fn offer_and_accept() {
+        let offer = KeyOffer::new("Test_1SAE", "Test_2SAE", "tls-psk", &keys()).unwrap();
+        let received = KeyOffer::decode(&offer.encode()).unwrap();
+        assert_eq!(received, offer);
+
+        // The slave may receive the keys in another order.
+        let mut slave_keys = keys();
+        slave_keys.reverse();
+        let accept = received.accept(&slave_keys).unwrap();
+        let accept = KeyAccept::decode(&accept.encode()).unwrap();
+        offer.verify_accept(&accept, &keys()).unwrap();
+    }
+
+    #[test]
+    fn mismatch() {
+        let offer = KeyOffer::new("Test_1SAE", "Test_2SAE", "tls-psk", &keys()).unwrap();
+        let mut other = keys();
+        other[1] = key(
+            "0a782fb5-3434-48fe-aa4d-14f41d46cf92",
+            "wHHVxRwDJs3/bXd38GHP3oe4svTuRpZS0yCC7x4Ly+s=",
+        );
+        assert!(matches!(
+            offer.accept(&other),
+            Err(PqkdError::KeySyncError(_))
+        ));
+        assert!(matches!(
+            offer.accept(&keys()[..1]),
+            Err(PqkdError::KeySyncError(_))
+        ));
+
+        let changed = KeyOffer {
+            context: "other".to_string(),
+            ..offer.clone()
+        };
+        assert!(changed.accept(&keys()).is_err());
+
+        let accept = offer.accept(&keys()).unwrap();
+        assert!(offer.verify_accept(&accept, &other).is_err());
+        let replayed = KeyOffer::new("Test_1SAE", "Test_2SAE", "tls-psk", &keys()).unwrap();
+        assert!(replayed.verify_accept(&accept, &keys()).is_err());
+    }
+
+    #[test]
+    fn version() {
+        let mut offer = KeyOffer::new("Test_1SAE", "Test_2SAE", "tls-psk", &keys()).unwrap();
+        offer.version = 2;
+        assert!(matches!(
+            KeyOffer::decode(&offer.encode()),
+            Err(PqkdError::KeySyncError(reason)) if reason == "unsupported version 2"
+        ));
+        assert!(KeyAccept::decode(b"{}").is_err());
+    }
+
+    #[test]
+    fn no_keys() {
+        assert!(matches!(
+            KeyOffer::new("Test_1SAE", "Test_2SAE", "tls-psk", &[]),
+            Err(PqkdError::KeySyncError(reason)) if reason == "no keys offered"
+        ));
+        let offer = KeyOffer::new("Test_1SAE", "Test_2SAE", "tls-psk", &keys()).unwrap();
+        let accept = offer.accept(&keys()).unwrap();
+        let empty = KeyOffer {
+            key_ids: Vec::new(),
+            ..offer
+        };
+        assert!(KeyOffer::decode(&empty.encode()).is_err());
+        assert!(empty.accept(&keys()).is_err());
+        assert!(empty.accept(&[]).is_err());
+        assert!(empty.verify_accept(&accept, &keys()).is_err());
+    }
+}
@@ -0,0 +1,184 @@
+//! Tracing of client operations with the `tracing` feature.
+//!
+//! Only methods, SAE IDs, key IDs, sizes, counts, status codes and
+//! latencies are recorded, never keys or random values. Without the
+//! feature all functions are no-ops.
+#![cfg_attr(not(feature = "tracing"), allow(unused_variables, dead_code))]
+#[cfg(feature = "async")]
+use std::future::Future;
+use std::time::Duration;
+#[cfg(feature = "tracing")]
+use std::time::Instant;
+
+use url::Url;
+
+use crate::error::PqkdError;
+use crate::qrng::{QrngFormat, QrngResponse};
+use crate::request::PqkdRequest;
+use crate::response::PqkdResponse;
+use crate::retry::RetryPolicy;
+use crate::transport::{TransportRequest, TransportResponse};
+
+#[cfg(feature = "tracing")]
+pub(crate) use tracing::Span;
+
+/// Placeholder of `tracing::Span`.
+#[cfg(not(feature = "tracing"))]
+pub(crate) struct Span;
+
+#[cfg(not(feature = "tracing"))]
+impl Span {
+    pub(crate) fn entered(self) -> Self {
+        self
+    }
+}
+
+/// Span of a request to the KME, including its retries.
+pub(crate) fn kme_span(request: &PqkdRequest) -> Span {
+    #[cfg(feature = "tracing")]
+    return tracing::debug_span!(
+        "kme_request",
+        method = ?request.pqkd_method(),
+        sae_id = request.sae_id(),
+        number = request.number(),
+        size = request.size(),
+        key_ids = %request.key_ids().join(","),
+    );
+    #[cfg(not(feature = "tracing"))]
+    Span
+}
+
+/// Span of a request to the QRNG, including its retries.
+pub(crate) fn qrng_span(format: QrngFormat, size: u32) -> Span {
+    #[cfg(feature = "tracing")]
+    return tracing::debug_span!("qrng_request", format = %format, size);
+    #[cfg(not(feature = "tracing"))]
+    Span
+}
+
+/// Runs `future` in `span`.
+#[cfg(feature = "async")]
+pub(crate) fn instrument<F: Future>(future: F, span: Span) -> impl Future<Output = F::Output> {
+    #[cfg(feature = "tracing")]
+    return tracing::Instrument::instrument(future, span);
+    #[cfg(not(feature = "tracing"))]
+    future
+}
+
+pub(crate) fn retry(attempt: u32, delay: Duration, err: &PqkdError) {
+    #[cfg(feature = "tracing")]
+    tracing::info!(attempt, delay_ms = delay.as_millis() as u64, error = %err, "retrying request");
+}
+
+pub(crate) fn kme_done(result: &Result<PqkdResponse, PqkdError>, elapsed: Duration) {
+    #[cfg(feature = "tracing")]
+    {
+        let latency_ms = elapsed.as_millis() as u64;
+        match result {
+            Ok(PqkdResponse::Status(status)) => tracing::debug!(
+                latency_ms,
+                source_kme_id = %status.source_kme_id,
+                stored_key_count = status.stored_key_count,
+                "status received"
+            ),
+            Ok(PqkdResponse::Keys(keys)) => tracing::debug!(
+                latency_ms,
+                key_count = keys.len(),
+                key_ids = %keys.iter().map(|key| key.key_id()).collect::<Vec<_>>().join(","),
+                "keys received"
+            ),
+            Err(err) => tracing::warn!(latency_ms, status = status_code(err), error = %err, "KME request failed"),
+        }
+    }
+}
+
+pub(crate) fn qrng_done(result: &Result<QrngResponse, PqkdError>, elapsed: Duration) {
+    #[cfg(feature = "tracing")]
+    {
+        let latency_ms = elapsed.as_millis() as u64;
+        match result {
+            Ok(_) => tracing::debug!(latency_ms, "random data received"),
+            Err(err) => tracing::warn!(latency_ms, status = status_code(err), error = %err, "QRNG request failed"),
+        }
+    }
+}
+
+#[cfg(feature = "tracing")]
+fn status_code(err: &PqkdError) -> Option<u16> {
+    match err {
+        PqkdError::HttpStatus(status) => Some(status.as_u16()),
+        PqkdError::RequestError(err) => err.status().map(|status| status.as_u16()),
+        _ => None,
+    }
+}
+
+/// A single HTTP exchange through the transport.
+pub(crate) struct Exchange {
+    #[cfg(feature = "tracing")]
+    method: reqwest::Method,
+    #[cfg(feature = "tracing")]
+    path: String,
+    #[cfg(feature = "tracing")]
+    started: Instant,
+}
+
+impl Exchange {
+    pub(crate) fn start(request: &TransportRequest) -> Self {
+        Self {
+            #[cfg(feature = "tracing")]
+            method: request.method.clone(),
+            #[cfg(feature = "tracing")]
+            path: request.url.path().to_string(),
+            #[cfg(feature = "tracing")]
+            started: Instant::now(),
+        }
+    }
+
+    pub(crate) fn finish(self, result: &Result<TransportResponse, PqkdError>) {
+        #[cfg(feature = "tracing")]
+        {
+            let latency_ms = self.started.elapsed().as_millis() as u64;
+            match result {
+                Ok(response) => tracing::debug!(
+                    method = %self.method,
+                    path = %self.path,
+                    status = response.status.as_u16(),
+                    latency_ms,
+                    "HTTP exchange"
+                ),
+                Err(err) => tracing::debug!(
+                    method = %self.method,
+                    path = %self.path,
+                    latency_ms,
+                    error = %err,
+                    "HTTP exchange failed"
+                ),
+            }
+        }
+    }
+}
+
+/// Settings of a built client worth recording.
+pub(crate) struct ClientSummary<'a> {
+    pub(crate) kme_addr: &'a Url,
+    pub(crate) qrng_addr: &'a Url,
+    pub(crate) tls: bool,
+    pub(crate) tls_reload: bool,
+    pub(crate) custom_transport: bool,
+    pub(crate) kme_pinned: bool,
+    pub(crate) retry_policy: RetryPolicy,
+}
+
+pub(crate) fn client_built(summary: ClientSummary<'_>) {
+    #[cfg(feature = "tracing")]
+    tracing::debug!(
+        kme_addr = %summary.kme_addr,
+        qrng_addr = %summary.qrng_addr,
+        tls = summary.tls,
+        tls_reload = summary.tls_reload,
+        custom_transport = summary.custom_transport,
+        kme_pinned = summary.kme_pinned,
+        max_retries = summary.retry_policy.max_retries,
+        "pQKD client built"
+    );
+}
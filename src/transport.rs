@@ -0,0 +1,156 @@
+//! Exchange of HTTP requests with the KME and QRNG servers.
+//!
+//! Clients send their requests through a transport: by default
+//! [ReqwestTransport] (or [blocking::transport::ReqwestTransport](crate::blocking::transport::ReqwestTransport)),
+//! which uses the HTTP client configured by the builder. Other transports,
+//! e.g. over a Unix socket proxy, can be plugged in with
+//! `BuilderPqkdClient::with_transport` by implementing [Transport] or
+//! [blocking::transport::Transport](crate::blocking::transport::Transport).
+//! [MemoryTransport] answers requests with a function, without any networking.
+use std::fmt;
+use std::sync::Arc;
+
+use reqwest::header::{HeaderMap, HeaderValue, CONTENT_TYPE};
+use reqwest::{Method, StatusCode};
+use url::Url;
+
+use crate::error::PqkdError;
+
+#[cfg(feature = "async")]
+pub use crate::async_impl::transport::{BoxFuture, ReqwestTransport, Transport};
+
+/// HTTP request to the KME or QRNG server.
+#[derive(Clone, Debug)]
+pub struct TransportRequest {
+    pub method: Method,
+    pub url: Url,
+    pub headers: HeaderMap,
+    pub body: Vec<u8>,
+}
+
+impl TransportRequest {
+    pub fn new(method: Method, url: Url) -> Self {
+        Self {
+            method,
+            url,
+            headers: HeaderMap::new(),
+            body: Vec::new(),
+        }
+    }
+
+    pub fn with_header(mut self, name: reqwest::header::HeaderName, value: HeaderValue) -> Self {
+        self.headers.insert(name, value);
+        self
+    }
+
+    pub fn with_body(self, body: Vec<u8>) -> Self {
+        Self { body, ..self }
+    }
+}
+
+/// HTTP response of the KME or QRNG server.
+#[derive(Clone, Debug)]
+pub struct TransportResponse {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: Vec<u8>,
+    /// Certificate (DER) the server authenticated with, if the response
+    /// was received over TLS.
+    pub peer_certificate: Option<Vec<u8>>,
+}
+
+impl TransportResponse {
+    pub fn new(status: StatusCode, body: Vec<u8>) -> Self {
+        Self {
+            status,
+            headers: HeaderMap::new(),
+            body,
+            peer_certificate: None,
+        }
+    }
+
+    /// Creates a response with a JSON body.
+    pub fn json(status: StatusCode, body: &serde_json::Value) -> Self {
+        let mut response = Self::new(status, body.to_string().into_bytes());
+        response
+            .headers
+            .insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        response
+    }
+
+    /// Returns an error if the server did not answer with a success status.
+    pub(crate) fn error_for_status(self) -> Result<Self, PqkdError> {
+        if self.status.is_success() {
+            Ok(self)
+        } else {
+            Err(PqkdError::HttpStatus(self.status))
+        }
+    }
+
+    pub(crate) fn text(&self) -> Result<&str, PqkdError> {
+        std::str::from_utf8(&self.body)
+            .map_err(|_| PqkdError::TransportError("response body is not UTF-8".to_string()))
+    }
+}
+
+type Handler = dyn Fn(TransportRequest) -> Result<TransportResponse, PqkdError> + Send + Sync;
+
+/// Transport answering requests with a function instead of sending them
+/// over the network, e.g. to test code using a client or to route the
+/// requests into the simulator (see `SimLink::transport`).
+///
+/// Implements both the asynchronous and the blocking [Transport].
+///
+/// # Example
+///
+/// ```
+/// use pqkd::transport::{MemoryTransport, TransportResponse};
+/// use reqwest::StatusCode;
+///
+/// let transport = MemoryTransport::new(|request| {
+///     assert!(request.url.path().ends_with("/status"));
+///     Ok(TransportResponse::new(StatusCode::SERVICE_UNAVAILABLE, Vec::new()))
+/// });
+/// ```
+#[derive(Clone)]
+pub struct MemoryTransport {
+    handler: Arc<Handler>,
+}
+
+impl MemoryTransport {
+    pub fn new(
+        handler: impl Fn(TransportRequest) -> Result<TransportResponse, PqkdError> + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            handler: Arc::new(handler),
+        }
+    }
+
+    pub(crate) fn handle(&self, request: TransportRequest) -> Result<TransportResponse, PqkdError> {
+        (self.handler)(request)
+    }
+}
+
+impl fmt::Debug for MemoryTransport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MemoryTransport").finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn error_for_status() {
+        let response = TransportResponse::json(StatusCode::OK, &serde_json::json!({"keys": []}));
+        assert_eq!(response.headers[CONTENT_TYPE], "application/json");
+        assert_eq!(response.error_for_status().unwrap().text().unwrap(), r#"{"keys":[]}"#);
+
+        let response = TransportResponse::new(StatusCode::SERVICE_UNAVAILABLE, Vec::new());
+        assert!(matches!(
+            response.error_for_status(),
+            Err(PqkdError::HttpStatus(StatusCode::SERVICE_UNAVAILABLE))
+        ));
+    }
+}
@@ -0,0 +1,340 @@
+//! Monitoring of the key buffers of links.
+//!
+//! The clients wrap [WatchState] in `StatusWatcher`, which requests the
+//! status of a set of links periodically and emits [WatchEvent]s when the
+//! number of stored keys crosses a threshold, the KME cannot be reached or
+//! its status requests fail.
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+use crate::error::PqkdError;
+use crate::PqkdStatus;
+
+/// Weight of the latest sample in the smoothed rate of change.
+const RATE_SMOOTHING: f64 = 0.3;
+
+/// Settings of a `StatusWatcher`.
+#[derive(Clone, Debug)]
+pub struct WatcherConfig {
+    /// SAE IDs of the links to watch.
+    pub sae_ids: Vec<String>,
+    /// Time between two status requests of a link.
+    pub interval: Duration,
+    /// Numbers of stored keys whose crossing emits an event.
+    pub thresholds: Vec<u32>,
+}
+
+impl Default for WatcherConfig {
+    fn default() -> Self {
+        Self {
+            sae_ids: Vec::new(),
+            interval: Duration::from_secs(5),
+            thresholds: Vec::new(),
+        }
+    }
+}
+
+/// Event emitted by a `StatusWatcher`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum WatchEvent {
+    /// The stored keys of the link to `sae_id` dropped below `threshold`
+    /// (or were below it when first seen).
+    BelowThreshold {
+        sae_id: String,
+        threshold: u32,
+        stored_key_count: u32,
+    },
+    /// The stored keys of the link to `sae_id` rose to `threshold` again.
+    Recovered {
+        sae_id: String,
+        threshold: u32,
+        stored_key_count: u32,
+    },
+    /// The KME could not be reached to request the status of the link to
+    /// `sae_id`: connecting or sending the request failed, or it timed out.
+    Unreachable { sae_id: String, error: String },
+    /// The status request of the link to `sae_id` reached the KME but
+    /// failed, e.g. with an HTTP error status or an invalid status.
+    StatusFailed { sae_id: String, error: String },
+    /// The status of the link to `sae_id` can be requested again after it
+    /// was unreachable or failed.
+    Reachable { sae_id: String },
+}
+
+/// Key buffer of a link as last seen by a `StatusWatcher`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LinkStats {
+    pub stored_key_count: u32,
+    /// Smoothed change of the stored keys in keys per second: the key
+    /// generation rate of the link minus the consumption of all its users.
+    /// `None` until the link has been seen twice.
+    pub rate: Option<f64>,
+    /// Whether the KME could not be reached by the last status request.
+    pub unreachable: bool,
+    /// Whether the last status request reached the KME but failed.
+    pub failed: bool,
+}
+
+impl LinkStats {
+    /// Estimates how long the stored keys last at the current rate, or
+    /// `None` if they are not decreasing.
+    pub fn time_to_empty(&self) -> Option<Duration> {
+        match self.rate {
+            Some(rate) if rate < 0.0 => Some(Duration::from_secs_f64(
+                self.stored_key_count as f64 / -rate,
+            )),
+            _ => None,
+        }
+    }
+}
+
+struct Link {
+    stored_key_count: u32,
+    rate: Option<f64>,
+    seen_at: Instant,
+}
+
+/// Why the last status request of a link failed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Failure {
+    Unreachable,
+    Failed,
+}
+
+/// Status of the watched links and the events their changes emit.
+#[derive(Default)]
+pub(crate) struct WatchState {
+    links: BTreeMap<String, Link>,
+    failures: BTreeMap<String, Failure>,
+}
+
+impl WatchState {
+    pub(crate) fn stats(&self, sae_id: &str) -> Option<LinkStats> {
+        self.links.get(sae_id).map(|link| LinkStats {
+            stored_key_count: link.stored_key_count,
+            rate: link.rate,
+            unreachable: self.failures.get(sae_id) == Some(&Failure::Unreachable),
+            failed: self.failures.get(sae_id) == Some(&Failure::Failed),
+        })
+    }
+
+    /// Records the result of a status request of the link to `sae_id`
+    /// made at `now` and returns the events it emits.
+    pub(crate) fn update(
+        &mut self,
+        sae_id: &str,
+        thresholds: &[u32],
+        result: Result<&PqkdStatus, &PqkdError>,
+        now: Instant,
+    ) -> Vec<WatchEvent> {
+        let mut events = Vec::new();
+        let status = match result {
+            Ok(status) => status,
+            Err(err) => {
+                let failure = if is_unreachable(err) {
+                    Failure::Unreachable
+                } else {
+                    Failure::Failed
+                };
+                if self.failures.insert(sae_id.to_string(), failure) != Some(failure) {
+                    let sae_id = sae_id.to_string();
+                    let error = err.to_string();
+                    events.push(match failure {
+                        Failure::Unreachable => WatchEvent::Unreachable { sae_id, error },
+                        Failure::Failed => WatchEvent::StatusFailed { sae_id, error },
+                    });
+                }
+                return events;
+            }
+        };
+        if self.failures.remove(sae_id).is_some() {
+            events.push(WatchEvent::Reachable {
+                sae_id: sae_id.to_string(),
+            });
+        }
+
+        let count = status.stored_key_count;
+        let previous = self.links.get(sae_id);
+        for &threshold in thresholds {
+            let was_below = previous.map(|link| link.stored_key_count < threshold);
+            match (was_below, count < threshold) {
+                (None | Some(false), true) => events.push(WatchEvent::BelowThreshold {
+                    sae_id: sae_id.to_string(),
+                    threshold,
+                    stored_key_count: count,
+                }),
+                (Some(true), false) => events.push(WatchEvent::Recovered {
+                    sae_id: sae_id.to_string(),
+                    threshold,
+                    stored_key_count: count,
+                }),
+                _ => {}
+            }
+        }
+        let rate = previous.and_then(|link| {
+            let elapsed = now.saturating_duration_since(link.seen_at).as_secs_f64();
+            if elapsed <= 0.0 {
+                return link.rate;
+            }
+            let sample = (count as f64 - link.stored_key_count as f64) / elapsed;
+            Some(match link.rate {
+                Some(rate) => rate + RATE_SMOOTHING * (sample - rate),
+                None => sample,
+            })
+        });
+        self.links.insert(
+            sae_id.to_string(),
+            Link {
+                stored_key_count: count,
+                rate,
+                seen_at: now,
+            },
+        );
+        events
+    }
+}
+
+/// Returns true if `err` shows that the request did not reach the KME or
+/// was not answered, as opposed to an answer that is not a valid status.
+fn is_unreachable(err: &PqkdError) -> bool {
+    match err {
+        PqkdError::RequestError(err) => err.is_connect() || err.is_timeout() || err.is_request(),
+        PqkdError::TransportError(_) | PqkdError::CircuitOpen(_) => true,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn status(stored_key_count: u32) -> PqkdStatus {
+        PqkdStatus {
+            max_key_count: 100000,
+            max_key_per_request: 128,
+            max_key_size: 1024,
+            source_kme_id: "Test_1KME".to_string(),
+            master_sae_id: "Test_1SAE".to_string(),
+            stored_key_count,
+            min_key_size: 64,
+            max_sae_id_count: 0,
+            key_size: 256,
+        }
+    }
+
+    #[test]
+    fn thresholds() {
+        let mut state = WatchState::default();
+        let start = Instant::now();
+        let thresholds = [100, 50];
+
+        let events = state.update("B", &thresholds, Ok(&status(80)), start);
+        assert_eq!(
+            events,
+            vec![WatchEvent::BelowThreshold {
+                sae_id: "B".to_string(),
+                threshold: 100,
+                stored_key_count: 80
+            }]
+        );
+        let events = state.update(
+            "B",
+            &thresholds,
+            Ok(&status(40)),
+            start + Duration::from_secs(1),
+        );
+        assert_eq!(events.len(), 1);
+        assert!(matches!(
+            events[0],
+            WatchEvent::BelowThreshold { threshold: 50, .. }
+        ));
+        let events = state.update(
+            "B",
+            &thresholds,
+            Ok(&status(120)),
+            start + Duration::from_secs(2),
+        );
+        assert_eq!(events.len(), 2);
+        assert!(events
+            .iter()
+            .all(|event| matches!(event, WatchEvent::Recovered { .. })));
+        assert!(state
+            .update(
+                "B",
+                &thresholds,
+                Ok(&status(130)),
+                start + Duration::from_secs(3)
+            )
+            .is_empty());
+    }
+
+    #[test]
+    fn rate() {
+        let mut state = WatchState::default();
+        let start = Instant::now();
+        state.update("B", &[], Ok(&status(1000)), start);
+        assert_eq!(state.stats("B").unwrap().rate, None);
+        state.update("B", &[], Ok(&status(900)), start + Duration::from_secs(10));
+        let stats = state.stats("B").unwrap();
+        assert_eq!(stats.rate, Some(-10.0));
+        assert_eq!(stats.time_to_empty(), Some(Duration::from_secs(90)));
+        state.update("B", &[], Ok(&status(900)), start + Duration::from_secs(20));
+        assert_eq!(state.stats("B").unwrap().rate, Some(-7.0));
+    }
+
+    #[test]
+    fn unreachable() {
+        let mut state = WatchState::default();
+        let start = Instant::now();
+        let err = PqkdError::TransportError("connection refused".to_string());
+
+        let events = state.update("B", &[], Err(&err), start);
+        assert!(matches!(&events[..], [WatchEvent::Unreachable { .. }]));
+        assert!(state.update("B", &[], Err(&err), start).is_empty());
+        assert!(matches!(
+            &state.update("B", &[], Ok(&status(10)), start)[..],
+            [WatchEvent::Reachable { .. }]
+        ));
+        assert!(state.update("B", &[], Ok(&status(10)), start).is_empty());
+        assert!(matches!(
+            &state.update("B", &[], Err(&err), start)[..],
+            [WatchEvent::Unreachable { .. }]
+        ));
+        assert!(state.stats("B").unwrap().unreachable);
+    }
+
+    #[test]
+    fn failed() {
+        let mut state = WatchState::default();
+        let start = Instant::now();
+        let unreachable = PqkdError::TransportError("connection refused".to_string());
+        let failed = PqkdError::HttpStatus(reqwest::StatusCode::INTERNAL_SERVER_ERROR);
+
+        state.update("B", &[], Ok(&status(10)), start);
+        let events = state.update("B", &[], Err(&failed), start);
+        assert!(matches!(&events[..], [WatchEvent::StatusFailed { sae_id, .. }] if sae_id == "B"));
+        let stats = state.stats("B").unwrap();
+        assert!(stats.failed && !stats.unreachable);
+        assert!(state.update("B", &[], Err(&failed), start).is_empty());
+        assert!(state
+            .update("B", &[], Err(&PqkdError::ErrorKmeRequest), start)
+            .is_empty());
+
+        // Switching between the kinds of failures emits an event each time.
+        assert!(matches!(
+            &state.update("B", &[], Err(&unreachable), start)[..],
+            [WatchEvent::Unreachable { .. }]
+        ));
+        let stats = state.stats("B").unwrap();
+        assert!(stats.unreachable && !stats.failed);
+        assert!(matches!(
+            &state.update("B", &[], Err(&failed), start)[..],
+            [WatchEvent::StatusFailed { .. }]
+        ));
+        assert!(matches!(
+            &state.update("B", &[], Ok(&status(10)), start)[..],
+            [WatchEvent::Reachable { .. }]
+        ));
+        assert!(!state.stats("B").unwrap().failed);
+    }
+}
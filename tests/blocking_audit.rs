@@ -0,0 +1,69 @@
+#![cfg(feature = "blocking")]
+use pqkd::audit::{self, AuditLog};
+use pqkd::blocking::{BuilderPqkdClient, PqkdClient};
+use pqkd::error::PqkdError;
+use pqkd::transport::{MemoryTransport, TransportResponse};
+use reqwest::StatusCode;
+use serde_json::json;
+
+const KEY: &str = "wHHVxRwDJs3/bXd38GHP3oe4svTuRpZS0yCC7x4Ly+s=";
+
+fn client(audit: AuditLog) -> PqkdClient {
+    let transport = MemoryTransport::new(|request| {
+        if request.url.path().ends_with("/dec_keys") {
+            return Ok(TransportResponse::new(StatusCode::BAD_REQUEST, Vec::new()));
+        }
+        Ok(TransportResponse::json(
+            StatusCode::OK,
+            &json!({"keys": [{"key_ID": "bc490419-7d60-487f-adc1-4ddcc177c139", "key": KEY}]}),
+        ))
+    });
+    BuilderPqkdClient::with_addr("http://kme.invalid")
+        .unwrap()
+        .with_local_sae_id("Test_1SAE")
+        .with_transport(transport)
+        .with_audit_log(audit)
+        .build()
+}
+
+#[test]
+fn test_audit_log() {
+    let path =
+        std::env::temp_dir().join(format!("pqkd-blocking-audit-{}.jsonl", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+
+    let pqkd_client = client(AuditLog::open(&path).unwrap());
+    pqkd_client.enc_keys("Test_2SAE").size(256).send().unwrap();
+    // A reopened log continues the chain.
+    let pqkd_client = client(AuditLog::open(&path).unwrap());
+    let result = pqkd_client
+        .dec_keys("Test_2SAE")
+        .key_id("7c7f3f8e-4b3a-4bd4-a5e4-0d6a0f4a6f41")
+        .send();
+    assert!(matches!(
+        result,
+        Err(PqkdError::HttpStatus(StatusCode::BAD_REQUEST))
+    ));
+
+    let log = std::fs::read_to_string(&path).unwrap();
+    assert!(!log.contains(KEY));
+    let last = audit::verify_file(&path).unwrap().unwrap();
+    assert_eq!(last.seq, 1);
+    assert_eq!(last.operation, "dec_keys");
+    assert_eq!(last.local_sae_id, "Test_1SAE");
+    assert_eq!(last.peer_sae_id, "Test_2SAE");
+    assert_eq!(last.key_ids, ["7c7f3f8e-4b3a-4bd4-a5e4-0d6a0f4a6f41"]);
+    assert_eq!(last.outcome, "HttpStatus");
+
+    std::fs::write(
+        &path,
+        log.replacen("\"key_size\":256", "\"key_size\":512", 1),
+    )
+    .unwrap();
+    assert!(matches!(
+        audit::verify_file(&path),
+        Err(PqkdError::AuditLogError(_))
+    ));
+    assert!(AuditLog::open(&path).is_err());
+    std::fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,96 @@
+#![cfg(feature = "blocking")]
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use pqkd::blocking::BuilderPqkdClient;
+use pqkd::budget::{BudgetLimit, Quota};
+use pqkd::error::PqkdError;
+use pqkd::transport::{MemoryTransport, TransportResponse};
+use reqwest::StatusCode;
+use serde_json::json;
+
+/// Returns a transport answering key requests with the requested number of
+/// keys, or `503 Service Unavailable` while `exhausted` is set, and a
+/// counter of the requests it received.
+fn transport(exhausted: Arc<AtomicBool>) -> (MemoryTransport, Arc<AtomicUsize>) {
+    let requests = Arc::new(AtomicUsize::new(0));
+    let counter = requests.clone();
+    let transport = MemoryTransport::new(move |request| {
+        counter.fetch_add(1, Ordering::SeqCst);
+        if exhausted.load(Ordering::SeqCst) {
+            return Ok(TransportResponse::new(
+                StatusCode::SERVICE_UNAVAILABLE,
+                Vec::new(),
+            ));
+        }
+        let body: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+        let keys: Vec<_> = (0..body["number"].as_u64().unwrap())
+            .map(|id| json!({"key_ID": id.to_string(), "key": "wHHVxRwDJs3/bXd38GHP3oe4svTuRpZS0yCC7x4Ly+s="}))
+            .collect();
+        Ok(TransportResponse::json(
+            StatusCode::OK,
+            &json!({ "keys": keys }),
+        ))
+    });
+    (transport, requests)
+}
+
+#[test]
+fn test_client_quota() {
+    let (transport, requests) = transport(Arc::default());
+    let pqkd_client = BuilderPqkdClient::with_addr("http://kme.invalid")
+        .unwrap()
+        .with_transport(transport)
+        .with_quota(Quota {
+            keys_per_second: Some(4),
+            ..Quota::default()
+        })
+        .build();
+
+    pqkd_client.enc_keys("Test_2SAE").number(3).send().unwrap();
+    let result = pqkd_client.clone().enc_keys("Test_3SAE").number(3).send();
+    assert!(matches!(
+        result,
+        Err(PqkdError::BudgetExceeded {
+            limit: BudgetLimit::KeysPerSecond,
+            sae_id: None,
+            remaining: 1
+        })
+    ));
+    assert_eq!(requests.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn test_sae_quota() {
+    let exhausted = Arc::new(AtomicBool::new(true));
+    let (transport, requests) = transport(exhausted.clone());
+    let pqkd_client = BuilderPqkdClient::with_addr("http://kme.invalid")
+        .unwrap()
+        .with_transport(transport)
+        .with_sae_quota(
+            "Test_2SAE",
+            Quota {
+                bits_per_hour: Some(1024),
+                ..Quota::default()
+            },
+        )
+        .build();
+
+    // Keys the KME refused to deliver are not counted.
+    let result = pqkd_client.enc_keys("Test_2SAE").number(2).send();
+    assert!(matches!(result, Err(PqkdError::HttpStatus(_))));
+    exhausted.store(false, Ordering::SeqCst);
+    pqkd_client.enc_keys("Test_2SAE").number(2).send().unwrap();
+
+    let result = pqkd_client.enc_keys("Test_2SAE").size(64).send();
+    assert!(matches!(
+        result,
+        Err(PqkdError::BudgetExceeded {
+            limit: BudgetLimit::BitsPerHour,
+            sae_id: Some(sae_id),
+            remaining: 0
+        }) if sae_id == "Test_2SAE"
+    ));
+    pqkd_client.enc_keys("Test_3SAE").number(2).send().unwrap();
+    assert_eq!(requests.load(Ordering::SeqCst), 3);
+}
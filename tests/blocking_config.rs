@@ -0,0 +1,140 @@
+#![cfg(all(feature = "blocking", feature = "config"))]
+use std::path::PathBuf;
+use std::time::Duration;
+
+use httpmock::MockServer;
+use pqkd::error::PqkdError;
+use pqkd::retry::RetryPolicy;
+use pqkd::blocking::BuilderPqkdClient;
+use serde_json::json;
+
+fn write_config(name: &str, contents: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("pqkd-blocking-{}-{}", std::process::id(), name));
+    std::fs::write(&path, contents).unwrap();
+    path
+}
+
+fn status() -> serde_json::Value {
+    json!({
+        "max_key_count": 4096,
+        "max_key_per_request": 64,
+        "max_key_size": 4096,
+        "source_KME_ID": "Test_2KME",
+        "master_SAE_ID": "Test_2SAE",
+        "stored_key_count": 0,
+        "min_key_size": 64,
+        "max_SAE_ID_count": 0,
+        "key_size": 256
+    })
+}
+
+#[test]
+fn test_from_config_profile() {
+    let alice_server = MockServer::start();
+    let bob_server = MockServer::start();
+    let bob_mock = bob_server.mock(|when, then| {
+        when.method("GET")
+            .path("/api/v1/keys/Test_1SAE/status");
+        then.status(200)
+            .header("content-type", "application/json")
+            .json_body(status());
+    });
+    let path = write_config("profiles.toml", &format!(
+        r#"
+        default_profile = "alice"
+
+        [profiles.alice]
+        kme_addr = "http://{}"
+        local_sae_id = "Test_1SAE"
+
+        [profiles.bob]
+        kme_addr = "http://{}"
+        local_sae_id = "Test_2SAE"
+        "#,
+        alice_server.address(),
+        bob_server.address(),
+    ));
+
+    let pqkd_client = BuilderPqkdClient::from_config(&path, Some("bob")).unwrap().build();
+    pqkd_client.status("Test_1SAE").send().unwrap();
+
+    bob_mock.assert();
+    assert_eq!(pqkd_client.local_sae_id(), "Test_2SAE");
+    assert!(matches!(
+        BuilderPqkdClient::from_config(&path, Some("carol")),
+        Err(PqkdError::ConfigError(_))
+    ));
+}
+
+#[test]
+fn test_from_config_json() {
+    let path = write_config("client.json", r#"{"kme_addr": "http://127.0.0.1:8082", "retry": {"max_retries": 4}}"#);
+
+    let pqkd_client = BuilderPqkdClient::from_config(&path, None).unwrap().build();
+
+    assert_eq!(pqkd_client.retry_policy().max_retries, 4);
+}
+
+#[test]
+fn test_retry_unavailable() {
+    let kme_server = MockServer::start();
+    let addr_kme_server: String = format!("http://{}", kme_server.address());
+    let mock = kme_server.mock(|when, then| {
+        when.method("POST")
+            .path("/api/v1/keys/Test_2SAE/enc_keys");
+        then.status(503)
+            .json_body(json!({"message": "no keys available"}));
+    });
+
+    let pqkd_client = BuilderPqkdClient::with_addr(&addr_kme_server)
+        .unwrap()
+        .with_retry_policy(RetryPolicy { max_retries: 2, backoff: Duration::from_millis(1), ..Default::default() })
+        .build();
+
+    assert!(pqkd_client.enc_keys("Test_2SAE").send().is_err());
+    mock.assert_hits(3);
+}
+
+#[test]
+fn test_no_retry_bad_request() {
+    let kme_server = MockServer::start();
+    let addr_kme_server: String = format!("http://{}", kme_server.address());
+    let mock = kme_server.mock(|when, then| {
+        when.method("POST")
+            .path("/api/v1/keys/Test_2SAE/dec_keys");
+        then.status(400)
+            .json_body(json!({"message": "unknown key ID"}));
+    });
+
+    let pqkd_client = BuilderPqkdClient::with_addr(&addr_kme_server)
+        .unwrap()
+        .with_retry_policy(RetryPolicy::new(3))
+        .build();
+
+    assert!(pqkd_client.dec_keys("Test_2SAE").key_id("17d3e519-10e9-43e6-bd7a-72b2da710dcd").send().is_err());
+    mock.assert_hits(1);
+}
+
+#[test]
+fn test_timeout() {
+    let kme_server = MockServer::start();
+    let addr_kme_server: String = format!("http://{}", kme_server.address());
+    kme_server.mock(|when, then| {
+        when.method("GET")
+            .path("/api/v1/keys/Test_2SAE/status");
+        then.status(200)
+            .delay(Duration::from_secs(2))
+            .json_body(status());
+    });
+
+    let pqkd_client = BuilderPqkdClient::with_addr(&addr_kme_server)
+        .unwrap()
+        .with_timeout(Duration::from_millis(100))
+        .unwrap()
+        .build();
+
+    match pqkd_client.status("Test_2SAE").send() {
+        Err(PqkdError::RequestError(err)) => assert!(err.is_timeout()),
+        _ => panic!("expected a timeout"),
+    }
+}
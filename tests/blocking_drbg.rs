@@ -0,0 +1,98 @@
+#![cfg(feature = "blocking")]
+use pqkd::drbg::DrbgConfig;
+use pqkd::blocking::BuilderPqkdClient;
+use httpmock::MockServer;
+
+#[test]
+fn test_drbg_reseed_interval() {
+    let qrng_server = MockServer::start();
+    let addr_qrng_server = format!("http://{}", qrng_server.address());
+
+    let instantiate = qrng_server.mock(|when, then| {
+        when.method("GET")
+            .path("/qrng/bytes")
+            .query_param("size", "48");
+        then.status(200)
+            .body([7u8; 48]);
+    });
+    let reseed = qrng_server.mock(|when, then| {
+        when.method("GET")
+            .path("/qrng/bytes")
+            .query_param("size", "32");
+        then.status(200)
+            .body([9u8; 32]);
+    });
+
+    let pqkd_client = BuilderPqkdClient::with_addr("http://127.0.0.1")
+        .unwrap()
+        .with_qrng_addr(&addr_qrng_server)
+        .unwrap()
+        .build();
+
+    let config = DrbgConfig { reseed_interval: 2, ..Default::default() };
+    let mut drbg = pqkd_client.drbg(config).unwrap();
+
+    let first = drbg.random_bytes(64).unwrap();
+    let second = drbg.random_bytes(64).unwrap();
+    assert_ne!(first, second);
+    reseed.assert_hits(0);
+
+    drbg.random_bytes(64).unwrap();
+    instantiate.assert_hits(1);
+    reseed.assert_hits(1);
+}
+
+#[test]
+fn test_drbg_prediction_resistance() {
+    let qrng_server = MockServer::start();
+    let addr_qrng_server = format!("http://{}", qrng_server.address());
+
+    qrng_server.mock(|when, then| {
+        when.method("GET")
+            .path("/qrng/bytes")
+            .query_param("size", "48");
+        then.status(200)
+            .body([7u8; 48]);
+    });
+    let reseed = qrng_server.mock(|when, then| {
+        when.method("GET")
+            .path("/qrng/bytes")
+            .query_param("size", "32");
+        then.status(200)
+            .body([9u8; 32]);
+    });
+
+    let pqkd_client = BuilderPqkdClient::with_addr("http://127.0.0.1")
+        .unwrap()
+        .with_qrng_addr(&addr_qrng_server)
+        .unwrap()
+        .build();
+
+    let config = DrbgConfig { prediction_resistance: true, ..Default::default() };
+    let mut drbg = pqkd_client.drbg(config).unwrap();
+
+    drbg.random_bytes(16).unwrap();
+    drbg.random_bytes(16).unwrap();
+    reseed.assert_hits(2);
+}
+
+#[test]
+fn test_drbg_short_entropy_from_qrng() {
+    let qrng_server = MockServer::start();
+    let addr_qrng_server = format!("http://{}", qrng_server.address());
+
+    qrng_server.mock(|when, then| {
+        when.method("GET")
+            .path("/qrng/bytes");
+        then.status(200)
+            .body([7u8; 8]);
+    });
+
+    let pqkd_client = BuilderPqkdClient::with_addr("http://127.0.0.1")
+        .unwrap()
+        .with_qrng_addr(&addr_qrng_server)
+        .unwrap()
+        .build();
+
+    assert!(pqkd_client.drbg(DrbgConfig::default()).is_err());
+}
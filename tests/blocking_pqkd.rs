@@ -1,3 +1,5 @@
+#![cfg(feature = "blocking")]
+#![allow(clippy::needless_borrow)]
 use httpmock::MockServer;
 use pqkd::{blocking::BuilderPqkdClient, PqkdStatus};
 use serde_json::json;
@@ -1,4 +1,8 @@
-use pqkd::qrng::{MAX_SIZE_FOR_BYTES_FORMAT, MAX_SIZE_FOR_STRING_FORMAT};
+#![cfg(feature = "blocking")]
+#![allow(clippy::unnecessary_cast)]
+use pqkd::entropy::EntropyMode;
+use pqkd::error::PqkdError;
+use pqkd::qrng::{QrngFormat, MAX_SIZE_FOR_BYTES_FORMAT, MAX_SIZE_FOR_STRING_FORMAT};
 use pqkd::blocking::BuilderPqkdClient;
 use serde_json::json;
 use httpmock::MockServer;
@@ -87,7 +91,7 @@ fn test_get_random_base64_for_the_large_size() {
 fn test_get_random_bytes() {
     let qrng_server = MockServer::start();
     let addr_qrng_server = format!("http://{}", qrng_server.address());
-    let binary = b"\x28\x214\x252\x207\x37\x218\x43\x144\x62\x183\x81\x251\x22\x163\x201\x90\x172\x215\x80\x51";
+    let binary = [28u8, 214, 252, 207, 37, 218, 43, 144, 62, 183, 81, 251, 22, 163, 201, 90, 172, 215, 80, 51];
     let size = 20usize;
 
     qrng_server.mock(|when, then| {
@@ -120,4 +124,117 @@ fn test_get_random_bytes_for_the_large_size() {
     let result = pqkd_client.get_random_bytes(size as u32);
     
     assert!(result.is_err());
-}
\ No newline at end of file
+}
+#[test]
+fn test_get_random_bytes_mixed_with_os_entropy() {
+    let qrng_server = MockServer::start();
+    let addr_qrng_server = format!("http://{}", qrng_server.address());
+    let binary = [0u8; 64];
+    let size = binary.len();
+
+    qrng_server.mock(|when, then| {
+        when.method("GET")
+            .path("/qrng/bytes")
+            .query_param("size", size.to_string());
+        then.status(200)
+            .body(binary);
+    });
+
+    let pqkd_client = BuilderPqkdClient::with_addr("http://127.0.0.1")
+        .unwrap()
+        .with_qrng_addr(&addr_qrng_server)
+        .unwrap()
+        .with_entropy_mode(EntropyMode::Mixed)
+        .build();
+
+    let first = pqkd_client.get_random_bytes(size as u32).unwrap();
+    let second = pqkd_client.get_random_bytes(size as u32).unwrap();
+
+    assert_eq!(first.len(), size);
+    assert_ne!(first, binary.to_vec());
+    assert_ne!(first, second);
+}
+
+#[test]
+fn test_get_random_with_metadata() {
+    let qrng_server = MockServer::start();
+    let addr_qrng_server = format!("http://{}", qrng_server.address());
+    let random_base64 = "w7jYDPNv789HHJTJg7iwkg4AYI0=";
+    let size = 20usize;
+
+    qrng_server.mock(|when, then| {
+        when.method("GET")
+            .path("/qrng/base64")
+            .query_param("size", size.to_string());
+        then.status(200)
+            .header("content-type", "application/json")
+            .json_body(json!({"result": random_base64, "size": size.to_string(), "format": "base64", "executeTime": 335}));
+    });
+
+    let pqkd_client = BuilderPqkdClient::with_addr("http://127.0.0.1")
+        .unwrap()
+        .with_qrng_addr(&addr_qrng_server)
+        .unwrap()
+        .build();
+
+    let result = pqkd_client.get_random(QrngFormat::Base64, size as u32).unwrap();
+
+    assert_eq!(result.size(), size as u32);
+    assert_eq!(result.format(), QrngFormat::Base64);
+    assert_eq!(result.execute_time(), Some(335));
+    assert_eq!(result.into_result().as_base64().unwrap(), random_base64);
+}
+
+#[test]
+fn test_get_random_hex_with_wrong_size() {
+    let qrng_server = MockServer::start();
+    let addr_qrng_server = format!("http://{}", qrng_server.address());
+    let size = 20usize;
+
+    qrng_server.mock(|when, then| {
+        when.method("GET")
+            .path("/qrng/hex")
+            .query_param("size", size.to_string());
+        then.status(200)
+            .header("content-type", "application/json")
+            .json_body(json!({"result": "5ede8635", "size": size.to_string(), "format": "hex", "executeTime": 335}));
+    });
+
+    let pqkd_client = BuilderPqkdClient::with_addr("http://127.0.0.1")
+        .unwrap()
+        .with_qrng_addr(&addr_qrng_server)
+        .unwrap()
+        .build();
+
+    let result = pqkd_client.get_random_hex(size as u32);
+
+    assert!(matches!(result, Err(PqkdError::QrngSizeMismatch { requested: 20, found: 4 })));
+}
+
+#[test]
+fn test_get_random_base64_bytes() {
+    let qrng_server = MockServer::start();
+    let addr_qrng_server = format!("http://{}", qrng_server.address());
+    let random_base64 = "w7jYDPNv789HHJTJg7iwkg4AYI0=";
+    let size = 20usize;
+
+    qrng_server.mock(|when, then| {
+        when.method("GET")
+            .path("/qrng/base64")
+            .query_param("size", size.to_string());
+        then.status(200)
+            .header("content-type", "application/json")
+            .json_body(json!({"result": random_base64, "size": size.to_string(), "format": "base64", "executeTime": 335}));
+    });
+
+    let pqkd_client = BuilderPqkdClient::with_addr("http://127.0.0.1")
+        .unwrap()
+        .with_qrng_addr(&addr_qrng_server)
+        .unwrap()
+        .build();
+
+    let result = pqkd_client.get_random_base64_bytes(size as u32).unwrap();
+
+    assert_eq!(result.len(), size);
+    assert_eq!(&result[..3], &[0xc3, 0xb8, 0xd8]);
+}
@@ -0,0 +1,89 @@
+#![cfg(feature = "blocking")]
+use pqkd::blocking::BuilderPqkdClient;
+use httpmock::MockServer;
+
+fn bytes(len: usize) -> Vec<u8> {
+    (0..len).map(|i| (i * 151 + 7) as u8).collect()
+}
+
+fn mock_qrng_bytes(qrng_server: &MockServer) {
+    for size in 1..=64usize {
+        qrng_server.mock(|when, then| {
+            when.method("GET")
+                .path("/qrng/bytes")
+                .query_param("size", size.to_string());
+            then.status(200)
+                .body(bytes(size));
+        });
+    }
+}
+
+#[test]
+fn test_get_random_range() {
+    let qrng_server = MockServer::start();
+    let addr_qrng_server = format!("http://{}", qrng_server.address());
+
+    mock_qrng_bytes(&qrng_server);
+
+    let pqkd_client = BuilderPqkdClient::with_addr("http://127.0.0.1")
+        .unwrap()
+        .with_qrng_addr(&addr_qrng_server)
+        .unwrap()
+        .build();
+
+    let value = pqkd_client.get_random_range(10..20).unwrap();
+    assert!((10..20).contains(&value));
+    assert!(pqkd_client.get_random_range(5..5).is_err());
+
+    let value = pqkd_client.get_random_f64().unwrap();
+    assert!((0.0..1.0).contains(&value));
+}
+
+#[test]
+fn test_get_random_range_fetches_only_needed_bytes() {
+    let qrng_server = MockServer::start();
+    let addr_qrng_server = format!("http://{}", qrng_server.address());
+
+    // A 16 character alphabet takes exactly 4 bits per character and never
+    // rejects, so 100 characters need 50 bytes, fetched in a single request.
+    let mock = qrng_server.mock(|when, then| {
+        when.method("GET")
+            .path("/qrng/bytes")
+            .query_param("size", "56");
+        then.status(200)
+            .body(bytes(56));
+    });
+
+    let pqkd_client = BuilderPqkdClient::with_addr("http://127.0.0.1")
+        .unwrap()
+        .with_qrng_addr(&addr_qrng_server)
+        .unwrap()
+        .build();
+
+    let password = pqkd_client.get_random_password(100, "0123456789abcdef").unwrap();
+    assert_eq!(password.len(), 100);
+    assert!(password.chars().all(|c| c.is_ascii_hexdigit()));
+    mock.assert();
+}
+
+#[test]
+fn test_get_random_uuid_and_permutation() {
+    let qrng_server = MockServer::start();
+    let addr_qrng_server = format!("http://{}", qrng_server.address());
+
+    mock_qrng_bytes(&qrng_server);
+
+    let pqkd_client = BuilderPqkdClient::with_addr("http://127.0.0.1")
+        .unwrap()
+        .with_qrng_addr(&addr_qrng_server)
+        .unwrap()
+        .build();
+
+    let uuid = pqkd_client.get_random_uuid().unwrap();
+    assert_eq!(uuid.len(), 36);
+    assert_eq!(&uuid[14..15], "4");
+
+    let mut permutation = pqkd_client.get_random_permutation(20).unwrap();
+    permutation.sort();
+    assert_eq!(permutation, (0..20).collect::<Vec<usize>>());
+}
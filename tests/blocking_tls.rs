@@ -0,0 +1,264 @@
+#![cfg(feature = "blocking")]
+use std::fs::File;
+use std::io::{BufReader, Read, Write};
+use std::net::TcpListener;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use pqkd::error::PqkdError;
+use pqkd::tls::TlsFiles;
+use pqkd::blocking::BuilderPqkdClient;
+
+fn cert(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/certs").join(name)
+}
+
+/// Starts a mock KME with a certificate for "pqkd.test", which requires
+/// clients to authenticate with a certificate issued by the test CA and
+/// answers every request with a status naming the client certificate.
+fn start_tls_server() -> String {
+    start_kme_server("server")
+}
+
+/// Like [start_tls_server], with the certificate `<name>.pem`.
+/// Key requests are answered with a single key.
+fn start_kme_server(name: &str) -> String {
+    let certs = rustls_pemfile::certs(&mut BufReader::new(File::open(cert(&format!("{}.pem", name))).unwrap()))
+        .unwrap()
+        .into_iter()
+        .map(rustls::Certificate)
+        .collect();
+    let key = rustls_pemfile::pkcs8_private_keys(&mut BufReader::new(File::open(cert(&format!("{}.key", name))).unwrap()))
+        .unwrap()
+        .remove(0);
+    let mut roots = rustls::RootCertStore::empty();
+    for ca_cert in rustls_pemfile::certs(&mut BufReader::new(File::open(cert("ca.pem")).unwrap())).unwrap() {
+        roots.add(&rustls::Certificate(ca_cert)).unwrap();
+    }
+    let config = rustls::ServerConfig::builder()
+        .with_safe_defaults()
+        .with_client_cert_verifier(rustls::server::AllowAnyAuthenticatedClient::new(roots).boxed())
+        .with_single_cert(certs, rustls::PrivateKey(key))
+        .unwrap();
+    let config = Arc::new(config);
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = format!("https://{}", listener.local_addr().unwrap());
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let connection = rustls::ServerConnection::new(config.clone()).unwrap();
+            let mut stream = rustls::StreamOwned::new(connection, stream.unwrap());
+            let mut request = Vec::new();
+            let mut buf = [0u8; 1024];
+            while !request_complete(&request) {
+                match stream.read(&mut buf) {
+                    Ok(0) | Err(_) => break,
+                    Ok(n) => request.extend_from_slice(&buf[..n]),
+                }
+            }
+            let Some(client_cert) = stream.conn.peer_certificates().and_then(|certs| certs.first().cloned()) else {
+                continue;
+            };
+            let master_sae_id = if contains(&client_cert.0, b"Test_1SAE") { "Test_1SAE" } else { "Test_2SAE" };
+            let body = if request.starts_with(b"POST") {
+                r#"{"keys": [{"key_ID": "bc490419-7d60-487f-adc1-4ddcc177c139", "key": "wHHVxRwDJs3/bXd38GHP3oe4svTuRpZS0yCC7x4Ly+s="}]}"#.to_string()
+            } else {
+                format!(
+                    r#"{{"source_KME_ID": "Test_1KME", "master_SAE_ID": "{}", "key_size": 256, "stored_key_count": 25000, "max_key_count": 100000, "max_key_per_request": 128, "max_key_size": 1024, "min_key_size": 64, "max_SAE_ID_count": 0}}"#,
+                    master_sae_id
+                )
+            };
+            let _ = write!(
+                stream,
+                "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            stream.conn.send_close_notify();
+            let _ = stream.flush();
+        }
+    });
+    addr
+}
+
+/// Returns true if `request` holds the headers and the whole body.
+fn request_complete(request: &[u8]) -> bool {
+    let Some(end) = request.windows(4).position(|window| window == b"\r\n\r\n") else {
+        return false;
+    };
+    let headers = String::from_utf8_lossy(&request[..end]).to_lowercase();
+    let content_length = headers
+        .lines()
+        .find_map(|line| line.strip_prefix("content-length:"))
+        .map_or(0, |len| len.trim().parse().unwrap());
+    request.len() >= end + 4 + content_length
+}
+
+fn fingerprint(name: &str) -> String {
+    use sha2::{Digest, Sha256};
+    let der = rustls_pemfile::certs(&mut BufReader::new(File::open(cert(name)).unwrap()))
+        .unwrap()
+        .remove(0);
+    hex::encode(Sha256::digest(der))
+}
+
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    haystack.windows(needle.len()).any(|window| window == needle)
+}
+
+#[test]
+fn test_tls_server_name() {
+    let addr = start_tls_server();
+
+    let pqkd_client = BuilderPqkdClient::with_addr(&addr)
+        .unwrap()
+        .with_tls_files(&TlsFiles::pem(cert("ca.pem"), cert("client-rsa.pem"), cert("client-rsa.key")))
+        .unwrap()
+        .with_server_name("pqkd.test")
+        .unwrap()
+        .build();
+    let status = pqkd_client.status("Test_2SAE").send().unwrap().as_status().unwrap();
+    assert_eq!(status.source_kme_id, "Test_1KME");
+
+    // The certificate of the server is not valid for its IP address.
+    let pqkd_client = BuilderPqkdClient::with_addr(&addr)
+        .unwrap()
+        .with_tls_files(&TlsFiles::pem(cert("ca.pem"), cert("client-rsa.pem"), cert("client-rsa.key")))
+        .unwrap()
+        .build();
+    assert!(pqkd_client.status("Test_2SAE").send().is_err());
+}
+
+#[test]
+fn test_tls_client_identities() {
+    let addr = start_tls_server();
+    let ca_cert = std::fs::read(cert("ca.pem")).unwrap();
+
+    let identities = [
+        ("client-rsa.pem", "client-rsa.key", "Test_1SAE"),
+        ("client-rsa.pem", "client-rsa-pkcs8.key", "Test_1SAE"),
+        ("client-ec.pem", "client-ec.key", "Test_2SAE"),
+    ];
+    for (client_cert, client_key, sae_id) in identities {
+        let pqkd_client = BuilderPqkdClient::with_addr(&addr)
+            .unwrap()
+            .with_tls(
+                &ca_cert,
+                &std::fs::read(cert(client_cert)).unwrap(),
+                &std::fs::read(cert(client_key)).unwrap(),
+            )
+            .unwrap()
+            .with_server_name("pqkd.test")
+            .unwrap()
+            .build();
+        let status = pqkd_client.status("Test_2SAE").send().unwrap().as_status().unwrap();
+        assert_eq!(status.master_sae_id, sae_id);
+    }
+
+    let pqkd_client = BuilderPqkdClient::with_addr(&addr)
+        .unwrap()
+        .with_tls_reload(
+            TlsFiles::pem(cert("ca.pem"), cert("client-ec.pem"), cert("client-ec.key")),
+            std::time::Duration::ZERO,
+        )
+        .unwrap()
+        .with_server_name("pqkd.test")
+        .unwrap()
+        .build();
+    assert!(pqkd_client.status("Test_2SAE").send().is_ok());
+}
+
+#[cfg(not(feature = "rustls-tls"))]
+#[test]
+fn test_tls_pkcs12() {
+    let addr = start_tls_server();
+    let ca_cert = std::fs::read(cert("ca.pem")).unwrap();
+    let pkcs12 = std::fs::read(cert("client.p12")).unwrap();
+
+    let pqkd_client = BuilderPqkdClient::with_addr(&addr)
+        .unwrap()
+        .with_pkcs12(&ca_cert, &pkcs12, "pqkd")
+        .unwrap()
+        .with_server_name("pqkd.test")
+        .unwrap()
+        .build();
+    let status = pqkd_client.status("Test_2SAE").send().unwrap().as_status().unwrap();
+    assert_eq!(status.master_sae_id, "Test_1SAE");
+
+    assert!(BuilderPqkdClient::with_addr(&addr)
+        .unwrap()
+        .with_pkcs12(&ca_cert, &pkcs12, "wrong")
+        .is_err());
+}
+
+#[cfg(feature = "rustls-tls")]
+#[test]
+fn test_tls_pkcs12_unsupported() {
+    let ca_cert = std::fs::read(cert("ca.pem")).unwrap();
+    let pkcs12 = std::fs::read(cert("client.p12")).unwrap();
+
+    assert!(BuilderPqkdClient::with_addr("https://127.0.0.1:8082")
+        .unwrap()
+        .with_pkcs12(&ca_cert, &pkcs12, "pqkd")
+        .is_err());
+}
+
+#[test]
+fn test_tls_kme_id() {
+    let kme_addr = start_kme_server("kme");
+    let server_addr = start_tls_server();
+    let files = TlsFiles::pem(cert("ca.pem"), cert("client-rsa.pem"), cert("client-rsa.key"));
+    let client = |addr: &str, kme_id: &str| {
+        BuilderPqkdClient::with_addr(addr)
+            .unwrap()
+            .with_tls_files(&files)
+            .unwrap()
+            .with_server_name("pqkd.test")
+            .unwrap()
+            .with_kme_id(kme_id)
+            .unwrap()
+            .build()
+    };
+
+    let pqkd_client = client(&kme_addr, "Test_1KME");
+    assert!(pqkd_client.status("Test_2SAE").send().is_ok());
+    assert_eq!(pqkd_client.enc_keys("Test_2SAE").send().unwrap().keys().len(), 1);
+
+    let pqkd_client = client(&kme_addr, "Test_2KME");
+    let err = pqkd_client.status("Test_2SAE").send().unwrap_err();
+    assert!(matches!(err, PqkdError::KmeIdentityMismatch(_)));
+    let err = pqkd_client.enc_keys("Test_2SAE").send().unwrap_err();
+    assert!(matches!(err, PqkdError::KmeIdentityMismatch(_)));
+
+    // The status names the pinned KME, but its certificate does not.
+    let pqkd_client = client(&server_addr, "Test_1KME");
+    let err = pqkd_client.status("Test_2SAE").send().unwrap_err();
+    assert!(matches!(err, PqkdError::KmeIdentityMismatch(_)));
+    let err = pqkd_client.dec_keys("Test_2SAE").key_id("bc490419-7d60-487f-adc1-4ddcc177c139").send().unwrap_err();
+    assert!(matches!(err, PqkdError::KmeIdentityMismatch(_)));
+}
+
+#[test]
+fn test_tls_kme_fingerprint() {
+    let addr = start_kme_server("kme");
+    let files = TlsFiles::pem(cert("ca.pem"), cert("client-ec.pem"), cert("client-ec.key"));
+    let client = |fingerprint: &str| {
+        BuilderPqkdClient::with_addr(&addr)
+            .unwrap()
+            .with_kme_fingerprint(fingerprint)
+            .unwrap()
+            .with_tls_files(&files)
+            .unwrap()
+            .with_server_name("pqkd.test")
+            .unwrap()
+            .build()
+    };
+
+    let pqkd_client = client(&fingerprint("kme.pem"));
+    assert!(pqkd_client.status("Test_2SAE").send().is_ok());
+    assert!(pqkd_client.enc_keys("Test_2SAE").send().is_ok());
+
+    let pqkd_client = client(&fingerprint("server.pem"));
+    let err = pqkd_client.enc_keys("Test_2SAE").send().unwrap_err();
+    assert!(matches!(err, PqkdError::KmeIdentityMismatch(_)));
+}
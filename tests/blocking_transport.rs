@@ -0,0 +1,90 @@
+#![cfg(feature = "blocking")]
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use pqkd::error::PqkdError;
+use pqkd::retry::RetryPolicy;
+use pqkd::transport::{MemoryTransport, TransportResponse};
+use pqkd::blocking::BuilderPqkdClient;
+use reqwest::{Method, StatusCode};
+use serde_json::json;
+
+fn status() -> serde_json::Value {
+    json!({
+        "source_KME_ID": "Test_1KME",
+        "target_KME_ID": "Test_2KME",
+        "master_SAE_ID": "Test_1SAE",
+        "slave_SAE_ID": "Test_2SAE",
+        "key_size": 256,
+        "stored_key_count": 25000,
+        "max_key_count": 100000,
+        "max_key_per_request": 128,
+        "max_key_size": 1024,
+        "min_key_size": 64,
+        "max_SAE_ID_count": 0
+    })
+}
+
+#[test]
+fn test_transport_requests() {
+    let transport = MemoryTransport::new(|request| {
+        match (request.method.clone(), request.url.path()) {
+            (Method::GET, "/api/v1/keys/Test_2SAE/status") => {
+                Ok(TransportResponse::json(StatusCode::OK, &status()))
+            }
+            (Method::POST, "/api/v1/keys/Test_1SAE/dec_keys") => {
+                assert_eq!(request.headers["content-type"], "application/json");
+                let body: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+                assert_eq!(body, json!({"key_IDs": [{"key_ID": "bc490419-7d60-487f-adc1-4ddcc177c139"}]}));
+                Ok(TransportResponse::json(
+                    StatusCode::OK,
+                    &json!({"keys": [{"key_ID": "bc490419-7d60-487f-adc1-4ddcc177c139", "key": "wHHVxRwDJs3/bXd38GHP3oe4svTuRpZS0yCC7x4Ly+s="}]}),
+                ))
+            }
+            _ => Ok(TransportResponse::new(StatusCode::NOT_FOUND, Vec::new())),
+        }
+    });
+    let pqkd_client = BuilderPqkdClient::with_addr("http://kme.invalid")
+        .unwrap()
+        .with_transport(transport)
+        .build();
+
+    let status = pqkd_client.status("Test_2SAE").send().unwrap().as_status().unwrap();
+    assert_eq!(status.source_kme_id, "Test_1KME");
+    let keys = pqkd_client
+        .dec_keys("Test_1SAE")
+        .key_id("bc490419-7d60-487f-adc1-4ddcc177c139")
+        .send()
+        .unwrap()
+        .keys();
+    assert_eq!(keys[0].key_id(), "bc490419-7d60-487f-adc1-4ddcc177c139");
+    assert!(matches!(
+        pqkd_client.status("Test_3SAE").send(),
+        Err(PqkdError::HttpStatus(StatusCode::NOT_FOUND))
+    ));
+}
+
+#[test]
+fn test_transport_retry() {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let counter = calls.clone();
+    let transport = MemoryTransport::new(move |_| {
+        if counter.fetch_add(1, Ordering::SeqCst) < 2 {
+            Ok(TransportResponse::new(StatusCode::SERVICE_UNAVAILABLE, Vec::new()))
+        } else {
+            Ok(TransportResponse::json(StatusCode::OK, &status()))
+        }
+    });
+    let pqkd_client = BuilderPqkdClient::with_addr("http://kme.invalid")
+        .unwrap()
+        .with_transport(transport)
+        .with_retry_policy(RetryPolicy {
+            max_retries: 2,
+            backoff: std::time::Duration::from_millis(1),
+            ..RetryPolicy::default()
+        })
+        .build();
+
+    assert!(pqkd_client.status("Test_2SAE").send().is_ok());
+    assert_eq!(calls.load(Ordering::SeqCst), 3);
+}
@@ -0,0 +1,141 @@
+#![cfg(feature = "blocking")]
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::Duration;
+
+use pqkd::blocking::{BuilderPqkdClient, PqkdClient, StatusWatcher};
+use pqkd::error::PqkdError;
+use pqkd::transport::{MemoryTransport, TransportResponse};
+use pqkd::watcher::{WatchEvent, WatcherConfig};
+use reqwest::StatusCode;
+use serde_json::json;
+
+/// Returns a client of a KME with `stored` keys, which does not answer
+/// while `down` is set.
+fn client(stored: Arc<AtomicU32>, down: Arc<AtomicBool>) -> PqkdClient {
+    let transport = MemoryTransport::new(move |_| {
+        if down.load(Ordering::SeqCst) {
+            return Err(PqkdError::TransportError("connection refused".to_string()));
+        }
+        Ok(TransportResponse::json(
+            StatusCode::OK,
+            &json!({
+                "source_KME_ID": "Test_1KME",
+                "master_SAE_ID": "Test_1SAE",
+                "key_size": 256,
+                "stored_key_count": stored.load(Ordering::SeqCst),
+                "max_key_count": 100000,
+                "max_key_per_request": 128,
+                "max_key_size": 1024,
+                "min_key_size": 64,
+                "max_SAE_ID_count": 0
+            }),
+        ))
+    });
+    BuilderPqkdClient::with_addr("http://kme.invalid")
+        .unwrap()
+        .with_transport(transport)
+        .build()
+}
+
+fn config() -> WatcherConfig {
+    WatcherConfig {
+        sae_ids: vec!["Test_2SAE".to_string()],
+        interval: Duration::from_millis(10),
+        thresholds: vec![1000],
+    }
+}
+
+#[test]
+fn test_watcher_events() {
+    let stored = Arc::new(AtomicU32::new(2000));
+    let down = Arc::new(AtomicBool::new(false));
+    let received = Arc::new(Mutex::new(Vec::new()));
+    let sink = received.clone();
+    let watcher = StatusWatcher::new(client(stored.clone(), down.clone()), config())
+        .on_event(move |event| sink.lock().unwrap().push(event.clone()));
+
+    assert!(watcher.poll().is_empty());
+    stored.store(500, Ordering::SeqCst);
+    watcher.poll();
+    down.store(true, Ordering::SeqCst);
+    watcher.poll();
+    assert!(watcher.stats("Test_2SAE").unwrap().unreachable);
+    down.store(false, Ordering::SeqCst);
+    stored.store(1500, Ordering::SeqCst);
+    watcher.poll();
+
+    let received = received.lock().unwrap();
+    assert_eq!(
+        received[0],
+        WatchEvent::BelowThreshold {
+            sae_id: "Test_2SAE".to_string(),
+            threshold: 1000,
+            stored_key_count: 500
+        }
+    );
+    assert!(
+        matches!(&received[1], WatchEvent::Unreachable { sae_id, .. } if sae_id == "Test_2SAE")
+    );
+    assert!(matches!(received[2], WatchEvent::Reachable { .. }));
+    assert!(matches!(
+        received[3],
+        WatchEvent::Recovered {
+            stored_key_count: 1500,
+            ..
+        }
+    ));
+    assert_eq!(received.len(), 4);
+}
+
+#[test]
+fn test_watcher_run() {
+    let stored = Arc::new(AtomicU32::new(2000));
+    let (sender, events) = mpsc::channel();
+    let sender = Mutex::new(sender);
+    let watcher = StatusWatcher::new(client(stored.clone(), Arc::default()), config()).on_event(
+        move |event| {
+            let _ = sender.lock().unwrap().send(event.clone());
+        },
+    );
+    let running = watcher.clone();
+    let (stopped, done) = mpsc::channel();
+    std::thread::spawn(move || {
+        running.run();
+        let _ = stopped.send(());
+    });
+
+    std::thread::sleep(Duration::from_millis(30));
+    stored.store(10, Ordering::SeqCst);
+    let event = events.recv_timeout(Duration::from_secs(1)).unwrap();
+    assert!(matches!(
+        event,
+        WatchEvent::BelowThreshold {
+            stored_key_count: 10,
+            ..
+        }
+    ));
+
+    watcher.stop();
+    done.recv_timeout(Duration::from_secs(1)).unwrap();
+    // A stopped watcher does not poll anymore.
+    watcher.clone().run();
+}
+
+#[test]
+fn test_watcher_stop_while_sleeping() {
+    let config = WatcherConfig {
+        interval: Duration::from_secs(3600),
+        ..config()
+    };
+    let watcher = StatusWatcher::new(client(Arc::default(), Arc::default()), config);
+    let running = watcher.clone();
+    let (stopped, done) = mpsc::channel();
+    std::thread::spawn(move || {
+        running.run();
+        let _ = stopped.send(());
+    });
+    std::thread::sleep(Duration::from_millis(30));
+    watcher.stop();
+    done.recv_timeout(Duration::from_secs(1)).unwrap();
+}
@@ -0,0 +1,97 @@
+#![cfg(feature = "async")]
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use pqkd::breaker::{BreakerPolicy, BreakerState, Endpoint};
+use pqkd::budget::Quota;
+use pqkd::error::PqkdError;
+use pqkd::transport::{MemoryTransport, TransportResponse};
+use pqkd::BuilderPqkdClient;
+use reqwest::StatusCode;
+use serde_json::json;
+
+#[tokio::test]
+async fn test_circuit_breaker() {
+    let kme_down = Arc::new(AtomicBool::new(true));
+    let kme_requests = Arc::new(AtomicUsize::new(0));
+    let (down, counter) = (kme_down.clone(), kme_requests.clone());
+    let transport = MemoryTransport::new(move |request| {
+        if request.url.path().starts_with("/qrng/") {
+            return Ok(TransportResponse::new(StatusCode::OK, vec![0; 8]));
+        }
+        counter.fetch_add(1, Ordering::SeqCst);
+        if down.load(Ordering::SeqCst) {
+            return Err(PqkdError::TransportError("connection refused".to_string()));
+        }
+        Ok(TransportResponse::json(
+            StatusCode::OK,
+            &json!({"keys": [{"key_ID": "bc490419-7d60-487f-adc1-4ddcc177c139", "key": "wHHVxRwDJs3/bXd38GHP3oe4svTuRpZS0yCC7x4Ly+s="}]}),
+        ))
+    });
+    let pqkd_client = BuilderPqkdClient::with_addr("http://kme.invalid")
+        .unwrap()
+        .with_transport(transport)
+        .with_circuit_breaker(BreakerPolicy {
+            failure_threshold: 2,
+            reset_timeout: Duration::from_millis(50),
+        })
+        .build();
+
+    for _ in 0..2 {
+        let result = pqkd_client.enc_keys("Test_2SAE").send().await;
+        assert!(matches!(result, Err(PqkdError::TransportError(_))));
+    }
+    assert_eq!(pqkd_client.circuit_state(Endpoint::Kme), BreakerState::Open);
+    let result = pqkd_client.enc_keys("Test_2SAE").send().await;
+    assert!(matches!(result, Err(PqkdError::CircuitOpen(Endpoint::Kme))));
+    assert_eq!(kme_requests.load(Ordering::SeqCst), 2);
+
+    // The QRNG has a circuit of its own.
+    assert_eq!(
+        pqkd_client.circuit_state(Endpoint::Qrng),
+        BreakerState::Closed
+    );
+    assert!(pqkd_client.get_random_bytes(8).await.is_ok());
+
+    tokio::time::sleep(Duration::from_millis(60)).await;
+    assert_eq!(
+        pqkd_client.circuit_state(Endpoint::Kme),
+        BreakerState::HalfOpen
+    );
+    kme_down.store(false, Ordering::SeqCst);
+    assert!(pqkd_client.enc_keys("Test_2SAE").send().await.is_ok());
+    assert_eq!(
+        pqkd_client.circuit_state(Endpoint::Kme),
+        BreakerState::Closed
+    );
+}
+
+#[tokio::test]
+async fn test_open_circuit_keeps_quota() {
+    let transport = MemoryTransport::new(|_| {
+        Err(PqkdError::TransportError("connection refused".to_string()))
+    });
+    let pqkd_client = BuilderPqkdClient::with_addr("http://kme.invalid")
+        .unwrap()
+        .with_transport(transport)
+        .with_circuit_breaker(BreakerPolicy {
+            failure_threshold: 2,
+            reset_timeout: Duration::from_secs(60),
+        })
+        .with_quota(Quota {
+            keys_per_second: Some(3),
+            ..Quota::default()
+        })
+        .build();
+
+    for _ in 0..2 {
+        let result = pqkd_client.enc_keys("Test_2SAE").send().await;
+        assert!(matches!(result, Err(PqkdError::TransportError(_))));
+    }
+    // Requests rejected by the open circuit give their keys back.
+    for _ in 0..5 {
+        let result = pqkd_client.enc_keys("Test_2SAE").send().await;
+        assert!(matches!(result, Err(PqkdError::CircuitOpen(Endpoint::Kme))));
+    }
+}
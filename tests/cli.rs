@@ -0,0 +1,114 @@
+#![cfg(feature = "cli")]
+use std::process::Command;
+
+use serde_json::json;
+use httpmock::MockServer;
+
+fn pqkd() -> Command {
+    Command::new(env!("CARGO_BIN_EXE_pqkd"))
+}
+
+#[test]
+fn test_cli_status_json() {
+    let kme_server = MockServer::start();
+    let addr_kme_server: String = format!("http://{}", kme_server.address());
+    let response = json!(
+        {
+            "max_key_count": 4096,
+            "max_key_per_request": 64,
+            "max_key_size": 4096,
+            "source_KME_ID": "Test_2KME",
+            "master_SAE_ID": "Test_2SAE",
+            "stored_key_count": 0,
+            "min_key_size": 64,
+            "max_SAE_ID_count": 0,
+            "key_size": 256
+        }
+    );
+
+    kme_server.mock(|when, then| {
+        when.method("GET")
+            .path("/api/v1/keys/Test_2SAE/status");
+        then.status(200)
+            .header("content-type", "application/json")
+            .json_body(response.clone());
+    });
+
+    let output = pqkd()
+        .args(["--kme-addr", &addr_kme_server, "--output", "json", "status", "Test_2SAE"])
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    let status: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(status["source_kme_id"], "Test_2KME");
+    assert_eq!(status["key_size"], 256);
+}
+
+#[test]
+fn test_cli_enc_keys_table() {
+    let kme_server = MockServer::start();
+    let addr_kme_server: String = format!("http://{}", kme_server.address());
+    let response_key_id = "17d3e519-10e9-43e6-bd7a-72b2da710dcd";
+    let response_key = "lRXjNYtHITV4KXkdIJZN/Pv0ojAkuLGwzwumMev959w=";
+
+    kme_server.mock(|when, then| {
+        when.method("POST")
+            .path("/api/v1/keys/Test_2SAE/enc_keys")
+            .json_body(json!({"size": 256, "number": 1}));
+        then.status(200)
+            .header("content-type", "application/json")
+            .json_body(json!({"keys": [{"key_ID": response_key_id, "key": response_key}]}));
+    });
+
+    let output = pqkd()
+        .args(["--kme-addr", &addr_kme_server, "enc-keys", "Test_2SAE", "--size", "256"])
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(lines[0], format!("{:<36}  KEY", "KEY ID"));
+    assert_eq!(lines[1], format!("{}  {}", response_key_id, response_key));
+}
+
+#[test]
+fn test_cli_random_to_file() {
+    let qrng_server = MockServer::start();
+    let addr_qrng_server = format!("http://{}", qrng_server.address());
+    let binary = [28u8, 214, 252, 207, 37, 218, 43, 144];
+
+    qrng_server.mock(|when, then| {
+        when.method("GET")
+            .path("/qrng/bytes")
+            .query_param("size", "8");
+        then.status(200)
+            .body(binary);
+    });
+
+    let dir = std::env::temp_dir().join(format!("pqkd-cli-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let file = dir.join("random.bin");
+
+    let output = pqkd()
+        .args(["--qrng-addr", &addr_qrng_server, "random", "--format", "bytes", "--size", "8", "--file"])
+        .arg(&file)
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(std::fs::read(&file).unwrap(), binary);
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_cli_failed_request() {
+    let output = pqkd()
+        .args(["--kme-addr", "http://127.0.0.1:1", "status", "Test_2SAE"])
+        .output()
+        .unwrap();
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8(output.stderr).unwrap().starts_with("error:"));
+}
@@ -0,0 +1,98 @@
+#![cfg(feature = "async")]
+use pqkd::drbg::DrbgConfig;
+use pqkd::BuilderPqkdClient;
+use httpmock::MockServer;
+
+#[tokio::test]
+async fn test_drbg_reseed_interval() {
+    let qrng_server = MockServer::start_async().await;
+    let addr_qrng_server = format!("http://{}", qrng_server.address());
+
+    let instantiate = qrng_server.mock_async(|when, then| {
+        when.method("GET")
+            .path("/qrng/bytes")
+            .query_param("size", "48");
+        then.status(200)
+            .body([7u8; 48]);
+    }).await;
+    let reseed = qrng_server.mock_async(|when, then| {
+        when.method("GET")
+            .path("/qrng/bytes")
+            .query_param("size", "32");
+        then.status(200)
+            .body([9u8; 32]);
+    }).await;
+
+    let pqkd_client = BuilderPqkdClient::with_addr("http://127.0.0.1")
+        .unwrap()
+        .with_qrng_addr(&addr_qrng_server)
+        .unwrap()
+        .build();
+
+    let config = DrbgConfig { reseed_interval: 2, ..Default::default() };
+    let mut drbg = pqkd_client.drbg(config).await.unwrap();
+
+    let first = drbg.random_bytes(64).await.unwrap();
+    let second = drbg.random_bytes(64).await.unwrap();
+    assert_ne!(first, second);
+    reseed.assert_hits_async(0).await;
+
+    drbg.random_bytes(64).await.unwrap();
+    instantiate.assert_hits_async(1).await;
+    reseed.assert_hits_async(1).await;
+}
+
+#[tokio::test]
+async fn test_drbg_prediction_resistance() {
+    let qrng_server = MockServer::start_async().await;
+    let addr_qrng_server = format!("http://{}", qrng_server.address());
+
+    qrng_server.mock_async(|when, then| {
+        when.method("GET")
+            .path("/qrng/bytes")
+            .query_param("size", "48");
+        then.status(200)
+            .body([7u8; 48]);
+    }).await;
+    let reseed = qrng_server.mock_async(|when, then| {
+        when.method("GET")
+            .path("/qrng/bytes")
+            .query_param("size", "32");
+        then.status(200)
+            .body([9u8; 32]);
+    }).await;
+
+    let pqkd_client = BuilderPqkdClient::with_addr("http://127.0.0.1")
+        .unwrap()
+        .with_qrng_addr(&addr_qrng_server)
+        .unwrap()
+        .build();
+
+    let config = DrbgConfig { prediction_resistance: true, ..Default::default() };
+    let mut drbg = pqkd_client.drbg(config).await.unwrap();
+
+    drbg.random_bytes(16).await.unwrap();
+    drbg.random_bytes(16).await.unwrap();
+    reseed.assert_hits_async(2).await;
+}
+
+#[tokio::test]
+async fn test_drbg_short_entropy_from_qrng() {
+    let qrng_server = MockServer::start_async().await;
+    let addr_qrng_server = format!("http://{}", qrng_server.address());
+
+    qrng_server.mock_async(|when, then| {
+        when.method("GET")
+            .path("/qrng/bytes");
+        then.status(200)
+            .body([7u8; 8]);
+    }).await;
+
+    let pqkd_client = BuilderPqkdClient::with_addr("http://127.0.0.1")
+        .unwrap()
+        .with_qrng_addr(&addr_qrng_server)
+        .unwrap()
+        .build();
+
+    assert!(pqkd_client.drbg(DrbgConfig::default()).await.is_err());
+}
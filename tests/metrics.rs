@@ -0,0 +1,121 @@
+#![cfg(feature = "async")]
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use pqkd::metrics::Metrics;
+use pqkd::transport::{MemoryTransport, TransportResponse};
+use pqkd::{BuilderPqkdClient, PqkdClient};
+use reqwest::{Method, StatusCode};
+use serde_json::json;
+
+const KEY: &str = "wHHVxRwDJs3/bXd38GHP3oe4svTuRpZS0yCC7x4Ly+s=";
+
+/// Returns a client of a KME with `stored` keys, which answers `enc_keys`
+/// requests of more than two keys with `503 Service Unavailable`.
+fn client(metrics: Metrics, stored: Arc<AtomicU32>) -> PqkdClient {
+    let transport =
+        MemoryTransport::new(
+            move |request| match (request.method.clone(), request.url.path()) {
+                (Method::GET, "/api/v1/keys/Test_2SAE/status") => Ok(TransportResponse::json(
+                    StatusCode::OK,
+                    &json!({
+                        "source_KME_ID": "Test_1KME",
+                        "master_SAE_ID": "Test_1SAE",
+                        "key_size": 256,
+                        "stored_key_count": stored.load(Ordering::SeqCst),
+                        "max_key_count": 100000,
+                        "max_key_per_request": 128,
+                        "max_key_size": 1024,
+                        "min_key_size": 64,
+                        "max_SAE_ID_count": 0
+                    }),
+                )),
+                (Method::POST, "/api/v1/keys/Test_2SAE/enc_keys") => {
+                    let body: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+                    if body["number"].as_u64().unwrap() > 2 {
+                        return Ok(TransportResponse::new(
+                            StatusCode::SERVICE_UNAVAILABLE,
+                            Vec::new(),
+                        ));
+                    }
+                    let keys: Vec<_> = (0..body["number"].as_u64().unwrap())
+                        .map(|id| json!({"key_ID": id.to_string(), "key": KEY}))
+                        .collect();
+                    Ok(TransportResponse::json(
+                        StatusCode::OK,
+                        &json!({ "keys": keys }),
+                    ))
+                }
+                (Method::GET, "/qrng/bytes") => {
+                    Ok(TransportResponse::new(StatusCode::OK, vec![0; 16]))
+                }
+                _ => Ok(TransportResponse::new(StatusCode::NOT_FOUND, Vec::new())),
+            },
+        );
+    BuilderPqkdClient::with_addr("http://kme.invalid")
+        .unwrap()
+        .with_transport(transport)
+        .with_metrics(metrics)
+        .build()
+}
+
+#[tokio::test]
+async fn test_metrics() {
+    let metrics = Metrics::new();
+    let pqkd_client = client(metrics.clone(), Arc::new(AtomicU32::new(25000)));
+
+    pqkd_client.status("Test_2SAE").send().await.unwrap();
+    pqkd_client
+        .enc_keys("Test_2SAE")
+        .number(2)
+        .send()
+        .await
+        .unwrap();
+    assert!(pqkd_client
+        .enc_keys("Test_2SAE")
+        .number(3)
+        .send()
+        .await
+        .is_err());
+    assert!(pqkd_client.status("Test_3SAE").send().await.is_err());
+    pqkd_client.get_random_bytes(16).await.unwrap();
+
+    assert_eq!(metrics.keys_requested("Test_2SAE"), 5);
+    assert_eq!(metrics.keys_delivered("Test_2SAE"), 2);
+    assert_eq!(metrics.key_bits("Test_2SAE"), 512);
+    assert_eq!(metrics.qrng_bytes(), 16);
+    assert_eq!(metrics.errors("HttpStatus"), 2);
+    assert_eq!(metrics.stored_key_count("Test_2SAE"), Some(25000));
+    assert_eq!(metrics.stored_key_count("Test_3SAE"), None);
+    assert_eq!(metrics.requests("status"), 2);
+    assert_eq!(metrics.requests("enc_keys"), 2);
+
+    let text = metrics.prometheus();
+    assert!(
+        text.contains("pqkd_keys_delivered_total{sae_id=\"Test_2SAE\",method=\"enc_keys\"} 2\n")
+    );
+    assert!(text.contains("pqkd_errors_total{operation=\"status\",error=\"HttpStatus\"} 1\n"));
+    assert!(text.contains("pqkd_stored_key_count{sae_id=\"Test_2SAE\"} 25000\n"));
+    assert!(text.contains("pqkd_request_duration_seconds_count{operation=\"qrng\"} 1\n"));
+    assert!(!text.contains(KEY));
+}
+
+#[tokio::test]
+async fn test_metrics_poll_status() {
+    let metrics = Metrics::new();
+    let stored = Arc::new(AtomicU32::new(10));
+    let pqkd_client = client(metrics.clone(), stored.clone());
+
+    let poll = tokio::spawn(async move {
+        pqkd_client
+            .poll_status("Test_2SAE", Duration::from_millis(10))
+            .await;
+    });
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert_eq!(metrics.stored_key_count("Test_2SAE"), Some(10));
+    stored.store(20, Ordering::SeqCst);
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert_eq!(metrics.stored_key_count("Test_2SAE"), Some(20));
+    poll.abort();
+}
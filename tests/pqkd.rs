@@ -1,3 +1,5 @@
+#![cfg(feature = "async")]
+#![allow(clippy::needless_borrow)]
 use pqkd::{PqkdStatus, BuilderPqkdClient};
 use serde_json::json;
 use httpmock::MockServer;
@@ -1,4 +1,8 @@
-use pqkd::qrng::{MAX_SIZE_FOR_BYTES_FORMAT, MAX_SIZE_FOR_STRING_FORMAT};
+#![cfg(feature = "async")]
+#![allow(clippy::unnecessary_cast)]
+use pqkd::entropy::EntropyMode;
+use pqkd::error::PqkdError;
+use pqkd::qrng::{QrngFormat, MAX_SIZE_FOR_BYTES_FORMAT, MAX_SIZE_FOR_STRING_FORMAT};
 use pqkd::BuilderPqkdClient;
 use serde_json::json;
 use httpmock::MockServer;
@@ -91,7 +95,7 @@ async fn test_get_random_base64_for_the_large_size() {
 async fn test_get_random_bytes() {
     let qrng_server = MockServer::start_async().await;
     let addr_qrng_server = format!("http://{}", qrng_server.address());
-    let binary = b"\x28\x214\x252\x207\x37\x218\x43\x144\x62\x183\x81\x251\x22\x163\x201\x90\x172\x215\x80\x51";
+    let binary = [28u8, 214, 252, 207, 37, 218, 43, 144, 62, 183, 81, 251, 22, 163, 201, 90, 172, 215, 80, 51];
     let size = 20usize;
 
     qrng_server.mock_async(|when, then| {
@@ -126,4 +130,149 @@ async fn test_get_random_bytes_for_the_large_size() {
         .await;
     
     assert!(result.is_err());
-}
\ No newline at end of file
+}
+#[tokio::test]
+async fn test_get_random_bytes_mixed_with_os_entropy() {
+    let qrng_server = MockServer::start_async().await;
+    let addr_qrng_server = format!("http://{}", qrng_server.address());
+    let binary = [0u8; 64];
+    let size = binary.len();
+
+    qrng_server.mock_async(|when, then| {
+        when.method("GET")
+            .path("/qrng/bytes")
+            .query_param("size", size.to_string());
+        then.status(200)
+            .body(binary);
+    }).await;
+
+    let pqkd_client = BuilderPqkdClient::with_addr("http://127.0.0.1")
+        .unwrap()
+        .with_qrng_addr(&addr_qrng_server)
+        .unwrap()
+        .with_entropy_mode(EntropyMode::Mixed)
+        .build();
+
+    let first = pqkd_client.get_random_bytes(size as u32)
+        .await.unwrap();
+    let second = pqkd_client.get_random_bytes(size as u32)
+        .await.unwrap();
+
+    assert_eq!(first.len(), size);
+    assert_ne!(first, binary.to_vec());
+    assert_ne!(first, second);
+}
+
+#[tokio::test]
+async fn test_get_random_with_metadata() {
+    let qrng_server = MockServer::start_async().await;
+    let addr_qrng_server = format!("http://{}", qrng_server.address());
+    let random_hex = "5ede863536f9c2cb29e2ca26d5aef0e2dffe44c7";
+    let size = 20usize;
+
+    qrng_server.mock_async(|when, then| {
+        when.method("GET")
+            .path("/qrng/hex")
+            .query_param("size", size.to_string());
+        then.status(200)
+            .header("content-type", "application/json")
+            .json_body(json!({"result": random_hex, "size": size.to_string(), "format": "hex", "executeTime": 335}));
+    }).await;
+
+    let pqkd_client = BuilderPqkdClient::with_addr("http://127.0.0.1")
+        .unwrap()
+        .with_qrng_addr(&addr_qrng_server)
+        .unwrap()
+        .build();
+
+    let result = pqkd_client.get_random(QrngFormat::Hex, size as u32)
+        .await.unwrap();
+
+    assert_eq!(result.size(), size as u32);
+    assert_eq!(result.format(), QrngFormat::Hex);
+    assert_eq!(result.execute_time(), Some(335));
+    assert_eq!(result.headers().get("content-type").unwrap(), "application/json");
+    assert_eq!(result.into_result().as_hex().unwrap(), random_hex);
+}
+
+#[tokio::test]
+async fn test_get_random_bytes_with_wrong_size() {
+    let qrng_server = MockServer::start_async().await;
+    let addr_qrng_server = format!("http://{}", qrng_server.address());
+    let size = 20usize;
+
+    qrng_server.mock_async(|when, then| {
+        when.method("GET")
+            .path("/qrng/bytes")
+            .query_param("size", size.to_string());
+        then.status(200)
+            .body([1u8; 16]);
+    }).await;
+
+    let pqkd_client = BuilderPqkdClient::with_addr("http://127.0.0.1")
+        .unwrap()
+        .with_qrng_addr(&addr_qrng_server)
+        .unwrap()
+        .build();
+
+    let result = pqkd_client.get_random_bytes(size as u32)
+        .await;
+
+    assert!(matches!(result, Err(PqkdError::QrngSizeMismatch { requested: 20, found: 16 })));
+}
+
+#[tokio::test]
+async fn test_get_random_hex_bytes() {
+    let qrng_server = MockServer::start_async().await;
+    let addr_qrng_server = format!("http://{}", qrng_server.address());
+    let random_hex = "5ede863536f9c2cb29e2ca26d5aef0e2dffe44c7";
+    let size = 20usize;
+
+    qrng_server.mock_async(|when, then| {
+        when.method("GET")
+            .path("/qrng/hex")
+            .query_param("size", size.to_string());
+        then.status(200)
+            .header("content-type", "application/json")
+            .json_body(json!({"result": random_hex, "size": size.to_string(), "format": "hex", "executeTime": 335}));
+    }).await;
+
+    let pqkd_client = BuilderPqkdClient::with_addr("http://127.0.0.1")
+        .unwrap()
+        .with_qrng_addr(&addr_qrng_server)
+        .unwrap()
+        .build();
+
+    let result = pqkd_client.get_random_hex_bytes(size as u32)
+        .await.unwrap();
+
+    assert_eq!(result.len(), size);
+    assert_eq!(&result[..4], &[0x5e, 0xde, 0x86, 0x35]);
+}
+
+#[tokio::test]
+async fn test_get_random_base64_bytes_invalid_encoding() {
+    let qrng_server = MockServer::start_async().await;
+    let addr_qrng_server = format!("http://{}", qrng_server.address());
+    let size = 20usize;
+
+    qrng_server.mock_async(|when, then| {
+        when.method("GET")
+            .path("/qrng/base64")
+            .query_param("size", size.to_string());
+        then.status(200)
+            .header("content-type", "application/json")
+            .json_body(json!({"result": "w7jYDPNv789HHJTJg7iwkg4AYI0", "size": size.to_string(), "format": "base64", "executeTime": 335}));
+    }).await;
+
+    let pqkd_client = BuilderPqkdClient::with_addr("http://127.0.0.1")
+        .unwrap()
+        .with_qrng_addr(&addr_qrng_server)
+        .unwrap()
+        .build();
+
+    let result = pqkd_client.get_random_base64_bytes(size as u32)
+        .await;
+
+    assert!(matches!(result, Err(PqkdError::InvalidQrngData(_))));
+}
@@ -0,0 +1,109 @@
+#![cfg(feature = "tower")]
+use std::future::poll_fn;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use pqkd::error::PqkdError;
+use pqkd::request::{PqkdMethod, PqkdRequest};
+use pqkd::service::KeyBackpressureLayer;
+use pqkd::transport::{MemoryTransport, TransportResponse};
+use pqkd::{BuilderPqkdClient, PqkdClient, PqkdResponse};
+use reqwest::StatusCode;
+use serde_json::json;
+use tower::Layer;
+use tower_service::Service;
+
+/// Returns a client of a KME with `stored` keys of 256 bits, and a counter
+/// of the status requests it answered.
+fn client(stored: Arc<AtomicU32>) -> (PqkdClient, Arc<AtomicU32>) {
+    let status_requests = Arc::new(AtomicU32::new(0));
+    let counter = status_requests.clone();
+    let transport = MemoryTransport::new(move |request| {
+        if request.url.path().ends_with("/status") {
+            counter.fetch_add(1, Ordering::SeqCst);
+            return Ok(TransportResponse::json(
+                StatusCode::OK,
+                &json!({
+                    "source_KME_ID": "Test_1KME",
+                    "master_SAE_ID": "Test_1SAE",
+                    "key_size": 256,
+                    "stored_key_count": stored.load(Ordering::SeqCst),
+                    "max_key_count": 100000,
+                    "max_key_per_request": 128,
+                    "max_key_size": 1024,
+                    "min_key_size": 64,
+                    "max_SAE_ID_count": 0
+                }),
+            ));
+        }
+        Ok(TransportResponse::json(
+            StatusCode::OK,
+            &json!({"keys": [{"key_ID": "bc490419-7d60-487f-adc1-4ddcc177c139", "key": "wHHVxRwDJs3/bXd38GHP3oe4svTuRpZS0yCC7x4Ly+s="}]}),
+        ))
+    });
+    let client = BuilderPqkdClient::with_addr("http://kme.invalid")
+        .unwrap()
+        .with_transport(transport)
+        .build();
+    (client, status_requests)
+}
+
+async fn call<S>(service: &mut S, request: PqkdRequest) -> Result<PqkdResponse, PqkdError>
+where
+    S: Service<PqkdRequest, Response = PqkdResponse, Error = PqkdError>,
+{
+    poll_fn(|cx| service.poll_ready(cx)).await?;
+    service.call(request).await
+}
+
+fn enc_keys(number: u32) -> PqkdRequest {
+    let mut request = PqkdRequest::new(PqkdMethod::EncKeys, "Test_2SAE");
+    request.set_number(number);
+    request
+}
+
+#[tokio::test]
+async fn test_service() {
+    let (mut client, _) = client(Arc::new(AtomicU32::new(10)));
+
+    let response = call(&mut client, PqkdRequest::new(PqkdMethod::Status, "Test_2SAE")).await;
+    assert_eq!(response.unwrap().as_status().unwrap().stored_key_count, 10);
+    assert_eq!(call(&mut client, enc_keys(1)).await.unwrap().keys().len(), 1);
+}
+
+#[tokio::test]
+async fn test_key_backpressure_waits_for_keys() {
+    let stored = Arc::new(AtomicU32::new(3));
+    let (client, status_requests) = client(stored.clone());
+    let mut service = KeyBackpressureLayer::new()
+        .with_poll_interval(Duration::from_millis(10))
+        .layer(client);
+
+    // Two keys of 512 bits need four stored keys of 256 bits.
+    let refill = tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        stored.store(4, Ordering::SeqCst);
+    });
+    assert!(call(&mut service, enc_keys(2)).await.is_ok());
+    assert!(status_requests.load(Ordering::SeqCst) > 1);
+    refill.await.unwrap();
+
+    // Status requests are passed through.
+    let requests = status_requests.load(Ordering::SeqCst);
+    call(&mut service, PqkdRequest::new(PqkdMethod::Status, "Test_2SAE")).await.unwrap();
+    assert_eq!(status_requests.load(Ordering::SeqCst), requests + 1);
+}
+
+#[tokio::test]
+async fn test_key_backpressure_reserve() {
+    let (client, _) = client(Arc::new(AtomicU32::new(10)));
+    let mut service = KeyBackpressureLayer::new()
+        .with_reserve(9)
+        .with_poll_interval(Duration::from_millis(10))
+        .with_max_wait(Duration::from_millis(30))
+        .layer(client);
+
+    let result = call(&mut service, enc_keys(1)).await;
+    assert!(matches!(result, Err(PqkdError::NotEnoughKeys { stored: 10, required: 11 })));
+}
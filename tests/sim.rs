@@ -0,0 +1,300 @@
+#![cfg(all(feature = "sim", feature = "async"))]
+use pqkd::combiner::CompositeKeyId;
+use pqkd::error::PqkdError;
+use pqkd::qrng::QrngFormat;
+use pqkd::sim::{Fault, FaultRule, FaultScenario, Side, SimConfig, SimLink, Simulator};
+use pqkd::relay::RelayMessage;
+use pqkd::sync::{KeyAccept, KeyOffer};
+use pqkd::{Key, KeyCombiner, KeyRelay};
+
+#[tokio::test]
+async fn test_sim_enc_dec_keys() {
+    let simulator = Simulator::start(SimConfig::default()).unwrap();
+    let client_a = simulator.client_a();
+    let client_b = simulator.client_b();
+
+    let keys_a = client_a.enc_keys(simulator.sae_id_b())
+        .number(4)
+        .size(512)
+        .send()
+        .await
+        .unwrap()
+        .keys();
+    let keys_b = client_b.dec_keys(simulator.sae_id_a())
+        .key_ids(keys_a.iter().map(|key| key.key_id()).collect())
+        .send()
+        .await
+        .unwrap()
+        .keys();
+
+    assert_eq!(keys_a.len(), 4);
+    assert_eq!(keys_a, keys_b);
+}
+
+#[tokio::test]
+async fn test_sim_status_counters() {
+    let simulator = Simulator::start(SimConfig { initial_key_count: 100, ..Default::default() }).unwrap();
+    let client_a = simulator.client_a();
+    let client_b = simulator.client_b();
+
+    let status = client_a.status(simulator.sae_id_b()).send().await.unwrap().as_status().unwrap();
+    assert_eq!(status.stored_key_count, 100);
+    assert_eq!(status.source_kme_id, "Sim_1KME");
+    assert_eq!(status.master_sae_id, "Sim_1SAE");
+
+    client_a.enc_keys(simulator.sae_id_b()).number(10).size(256).send().await.unwrap();
+
+    let status = client_b.status(simulator.sae_id_a()).send().await.unwrap().as_status().unwrap();
+    assert_eq!(status.stored_key_count, 90);
+    assert_eq!(status.source_kme_id, "Sim_2KME");
+}
+
+#[tokio::test]
+async fn test_sim_unknown_key_id() {
+    let simulator = Simulator::start(SimConfig::default()).unwrap();
+
+    let result = simulator.client_b()
+        .dec_keys(simulator.sae_id_a())
+        .key_id("17d3e519-10e9-43e6-bd7a-72b2da710dcd")
+        .send()
+        .await;
+
+    assert!(result.is_err());
+}
+
+#[tokio::test]
+async fn test_sim_qrng() {
+    let simulator = Simulator::start(SimConfig::default()).unwrap();
+    let client = simulator.client_a();
+
+    assert_eq!(client.get_random_bytes(100).await.unwrap().len(), 100);
+    assert_eq!(client.get_random_hex_bytes(20).await.unwrap().len(), 20);
+    let response = client.get_random(QrngFormat::Base64, 32).await.unwrap();
+    assert_eq!(response.result().to_bytes().unwrap().len(), 32);
+    assert!(matches!(
+        client.get_random_hex(pqkd::qrng::MAX_SIZE_FOR_STRING_FORMAT + 1).await,
+        Err(PqkdError::InvalidSize { .. })
+    ));
+}
+
+#[cfg(feature = "blocking")]
+#[test]
+fn test_sim_blocking_client() {
+    let simulator = Simulator::start(SimConfig::default()).unwrap();
+    let client_a = simulator.blocking_client_a();
+    let client_b = simulator.blocking_client_b();
+
+    let keys_a = client_a.enc_keys(simulator.sae_id_b()).send().unwrap().keys();
+    let keys_b = client_b.dec_keys(simulator.sae_id_a())
+        .key_id(keys_a[0].key_id())
+        .send()
+        .unwrap()
+        .keys();
+
+    assert_eq!(keys_a, keys_b);
+    assert_eq!(simulator.link().stored_key_count(), 4096 - 2);
+}
+
+#[tokio::test]
+async fn test_sim_memory_transport() {
+    let link = SimLink::new(SimConfig::default());
+    let config = link.config();
+    let client_a = link.client(Side::A);
+    let client_b = link.client(Side::B);
+
+    let keys_a = client_a.enc_keys(&config.sae_id_b).number(2).send().await.unwrap().keys();
+    let keys_b = client_b.dec_keys(&config.sae_id_a)
+        .key_ids(keys_a.iter().map(|key| key.key_id()).collect())
+        .send()
+        .await
+        .unwrap()
+        .keys();
+    assert_eq!(keys_a, keys_b);
+
+    let status = client_b.status(&config.sae_id_a).send().await.unwrap().as_status().unwrap();
+    assert_eq!(status.source_kme_id, "Sim_2KME");
+    assert_eq!(client_a.get_random_bytes(64).await.unwrap().len(), 64);
+
+    link.set_scenario(FaultScenario::new().rule(FaultRule::new(Fault::ConnectionReset).times(1)));
+    assert!(matches!(
+        client_a.status(&config.sae_id_b).send().await,
+        Err(PqkdError::TransportError(_))
+    ));
+    link.set_scenario(FaultScenario::no_keys_burst(Side::A, 1));
+    assert!(matches!(
+        client_a.enc_keys(&config.sae_id_b).send().await,
+        Err(PqkdError::HttpStatus(status)) if status.as_u16() == 503
+    ));
+    assert!(client_a.enc_keys(&config.sae_id_b).send().await.is_ok());
+}
+
+#[cfg(feature = "blocking")]
+#[test]
+fn test_sim_blocking_memory_transport() {
+    let link = SimLink::new(SimConfig::default());
+    let config = link.config();
+    let client_a = link.blocking_client(Side::A);
+    let client_b = link.blocking_client(Side::B);
+
+    let keys_a = client_a.enc_keys(&config.sae_id_b).send().unwrap().keys();
+    let keys_b = client_b.dec_keys(&config.sae_id_a)
+        .key_id(keys_a[0].key_id())
+        .send()
+        .unwrap()
+        .keys();
+    assert_eq!(keys_a, keys_b);
+    assert_eq!(link.pending_key_count(Side::A), 0);
+}
+
+#[tokio::test]
+async fn test_sim_key_sync() {
+    let link = SimLink::new(SimConfig::default());
+    let config = link.config();
+    let client_a = link.client(Side::A);
+    let client_b = link.client(Side::B);
+
+    let (keys_a, offer) = client_a.offer_keys(&config.sae_id_b, "tls-psk", 2, 256).await.unwrap();
+    let received = KeyOffer::decode(&offer.encode()).unwrap();
+    assert_eq!(received.master_sae_id, config.sae_id_a);
+    assert_eq!(received.context, "tls-psk");
+    let (keys_b, accept) = client_b.accept_keys(&received).await.unwrap();
+    assert_eq!(keys_a, keys_b);
+    offer.verify_accept(&KeyAccept::decode(&accept.encode()).unwrap(), &keys_a).unwrap();
+
+    // Offers are only accepted by the slave SAE they are meant for.
+    let (_, offer) = client_a.offer_keys(&config.sae_id_b, "tls-psk", 1, 256).await.unwrap();
+    assert!(matches!(client_a.accept_keys(&offer).await, Err(PqkdError::KeySyncError(_))));
+}
+
+#[cfg(feature = "blocking")]
+#[test]
+fn test_sim_blocking_key_sync() {
+    let link = SimLink::new(SimConfig::default());
+    let config = link.config();
+    let client_a = link.blocking_client(Side::A);
+    let client_b = link.blocking_client(Side::B);
+
+    let (keys_a, offer) = client_a.offer_keys(&config.sae_id_b, "tls-psk", 2, 256).unwrap();
+    let (keys_b, accept) = client_b.accept_keys(&KeyOffer::decode(&offer.encode()).unwrap()).unwrap();
+    assert_eq!(keys_a, keys_b);
+    offer.verify_accept(&KeyAccept::decode(&accept.encode()).unwrap(), &keys_a).unwrap();
+}
+
+#[tokio::test]
+async fn test_sim_key_confirmation() {
+    let link = SimLink::new(SimConfig::default());
+    let config = link.config();
+    let client_a = link.client(Side::A);
+    let client_b = link.client(Side::B);
+
+    let keys_a = client_a.enc_keys(&config.sae_id_b).send().await.unwrap().keys();
+    let keys_b = client_b.dec_keys(&config.sae_id_a)
+        .key_ids(keys_a.iter().map(|key| key.key_id()).collect())
+        .send()
+        .await
+        .unwrap()
+        .keys();
+
+    // A challenges B to prove that it holds the same key.
+    let nonce = Key::confirmation_nonce().unwrap();
+    let tag = keys_b[0].confirmation_tag(&config.sae_id_b, &nonce).unwrap();
+    keys_a[0].verify_confirmation_tag(&config.sae_id_b, &nonce, &tag).unwrap();
+    assert!(matches!(
+        keys_a[0].verify_confirmation_tag(&config.sae_id_a, &nonce, &tag),
+        Err(PqkdError::KeyMismatch(key_id)) if key_id == keys_a[0].key_id()
+    ));
+}
+
+#[tokio::test]
+async fn test_sim_key_combiner() {
+    let links = [SimLink::new(SimConfig::default()), SimLink::new(SimConfig::default())];
+    let config = links[0].config();
+    let master = links.iter().fold(KeyCombiner::new(), |combiner, link| {
+        combiner.with_link(link.client(Side::A), &config.sae_id_b)
+    });
+    let slave = links.iter().fold(KeyCombiner::new(), |combiner, link| {
+        combiner.with_link(link.client(Side::B), &config.sae_id_a)
+    });
+
+    let keys_a = master.enc_keys(2, 256).await.unwrap();
+    assert_eq!(keys_a.len(), 2);
+    assert_eq!(keys_a[0].key_id().key_ids.len(), 2);
+    let key_ids: Vec<CompositeKeyId> = keys_a.iter().map(|key| key.key_id().clone()).collect();
+    let keys_b = slave.dec_keys(&key_ids).await.unwrap();
+    assert_eq!(keys_a, keys_b);
+
+    let single = KeyCombiner::new().with_link(links[0].client(Side::A), &config.sae_id_b);
+    assert!(matches!(single.enc_keys(1, 256).await, Err(PqkdError::CombineError(_))));
+}
+
+#[cfg(feature = "blocking")]
+#[test]
+fn test_sim_blocking_key_combiner() {
+    let links = [SimLink::new(SimConfig::default()), SimLink::new(SimConfig::default())];
+    let config = links[0].config();
+    let master = links.iter().fold(pqkd::blocking::KeyCombiner::new(), |combiner, link| {
+        combiner.with_link(link.blocking_client(Side::A), &config.sae_id_b)
+    });
+    let slave = links.iter().fold(pqkd::blocking::KeyCombiner::new(), |combiner, link| {
+        combiner.with_link(link.blocking_client(Side::B), &config.sae_id_a)
+    });
+
+    let keys_a = master.enc_keys(3, 512).unwrap();
+    let key_ids: Vec<CompositeKeyId> = keys_a.iter().map(|key| key.key_id().clone()).collect();
+    assert_eq!(slave.dec_keys(&key_ids).unwrap(), keys_a);
+    assert!(slave.dec_keys(&[CompositeKeyId { key_ids: vec!["unknown".to_string()] }]).is_err());
+}
+
+/// Links Sim_1SAE–Sim_2SAE and Sim_2SAE–Sim_3SAE.
+fn relay_path() -> [SimLink; 2] {
+    [
+        SimLink::new(SimConfig::default()),
+        SimLink::new(SimConfig {
+            sae_id_a: "Sim_2SAE".to_string(),
+            sae_id_b: "Sim_3SAE".to_string(),
+            ..SimConfig::default()
+        }),
+    ]
+}
+
+#[tokio::test]
+async fn test_sim_key_relay() {
+    let [link_ab, link_bc] = relay_path();
+    let relay = KeyRelay::new()
+        .with_hop(link_ab.client(Side::A), link_ab.client(Side::B))
+        .with_hop(link_bc.client(Side::A), link_bc.client(Side::B));
+    let (key_a, key_c) = relay.relay_key(256).await.unwrap();
+    assert_eq!(key_a, key_c);
+    assert_eq!(key_a.key().len(), 44);
+
+    // The same steps, with the messages sent between the nodes.
+    let (key_a, message) = link_ab.client(Side::A).relay_key("Sim_2SAE", "Sim_3SAE", 512).await.unwrap();
+    let message = RelayMessage::decode(&message.encode()).unwrap();
+    let key_b = link_ab.client(Side::B).receive_relayed_key("Sim_1SAE", &message).await.unwrap();
+    let message = link_bc.client(Side::A).forward_relayed_key("Sim_3SAE", &key_b, &message).await.unwrap();
+    let key_c = link_bc.client(Side::B).receive_relayed_key("Sim_2SAE", &message).await.unwrap();
+    assert_eq!(key_c.key_id(), key_a.key_id());
+    assert_eq!(key_c, key_a);
+
+    // Hop keys are deleted once fetched.
+    assert!(link_bc.client(Side::B).receive_relayed_key("Sim_2SAE", &message).await.is_err());
+    assert!(matches!(KeyRelay::new().relay_key(256).await, Err(PqkdError::RelayError(_))));
+}
+
+#[cfg(feature = "blocking")]
+#[test]
+fn test_sim_blocking_key_relay() {
+    let [link_ab, link_bc] = relay_path();
+    let relay = pqkd::blocking::KeyRelay::new()
+        .with_hop(link_ab.blocking_client(Side::A), link_ab.blocking_client(Side::B))
+        .with_hop(link_bc.blocking_client(Side::A), link_bc.blocking_client(Side::B));
+    let (key_a, key_c) = relay.relay_key(1024).unwrap();
+    assert_eq!(key_a, key_c);
+
+    let (_, message) = link_ab.blocking_client(Side::A).relay_key("Sim_2SAE", "Sim_3SAE", 256).unwrap();
+    let tampered = RelayMessage { destination_sae_id: "Sim_4SAE".to_string(), ..message };
+    assert!(matches!(
+        link_ab.blocking_client(Side::B).receive_relayed_key("Sim_1SAE", &tampered),
+        Err(PqkdError::RelayError(_))
+    ));
+}
@@ -0,0 +1,124 @@
+#![cfg(all(feature = "sim", feature = "async"))]
+use std::time::{Duration, Instant};
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use pqkd::error::PqkdError;
+use pqkd::sim::{Endpoint, Fault, FaultRule, FaultScenario, SimConfig, Side, Simulator};
+
+#[tokio::test]
+async fn test_fault_no_keys_burst() {
+    let simulator = Simulator::start(SimConfig::default()).unwrap();
+    simulator.link().set_scenario(FaultScenario::no_keys_burst(Side::A, 2));
+    let client = simulator.client_a();
+
+    for _ in 0..2 {
+        let result = client.enc_keys(simulator.sae_id_b()).send().await;
+        match result {
+            Err(PqkdError::HttpStatus(status)) => assert_eq!(status.as_u16(), 503),
+            other => panic!("unexpected result: {:?}", other.map(|_| ())),
+        }
+    }
+    assert!(client.enc_keys(simulator.sae_id_b()).send().await.is_ok());
+    assert_eq!(simulator.link().stored_key_count(), 4096 - 2);
+}
+
+#[tokio::test]
+async fn test_fault_latency() {
+    let simulator = Simulator::start(SimConfig::default()).unwrap();
+    simulator.link().set_scenario(FaultScenario::slow_link(Duration::from_millis(200)));
+
+    let start = Instant::now();
+    simulator.client_a().status(simulator.sae_id_b()).send().await.unwrap();
+    assert!(start.elapsed() >= Duration::from_millis(200));
+}
+
+#[tokio::test]
+async fn test_fault_truncated_json() {
+    let simulator = Simulator::start(SimConfig::default()).unwrap();
+    simulator.link().set_scenario(
+        FaultScenario::new().rule(FaultRule::new(Fault::TruncatedJson).endpoint(Endpoint::Status).times(1)),
+    );
+    let client = simulator.client_a();
+
+    assert!(matches!(
+        client.status(simulator.sae_id_b()).send().await,
+        Err(PqkdError::SerdeJsonError(_))
+    ));
+    assert!(client.status(simulator.sae_id_b()).send().await.is_ok());
+}
+
+#[tokio::test]
+async fn test_fault_wrong_key_size() {
+    let simulator = Simulator::start(SimConfig::default()).unwrap();
+    simulator.link().set_scenario(FaultScenario::new().rule(FaultRule::new(Fault::WrongKeySize).on(Side::A)));
+
+    let keys = simulator.client_a()
+        .enc_keys(simulator.sae_id_b())
+        .size(512)
+        .send()
+        .await
+        .unwrap()
+        .keys();
+    assert_eq!(BASE64.decode(keys[0].key()).unwrap().len(), 32);
+}
+
+#[tokio::test]
+async fn test_fault_mismatched_key_ids() {
+    let simulator = Simulator::start(SimConfig::default()).unwrap();
+    simulator.link().set_scenario(
+        FaultScenario::new().rule(FaultRule::new(Fault::MismatchedKeyIds).endpoint(Endpoint::EncKeys)),
+    );
+
+    let keys = simulator.client_a().enc_keys(simulator.sae_id_b()).send().await.unwrap().keys();
+    let result = simulator.client_b()
+        .dec_keys(simulator.sae_id_a())
+        .key_id(keys[0].key_id())
+        .send()
+        .await;
+    assert!(matches!(result, Err(PqkdError::HttpStatus(_))));
+    assert_eq!(simulator.link().pending_key_count(Side::B), 1);
+}
+
+#[tokio::test]
+async fn test_fault_connection_reset() {
+    let simulator = Simulator::start(SimConfig::default()).unwrap();
+    simulator.link().set_scenario(FaultScenario::flaky(Side::B, 2));
+    let client = simulator.client_b();
+
+    assert!(client.status(simulator.sae_id_a()).send().await.is_ok());
+    assert!(matches!(
+        client.status(simulator.sae_id_a()).send().await,
+        Err(PqkdError::RequestError(_))
+    ));
+    assert!(client.status(simulator.sae_id_a()).send().await.is_ok());
+}
+
+#[tokio::test]
+async fn test_fault_garbage_reset() {
+    let simulator = Simulator::start(SimConfig::default()).unwrap();
+    simulator.link().set_scenario(
+        FaultScenario::new().rule(FaultRule::new(Fault::GarbageReset).on(Side::A).times(1)),
+    );
+    let client = simulator.client_a();
+
+    assert!(matches!(
+        client.status(simulator.sae_id_b()).send().await,
+        Err(PqkdError::RequestError(_))
+    ));
+    assert!(client.status(simulator.sae_id_b()).send().await.is_ok());
+
+    simulator.link().clear_faults();
+    assert!(client.status(simulator.sae_id_b()).send().await.is_ok());
+}
+
+#[cfg(feature = "blocking")]
+#[test]
+fn test_fault_blocking_client() {
+    let simulator = Simulator::start(SimConfig::default()).unwrap();
+    simulator.link().set_scenario(FaultScenario::no_keys_burst(Side::B, 1));
+    let client = simulator.blocking_client_b();
+
+    assert!(client.enc_keys(simulator.sae_id_a()).send().is_err());
+    assert!(client.enc_keys(simulator.sae_id_a()).send().is_ok());
+}
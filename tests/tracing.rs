@@ -0,0 +1,116 @@
+#![cfg(all(feature = "tracing", feature = "async"))]
+use std::fmt::{Debug, Write};
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use pqkd::retry::RetryPolicy;
+use pqkd::transport::{MemoryTransport, TransportResponse};
+use pqkd::BuilderPqkdClient;
+use reqwest::StatusCode;
+use serde_json::json;
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Metadata, Subscriber};
+
+const KEY: &str = "wHHVxRwDJs3/bXd38GHP3oe4svTuRpZS0yCC7x4Ly+s=";
+const KEY_ID: &str = "bc490419-7d60-487f-adc1-4ddcc177c139";
+const RANDOM_HEX: &str = "5f1e2d3c4b5a69788796a5b4c3d2e1f0";
+
+/// Subscriber recording every span and event as a line of text.
+#[derive(Clone, Default)]
+struct Recorder {
+    lines: Arc<Mutex<Vec<String>>>,
+    next_id: Arc<AtomicU64>,
+}
+
+impl Recorder {
+    fn output(&self) -> String {
+        self.lines.lock().unwrap().join("\n")
+    }
+}
+
+struct Line(String);
+
+impl Visit for Line {
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        let _ = write!(self.0, " {}={:?}", field.name(), value);
+    }
+}
+
+impl Subscriber for Recorder {
+    fn enabled(&self, _: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, span: &Attributes<'_>) -> Id {
+        let mut line = Line(format!("span {}", span.metadata().name()));
+        span.record(&mut line);
+        self.lines.lock().unwrap().push(line.0);
+        Id::from_u64(self.next_id.fetch_add(1, Ordering::SeqCst) + 1)
+    }
+
+    fn record(&self, _: &Id, values: &Record<'_>) {
+        let mut line = Line("record".to_string());
+        values.record(&mut line);
+        self.lines.lock().unwrap().push(line.0);
+    }
+
+    fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let mut line = Line(event.metadata().level().to_string());
+        event.record(&mut line);
+        self.lines.lock().unwrap().push(line.0);
+    }
+
+    fn enter(&self, _: &Id) {}
+
+    fn exit(&self, _: &Id) {}
+}
+
+#[tokio::test]
+async fn test_tracing() {
+    let recorder = Recorder::default();
+    let _guard = tracing::subscriber::set_default(recorder.clone());
+
+    let unavailable = Arc::new(AtomicU32::new(1));
+    let transport = MemoryTransport::new(move |request| {
+        let path = request.url.path();
+        if path.ends_with("/enc_keys") && unavailable.fetch_sub(1, Ordering::SeqCst) > 0 {
+            return Ok(TransportResponse::new(StatusCode::SERVICE_UNAVAILABLE, Vec::new()));
+        }
+        if path.starts_with("/qrng/") {
+            return Ok(TransportResponse::json(StatusCode::OK, &json!({"result": RANDOM_HEX})));
+        }
+        Ok(TransportResponse::json(
+            StatusCode::OK,
+            &json!({"keys": [{"key_ID": KEY_ID, "key": KEY}]}),
+        ))
+    });
+    let pqkd_client = BuilderPqkdClient::with_addr("http://kme.invalid")
+        .unwrap()
+        .with_transport(transport)
+        .with_retry_policy(RetryPolicy {
+            max_retries: 1,
+            backoff: std::time::Duration::from_millis(1),
+            ..RetryPolicy::default()
+        })
+        .build();
+
+    let keys = pqkd_client.enc_keys("Test_2SAE").send().await.unwrap().keys();
+    assert_eq!(keys[0].key(), KEY);
+    pqkd_client.dec_keys("Test_1SAE").key_id(KEY_ID).send().await.unwrap();
+    assert_eq!(pqkd_client.get_random_hex(16).await.unwrap(), RANDOM_HEX);
+
+    let output = recorder.output();
+    assert!(output.contains("pQKD client built"));
+    assert!(output.contains("span kme_request method=EncKeys sae_id=\"Test_2SAE\" number=1 size=512"));
+    assert!(output.contains("status=503"));
+    assert!(output.contains("retrying request"));
+    assert!(output.contains(&format!("key_count=1 key_ids={}", KEY_ID)));
+    assert!(output.contains(&format!("span kme_request method=DesKeys sae_id=\"Test_1SAE\" number=1 size=512 key_ids={}", KEY_ID)));
+    assert!(output.contains("span qrng_request format=hex size=16"));
+    assert!(output.contains("latency_ms="));
+    assert!(!output.contains(KEY), "key material recorded:\n{}", output);
+    assert!(!output.contains(RANDOM_HEX), "random data recorded:\n{}", output);
+}
@@ -0,0 +1,114 @@
+#![cfg(feature = "async")]
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use pqkd::error::PqkdError;
+use pqkd::transport::{MemoryTransport, TransportResponse};
+use pqkd::watcher::{WatchEvent, WatcherConfig};
+use pqkd::{BuilderPqkdClient, PqkdClient, StatusWatcher};
+use reqwest::StatusCode;
+use serde_json::json;
+
+/// Returns a client of a KME with `stored` keys, which does not answer
+/// while `down` is set.
+fn client(stored: Arc<AtomicU32>, down: Arc<AtomicBool>) -> PqkdClient {
+    let transport = MemoryTransport::new(move |_| {
+        if down.load(Ordering::SeqCst) {
+            return Err(PqkdError::TransportError("connection refused".to_string()));
+        }
+        Ok(TransportResponse::json(
+            StatusCode::OK,
+            &json!({
+                "source_KME_ID": "Test_1KME",
+                "master_SAE_ID": "Test_1SAE",
+                "key_size": 256,
+                "stored_key_count": stored.load(Ordering::SeqCst),
+                "max_key_count": 100000,
+                "max_key_per_request": 128,
+                "max_key_size": 1024,
+                "min_key_size": 64,
+                "max_SAE_ID_count": 0
+            }),
+        ))
+    });
+    BuilderPqkdClient::with_addr("http://kme.invalid")
+        .unwrap()
+        .with_transport(transport)
+        .build()
+}
+
+fn config() -> WatcherConfig {
+    WatcherConfig {
+        sae_ids: vec!["Test_2SAE".to_string()],
+        interval: Duration::from_millis(10),
+        thresholds: vec![1000],
+    }
+}
+
+#[tokio::test]
+async fn test_watcher_events() {
+    let stored = Arc::new(AtomicU32::new(2000));
+    let down = Arc::new(AtomicBool::new(false));
+    let watcher = StatusWatcher::new(client(stored.clone(), down.clone()), config());
+    let mut events = watcher.subscribe();
+
+    assert!(watcher.poll().await.is_empty());
+    stored.store(500, Ordering::SeqCst);
+    watcher.poll().await;
+    assert_eq!(
+        events.recv().await.unwrap(),
+        WatchEvent::BelowThreshold {
+            sae_id: "Test_2SAE".to_string(),
+            threshold: 1000,
+            stored_key_count: 500
+        }
+    );
+    let stats = watcher.stats("Test_2SAE").unwrap();
+    assert_eq!(stats.stored_key_count, 500);
+    assert!(stats.rate.unwrap() < 0.0);
+
+    down.store(true, Ordering::SeqCst);
+    watcher.poll().await;
+    assert!(
+        matches!(events.recv().await.unwrap(), WatchEvent::Unreachable { sae_id, .. } if sae_id == "Test_2SAE")
+    );
+    assert!(watcher.stats("Test_2SAE").unwrap().unreachable);
+
+    down.store(false, Ordering::SeqCst);
+    stored.store(1500, Ordering::SeqCst);
+    watcher.poll().await;
+    assert!(matches!(
+        events.recv().await.unwrap(),
+        WatchEvent::Reachable { .. }
+    ));
+    assert!(matches!(
+        events.recv().await.unwrap(),
+        WatchEvent::Recovered {
+            stored_key_count: 1500,
+            ..
+        }
+    ));
+}
+
+#[tokio::test]
+async fn test_watcher_run() {
+    let stored = Arc::new(AtomicU32::new(2000));
+    let watcher = StatusWatcher::new(client(stored.clone(), Arc::default()), config());
+    let mut events = watcher.subscribe();
+    let run = tokio::spawn(watcher.clone().run());
+
+    tokio::time::sleep(Duration::from_millis(30)).await;
+    stored.store(10, Ordering::SeqCst);
+    let event = tokio::time::timeout(Duration::from_secs(1), events.recv())
+        .await
+        .unwrap();
+    assert!(matches!(
+        event.unwrap(),
+        WatchEvent::BelowThreshold {
+            stored_key_count: 10,
+            ..
+        }
+    ));
+    run.abort();
+}